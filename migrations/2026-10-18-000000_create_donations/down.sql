@@ -0,0 +1,4 @@
+DROP TABLE bot_state;
+DROP INDEX deliveries_donation_id;
+DROP TABLE deliveries;
+DROP TABLE donations;
@@ -0,0 +1,27 @@
+CREATE TABLE donations (
+    id TEXT PRIMARY KEY NOT NULL,
+    campaign_id TEXT,
+    amount_value TEXT NOT NULL,
+    amount_currency TEXT NOT NULL,
+    donor_name TEXT,
+    donor_comment TEXT,
+    received_at TIMESTAMP NOT NULL
+);
+
+CREATE TABLE deliveries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    donation_id TEXT REFERENCES donations (id),
+    channel_id TEXT NOT NULL,
+    channel_name TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    status TEXT NOT NULL,
+    error TEXT,
+    attempted_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX deliveries_donation_id ON deliveries (donation_id);
+
+CREATE TABLE bot_state (
+    key TEXT PRIMARY KEY NOT NULL,
+    value TEXT NOT NULL
+);
@@ -0,0 +1,4 @@
+DROP INDEX webhook_attempts_delivery_id;
+DROP TABLE webhook_attempts;
+DROP INDEX webhook_deliveries_event_key;
+DROP TABLE webhook_deliveries;
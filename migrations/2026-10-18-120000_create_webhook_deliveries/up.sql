@@ -0,0 +1,22 @@
+CREATE TABLE webhook_deliveries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    webhook TEXT NOT NULL,
+    event TEXT NOT NULL,
+    event_key TEXT,
+    url TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE UNIQUE INDEX webhook_deliveries_event_key ON webhook_deliveries (webhook, event_key);
+
+CREATE TABLE webhook_attempts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    delivery_id INTEGER NOT NULL REFERENCES webhook_deliveries (id),
+    status_code INTEGER,
+    error TEXT,
+    attempted_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX webhook_attempts_delivery_id ON webhook_attempts (delivery_id);
@@ -0,0 +1,2 @@
+ALTER TABLE donations DROP COLUMN comment_reason;
+ALTER TABLE donations DROP COLUMN comment_status;
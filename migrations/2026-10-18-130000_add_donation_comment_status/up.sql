@@ -0,0 +1,2 @@
+ALTER TABLE donations ADD COLUMN comment_status TEXT;
+ALTER TABLE donations ADD COLUMN comment_reason TEXT;
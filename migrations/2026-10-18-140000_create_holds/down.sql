@@ -0,0 +1,3 @@
+DROP INDEX holds_status;
+DROP INDEX holds_donation;
+DROP TABLE holds;
@@ -0,0 +1,12 @@
+CREATE TABLE holds (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    donation_id TEXT NOT NULL REFERENCES donations (id),
+    reasons TEXT NOT NULL,
+    status TEXT NOT NULL,
+    held_at TIMESTAMP NOT NULL,
+    decided_at TIMESTAMP,
+    decided_by TEXT
+);
+
+CREATE UNIQUE INDEX holds_donation ON holds (donation_id);
+CREATE INDEX holds_status ON holds (status, held_at);
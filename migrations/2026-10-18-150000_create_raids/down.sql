@@ -0,0 +1,2 @@
+DROP INDEX raids_raided_at;
+DROP TABLE raids;
@@ -0,0 +1,11 @@
+CREATE TABLE raids (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    from_id TEXT NOT NULL,
+    from_name TEXT NOT NULL,
+    to_id TEXT NOT NULL,
+    to_name TEXT NOT NULL,
+    viewers BIGINT NOT NULL,
+    raided_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX raids_raided_at ON raids (raided_at);
@@ -0,0 +1,2 @@
+DROP INDEX streams_channel;
+DROP TABLE streams;
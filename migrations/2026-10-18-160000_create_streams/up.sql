@@ -0,0 +1,9 @@
+CREATE TABLE streams (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    channel_id TEXT NOT NULL,
+    channel_name TEXT NOT NULL,
+    started_at TIMESTAMP NOT NULL,
+    ended_at TIMESTAMP
+);
+
+CREATE INDEX streams_channel ON streams (channel_id, started_at);
@@ -0,0 +1,2 @@
+DROP INDEX attributions_channel;
+DROP TABLE attributions;
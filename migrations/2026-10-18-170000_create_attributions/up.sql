@@ -0,0 +1,10 @@
+CREATE TABLE attributions (
+    donation_id TEXT NOT NULL REFERENCES donations (id),
+    channel_id TEXT NOT NULL,
+    channel_name TEXT NOT NULL,
+    live BOOLEAN NOT NULL,
+    credited BOOLEAN NOT NULL,
+    PRIMARY KEY (donation_id, channel_id)
+);
+
+CREATE INDEX attributions_channel ON attributions (channel_id);
@@ -0,0 +1 @@
+ALTER TABLE donations ADD COLUMN team_event_id TEXT;
@@ -0,0 +1,3 @@
+DROP INDEX reward_claims_status;
+DROP INDEX reward_claims_donation;
+DROP TABLE reward_claims;
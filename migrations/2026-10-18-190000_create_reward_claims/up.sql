@@ -0,0 +1,18 @@
+CREATE TABLE reward_claims (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    donation_id TEXT NOT NULL REFERENCES donations (id),
+    donor_name TEXT NOT NULL,
+    reward_id TEXT NOT NULL,
+    reward_name TEXT NOT NULL,
+    quantity INTEGER NOT NULL,
+    response TEXT,
+    channel_id TEXT,
+    channel_name TEXT,
+    status TEXT NOT NULL,
+    claimed_at TIMESTAMP NOT NULL,
+    done_at TIMESTAMP,
+    done_by TEXT
+);
+
+CREATE INDEX reward_claims_donation ON reward_claims (donation_id);
+CREATE INDEX reward_claims_status ON reward_claims (status, claimed_at);
@@ -0,0 +1,2 @@
+ALTER TABLE donations DROP COLUMN poll_option_id;
+ALTER TABLE donations DROP COLUMN poll_id;
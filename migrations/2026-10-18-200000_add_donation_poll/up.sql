@@ -0,0 +1,2 @@
+ALTER TABLE donations ADD COLUMN poll_id TEXT;
+ALTER TABLE donations ADD COLUMN poll_option_id TEXT;
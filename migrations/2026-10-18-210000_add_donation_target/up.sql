@@ -0,0 +1 @@
+ALTER TABLE donations ADD COLUMN target_id TEXT;
@@ -0,0 +1 @@
+DROP TABLE reached_targets;
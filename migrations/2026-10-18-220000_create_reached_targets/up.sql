@@ -0,0 +1,6 @@
+CREATE TABLE reached_targets (
+    target_id TEXT PRIMARY KEY NOT NULL,
+    target_name TEXT NOT NULL,
+    donation_id TEXT REFERENCES donations (id),
+    reached_at TIMESTAMP NOT NULL
+);
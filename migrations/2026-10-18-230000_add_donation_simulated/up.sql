@@ -0,0 +1 @@
+ALTER TABLE donations ADD COLUMN simulated BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE holds ADD COLUMN comment_only BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,184 @@
+use crate::bot::auth::{Channel, LiveStream};
+use crate::config::TeamConfig;
+use crate::db::models::NewAttribution;
+use crate::routes::tiltify::TiltifyDonation;
+
+/// The participant whose supporting campaign a donation went to, from `[team.campaigns]` or
+/// their `campaign_id` setting.
+pub fn campaign_owner<'a>(
+    channels: &'a [Channel],
+    team: &TeamConfig,
+    campaign_id: Option<&str>,
+    team_event_id: Option<&str>,
+) -> Option<&'a Channel> {
+    let campaign_id = campaign_id.filter(|_| team.includes(team_event_id))?;
+    team.campaigns
+        .get(campaign_id)
+        .and_then(|name| channels.iter().find(|c| c.matches(name)))
+        .or_else(|| {
+            channels
+                .iter()
+                .find(|c| c.settings.campaign_id.as_deref() == Some(campaign_id))
+        })
+}
+
+/// The participant a donation is credited to: the one owning its target, else its reward,
+/// else its campaign.
+pub fn credited<'a>(
+    donation: &TiltifyDonation,
+    channels: &'a [Channel],
+    team: &TeamConfig,
+) -> Option<&'a Channel> {
+    let owns = |id: &Option<String>, ids: fn(&Channel) -> &[String]| {
+        id.as_ref()
+            .and_then(|id| channels.iter().find(|c| ids(c).contains(id)))
+    };
+    owns(&donation.target_id, |c| &c.settings.targets)
+        .or_else(|| owns(&donation.reward_id, |c| &c.settings.rewards))
+        .or_else(|| {
+            campaign_owner(
+                channels,
+                team,
+                donation.campaign_id.as_deref(),
+                donation.team_event_id.as_deref(),
+            )
+        })
+}
+
+/// Attributes a donation to every participant that was live and to the one it is credited to.
+pub fn attribute<'a>(
+    donation: &'a TiltifyDonation,
+    channels: &'a [Channel],
+    team: &TeamConfig,
+    live: &'a [LiveStream],
+) -> Vec<NewAttribution<'a>> {
+    let credited = credited(donation, channels, team);
+    let is_credited = |channel: &Channel| credited.is_some_and(|c| c.user_id == channel.user_id);
+    let mut attributions: Vec<NewAttribution> = live
+        .iter()
+        .map(|stream| NewAttribution {
+            donation_id: &donation.id,
+            channel_id: stream.channel.user_id.as_str(),
+            channel_name: stream.channel.name.as_str(),
+            live: true,
+            credited: is_credited(&stream.channel),
+        })
+        .collect();
+    if let Some(channel) = credited
+        && !attributions.iter().any(|a| a.credited)
+    {
+        attributions.push(NewAttribution {
+            donation_id: &donation.id,
+            channel_id: channel.user_id.as_str(),
+            channel_name: channel.name.as_str(),
+            live: false,
+            credited: true,
+        });
+    }
+    attributions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Store;
+    use std::collections::BTreeMap;
+
+    fn channel(name: &str, settings: serde_json::Value) -> Channel {
+        serde_json::from_value(serde_json::json!({
+            "user_id": name,
+            "name": name,
+            "settings": settings,
+        }))
+        .unwrap()
+    }
+
+    fn channels() -> Vec<Channel> {
+        vec![
+            channel(
+                "alice",
+                serde_json::json!({ "campaign_id": "alice-campaign" }),
+            ),
+            channel(
+                "bob",
+                serde_json::json!({ "targets": ["blindfold"], "rewards": ["song"] }),
+            ),
+            channel("carol", serde_json::json!({})),
+        ]
+    }
+
+    #[test]
+    fn credits_target_then_reward_then_campaign() {
+        let channels = channels();
+        let team = TeamConfig {
+            campaigns: BTreeMap::from([("carol-campaign".to_string(), "carol".to_string())]),
+            ..TeamConfig::default()
+        };
+        let credited_name = |donation: &TiltifyDonation| {
+            credited(donation, &channels, &team).map(|c| c.name.to_string())
+        };
+
+        let mut to_alice = TiltifyDonation::test("1", "5.00");
+        to_alice.campaign_id = Some("alice-campaign".to_string());
+        assert_eq!(credited_name(&to_alice).as_deref(), Some("alice"));
+        to_alice.reward_id = Some("song".to_string());
+        assert_eq!(credited_name(&to_alice).as_deref(), Some("bob"));
+        assert_eq!(credited_name(&TiltifyDonation::test("2", "5.00")), None);
+        let mut to_carol = TiltifyDonation::test("3", "5.00");
+        to_carol.campaign_id = Some("carol-campaign".to_string());
+        assert_eq!(credited_name(&to_carol).as_deref(), Some("carol"));
+        to_carol.team_event_id = Some("last-year".to_string());
+        let team = TeamConfig {
+            event_id: Some("this-year".to_string()),
+            ..team
+        };
+        assert!(credited(&to_carol, &channels, &team).is_none());
+    }
+
+    #[tokio::test]
+    async fn totals_raised_while_live() {
+        let channels = channels();
+        let live = |names: &[&str]| -> Vec<LiveStream> {
+            names
+                .iter()
+                .map(|name| LiveStream {
+                    channel: channels
+                        .iter()
+                        .find(|c| c.name.as_str() == *name)
+                        .unwrap()
+                        .clone(),
+                    viewers: 10,
+                })
+                .collect()
+        };
+        let store = Store::open(":memory:").unwrap();
+        let mut to_bob = TiltifyDonation::test("1", "20.00");
+        to_bob.target_id = Some("blindfold".to_string());
+        let while_both_live = live(&["alice", "carol"]);
+        let while_alice_live = live(&["alice"]);
+        let donations = [
+            (to_bob, &while_both_live),
+            (TiltifyDonation::test("2", "5.00"), &while_alice_live),
+        ];
+
+        for (donation, live) in &donations {
+            store.insert_donation(donation).await.unwrap();
+            let attributions = attribute(donation, &channels, &TeamConfig::default(), live);
+            store.attribute(&attributions).await.unwrap();
+            store.attribute(&attributions).await.unwrap();
+        }
+        let raised = store.raised_by_channel(None).await.unwrap();
+
+        let names: Vec<&str> = raised.iter().map(|r| r.channel_name.as_str()).collect();
+        assert_eq!(names, ["alice", "carol", "bob"]);
+        assert_eq!(raised[0].live_donations, 2);
+        assert_eq!(
+            raised[0].summary(),
+            "alice raised $25.00 from 2 donations while live"
+        );
+        assert_eq!(
+            raised[2].summary(),
+            "bob raised $0.00 from 0 donations while live, plus $20.00 credited to them"
+        );
+    }
+}
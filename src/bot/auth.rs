@@ -1,13 +1,17 @@
+use crate::bot::helix;
+use crate::bot::settings::ChannelSettings;
+use crate::config::TwitchConfig;
+use crate::metrics;
 use eyre::Report;
 use serde_derive::{Deserialize, Serialize};
-use std::env;
 use std::fmt::Debug;
 use std::io::{Read, Write};
 use std::path::Path;
-use tracing::{error, info, Instrument};
+use std::time::Instant;
+use tracing::{error, info, warn, Instrument};
 use twitch_api::client::CompatError;
 use twitch_api::helix::streams::StreamType;
-use twitch_api::types::{UserId, UserName};
+use twitch_api::types::{UserId, UserName, UserNameRef};
 use twitch_api::{HelixClient, TwitchClient};
 use twitch_oauth2::{AccessToken, TwitchToken};
 use twitch_oauth2::AppAccessToken;
@@ -42,24 +46,51 @@ impl Channels {
         }
     }
 
-    #[tracing::instrument(skip(self, client))]
+    #[tracing::instrument(skip(self, client, twitch))]
     pub async fn get_live_channels(
         &self,
         client: &HelixClient<'_, reqwest::Client>,
+        twitch: &TwitchConfig,
         token: &UserToken,
     ) -> Vec<Channel> {
+        self.get_live_streams(client, twitch, token)
+            .await
+            .into_iter()
+            .map(|s| s.channel)
+            .collect()
+    }
+
+    /// The participating channels that are live, with their viewer counts.
+    #[tracing::instrument(skip(self, client, twitch))]
+    pub async fn get_live_streams(
+        &self,
+        client: &HelixClient<'_, reqwest::Client>,
+        twitch: &TwitchConfig,
+        token: &UserToken,
+    ) -> Vec<LiveStream> {
         let all: Vec<UserId> = self.0.iter().map(|c| c.user_id.clone()).collect();
         let req = twitch_api::helix::streams::get_streams::GetStreamsRequest::user_ids(all);
-        match client.req_get(req, token).in_current_span().await {
-            Ok(res) => res
-                .data
-                .iter()
-                .filter(|s| s.type_ == StreamType::Live)
-                .map(|s| Channel {
-                    name: s.user_login.clone(),
-                    user_id: s.user_id.clone(),
-                })
-                .collect::<Vec<Channel>>(),
+        let started = Instant::now();
+        let res = helix::get(client, twitch, req, token).in_current_span().await;
+        metrics::observe_helix("get_streams", started, &res);
+        match res {
+            Ok(res) => {
+                let live = res
+                    .data
+                    .iter()
+                    .filter(|s| s.type_ == StreamType::Live)
+                    .map(|s| LiveStream {
+                        channel: Channel {
+                            name: s.user_login.clone(),
+                            user_id: s.user_id.clone(),
+                            settings: ChannelSettings::default(),
+                        },
+                        viewers: s.viewer_count,
+                    })
+                    .collect::<Vec<LiveStream>>();
+                metrics::LIVE_CHANNELS.set(live.len() as i64);
+                live
+            }
             Err(e) => {
                 error!("{e:?}");
                 Vec::new()
@@ -67,20 +98,25 @@ impl Channels {
         }
     }
 
-    #[tracing::instrument(skip(self, client))]
+    #[tracing::instrument(skip(self, client, twitch))]
     pub async fn get_moderated_channels(
         &self,
         client: &HelixClient<'_, reqwest::Client>,
+        twitch: &TwitchConfig,
         token: &UserToken,
     ) -> Vec<Channel> {
         let req = twitch_api::helix::moderation::get_moderated_channels::GetModeratedChannelsRequest::user_id(&token.user_id).first(100);
-        match client.req_get(req, token).in_current_span().await {
+        let started = Instant::now();
+        let res = helix::get(client, twitch, req, token).in_current_span().await;
+        metrics::observe_helix("get_moderated_channels", started, &res);
+        match res {
             Ok(res) => res
                 .data
                 .iter()
                 .map(|s| Channel {
                     name: s.broadcaster_login.clone(),
                     user_id: s.broadcaster_id.clone(),
+                    settings: ChannelSettings::default(),
                 })
                 .collect::<Vec<Channel>>(),
             Err(e) => {
@@ -90,32 +126,87 @@ impl Channels {
         }
     }
     
+    /// Finds a channel by user id or login name.
+    pub fn find(&self, id_or_name: &str) -> Option<&Channel> {
+        self.0.iter().find(|c| c.matches(id_or_name))
+    }
+
+    pub fn find_mut(&mut self, id_or_name: &str) -> Option<&mut Channel> {
+        self.0.iter_mut().find(|c| c.matches(id_or_name))
+    }
+
+    /// Updates login names of channels that were renamed, returning `(old, new)` pairs.
+    #[tracing::instrument(skip(self, client, twitch, token))]
+    pub async fn refresh_names(
+        &mut self,
+        client: &HelixClient<'_, reqwest::Client>,
+        twitch: &TwitchConfig,
+        token: &UserToken,
+    ) -> Result<Vec<(UserName, UserName)>, Report> {
+        let mut renamed = Vec::new();
+        let ids: Vec<UserId> = self.0.iter().map(|c| c.user_id.clone()).collect();
+        for chunk in ids.chunks(100) {
+            let req = twitch_api::helix::users::GetUsersRequest::ids(chunk);
+            let started = Instant::now();
+            let res = helix::get(client, twitch, req, token).in_current_span().await;
+            metrics::observe_helix("get_users", started, &res);
+            for user in res?.data {
+                if let Some(channel) = self.0.iter_mut().find(|c| c.user_id == user.id)
+                    && channel.name != user.login
+                {
+                    renamed.push((channel.name.clone(), user.login.clone()));
+                    channel.name = user.login;
+                }
+            }
+        }
+        Ok(renamed)
+    }
+
+    /// Adds a channel, returning `false` when it is already present.
+    pub fn add(&mut self, channel: Channel) -> bool {
+        if self.find(channel.user_id.as_str()).is_some() {
+            return false;
+        }
+        self.0.push(channel);
+        true
+    }
+
+    /// Removes a channel by user id or login name.
+    pub fn remove(&mut self, id_or_name: &str) -> Option<Channel> {
+        let index = self.0.iter().position(|c| c.matches(id_or_name))?;
+        Some(self.0.remove(index))
+    }
+
     pub async fn get_moderated_live_channels(
         &self,
         client: &HelixClient<'_, reqwest::Client>,
+        twitch: &TwitchConfig,
         token: &UserToken,
     ) -> Vec<Channel> {
-        let (live, moderated) = tokio::join!(self.get_live_channels(client, token), self.get_moderated_channels(client, token));
+        let (live, moderated) = tokio::join!(self.get_live_channels(client, twitch, token), self.get_moderated_channels(client, twitch, token));
         info!("Found {} live and {} moderated channels", live.len(), moderated.len());
         info!("Live: {}", live.iter().map(|c| c.name.clone().to_string()).collect::<Vec<String>>().join(" "));
         info!("Moderated: {}", moderated.iter().map(|c| c.name.clone().to_string()).collect::<Vec<String>>().join(" "));
         let mut channels: Vec<Channel> = Vec::new();
         for channel in moderated {
-            if live.iter().any(|live| live.user_id == channel.user_id) {
-                channels.push(channel);
+            if !live.iter().any(|live| live.user_id == channel.user_id) {
+                continue;
+            }
+            // Keep the stored settings, but take the login name Helix knows now.
+            if let Some(stored) = self.0.iter().find(|c| c.user_id == channel.user_id) {
+                channels.push(Channel {
+                    name: channel.name,
+                    ..stored.clone()
+                });
             }
         }
         channels
     }
 }
 
-impl Into<Vec<UserId>> for Channels {
-    fn into(self) -> Vec<UserId> {
-        let mut channels: Vec<UserId> = Vec::new();
-        for channel in self.0 {
-            channels.push(channel.into());
-        }
-        channels
+impl From<Channels> for Vec<UserId> {
+    fn from(channels: Channels) -> Self {
+        channels.0.into_iter().map(UserId::from).collect()
     }
 }
 
@@ -123,17 +214,116 @@ impl Into<Vec<UserId>> for Channels {
 pub struct Channel {
     pub user_id: UserId,
     pub name: UserName,
+    #[serde(default, skip_serializing_if = "ChannelSettings::is_default")]
+    pub settings: ChannelSettings,
+}
+
+/// A participating channel that is live.
+#[derive(Serialize, Debug, Clone)]
+pub struct LiveStream {
+    #[serde(flatten)]
+    pub channel: Channel,
+    pub viewers: usize,
 }
 
-impl Into<UserId> for Channel {
-    fn into(self) -> UserId {
-        self.user_id
+impl From<Channel> for UserId {
+    fn from(channel: Channel) -> Self {
+        channel.user_id
     }
 }
 
+impl Channel {
+    pub fn matches(&self, id_or_name: &str) -> bool {
+        self.user_id.as_str() == id_or_name || self.name.as_str().eq_ignore_ascii_case(id_or_name)
+    }
+
+    /// Looks up a channel by its login name through Helix GetUsers.
+    #[tracing::instrument(skip(client, twitch, token))]
+    pub async fn resolve(
+        client: &HelixClient<'_, reqwest::Client>,
+        twitch: &TwitchConfig,
+        token: &UserToken,
+        login: &str,
+    ) -> Result<Option<Channel>, Report> {
+        let logins: &[&UserNameRef] = &[login.into()];
+        let req = twitch_api::helix::users::GetUsersRequest::logins(logins);
+        let started = Instant::now();
+        let res = helix::get(client, twitch, req, token).in_current_span().await;
+        metrics::observe_helix("get_users", started, &res);
+        Ok(res?.data.into_iter().next().map(|u| Channel {
+            user_id: u.id,
+            name: u.login,
+            settings: ChannelSettings::default(),
+        }))
+    }
+}
+
+/// Streamers who logged in so the bot can act in their channels with their own token.
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Streamers(pub Vec<User>);
 
+impl Streamers {
+    #[tracing::instrument(skip(path))]
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Report> {
+        let mut file = std::fs::File::create(path)?;
+        let contents = serde_json::to_string(&self)?;
+
+        Ok(file.write_all(contents.as_bytes())?)
+    }
+
+    #[tracing::instrument(skip(path))]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Report> {
+        let mut file = std::fs::File::open(path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn find(&self, user_id: &str) -> Option<&User> {
+        self.0.iter().find(|s| s.user_id.as_str() == user_id)
+    }
+
+    pub fn find_mut(&mut self, user_id: &str) -> Option<&mut User> {
+        self.0.iter_mut().find(|s| s.user_id.as_str() == user_id)
+    }
+
+    /// Adds a streamer, replacing an earlier login of the same account.
+    pub fn upsert(&mut self, streamer: User) {
+        match self.find_mut(streamer.user_id.as_str()) {
+            Some(existing) => *existing = streamer,
+            None => self.0.push(streamer),
+        }
+    }
+
+    /// A usable token of the broadcaster of `channel`, refreshed when it elapsed. The file at
+    /// `path` is read again when the streamer isn't known yet, they may have logged in since.
+    pub async fn token_for(
+        &mut self,
+        client: &HelixClient<'static, reqwest::Client>,
+        twitch: &TwitchConfig,
+        path: &str,
+        channel: &Channel,
+    ) -> Result<UserToken, Report> {
+        if self.find(channel.user_id.as_str()).is_none() {
+            *self = Streamers::load(path).unwrap_or_default();
+        }
+        let streamer = self
+            .find_mut(channel.user_id.as_str())
+            .ok_or_else(|| eyre::eyre!("{} hasn't logged in with `auth streamer`", channel.name))?;
+        streamer.ensure_token(client, twitch).await?;
+        let token = streamer
+            .user_token
+            .as_mut()
+            .ok_or_else(|| eyre::eyre!("streamer has no token"))?;
+        if token.is_elapsed() {
+            token.refresh_token(client).await?;
+            streamer.access_token = Some(token.access_token.clone());
+            streamer.refresh_token = token.refresh_token.clone();
+        }
+        Ok(token.clone())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct User {
     pub user_id: UserId,
@@ -154,9 +344,14 @@ pub enum UserError {
 }
 
 impl User {
-    #[allow(unused)]
+    /// Scopes a streamer grants through `auth streamer`.
     pub fn user_scopes() -> Vec<Scope> {
-        vec![Scope::ChannelBot, Scope::ModeratorManageAnnouncements]
+        vec![
+            Scope::ChannelBot,
+            Scope::ModeratorManageAnnouncements,
+            Scope::ModerationRead,
+            Scope::ModeratorManageShoutouts,
+        ]
     }
 
     #[allow(unused)]
@@ -190,15 +385,49 @@ impl User {
         }
     }
 
-    #[tracing::instrument(skip(self, client))]
-    pub async fn new_user_token(
-        &mut self,
+    /// Loads a stored user, logging in again when it has no usable tokens.
+    #[tracing::instrument(skip(client, twitch))]
+    pub async fn load_or_login(
+        path: impl AsRef<Path> + Debug,
         client: &HelixClient<'_, reqwest::Client>,
-    ) -> Result<UserToken, Report> {
-        let client_id = env::var("CLIENT_ID").expect("CLIENT_ID not set");
+        twitch: &TwitchConfig,
+    ) -> Result<Self, Report> {
+        let Ok(mut user) = Self::load(path) else {
+            return Self::login(client, twitch).await;
+        };
+        match user.ensure_token(client, twitch).await {
+            Ok(()) => Ok(user),
+            Err(e) => {
+                warn!("Stored tokens of {} are unusable, logging in again: {e}", user.twitch_name);
+                Self::login(client, twitch).await
+            }
+        }
+    }
+
+    /// Checks that the user is the account configured in `twitch.bot_user_*`.
+    pub fn check_account(&self, twitch: &TwitchConfig) -> Result<(), Report> {
+        if let Some(id) = &twitch.bot_user_id
+            && self.user_id.as_str() != id
+        {
+            eyre::bail!("logged in as user id {}, but twitch.bot_user_id is {id}", self.user_id);
+        }
+        if let Some(name) = &twitch.bot_user_name
+            && !self.twitch_name.as_str().eq_ignore_ascii_case(name)
+        {
+            eyre::bail!("logged in as {}, but twitch.bot_user_name is {name}", self.twitch_name);
+        }
+        Ok(())
+    }
 
-        let mut builder = twitch_oauth2::tokens::DeviceUserTokenBuilder::new(
-            client_id.clone(),
+    /// Logs the bot in through the device flow and returns it with a refreshable token.
+    #[tracing::instrument(skip(client, twitch))]
+    pub async fn login(
+        client: &HelixClient<'_, reqwest::Client>,
+        twitch: &TwitchConfig,
+    ) -> Result<Self, Report> {
+        Self::login_with_scopes(
+            client,
+            twitch,
             vec![
                 Scope::UserBot,
                 Scope::ChannelBot,
@@ -207,7 +436,49 @@ impl User {
                 Scope::ModeratorManageAnnouncements,
                 Scope::UserReadModeratedChannels,
             ],
-        );
+        )
+        .await
+    }
+
+    /// Logs a streamer in through the device flow with [`User::user_scopes`].
+    #[tracing::instrument(skip(client, twitch))]
+    pub async fn login_streamer(
+        client: &HelixClient<'_, reqwest::Client>,
+        twitch: &TwitchConfig,
+    ) -> Result<Self, Report> {
+        Self::login_with_scopes(client, twitch, Self::user_scopes()).await
+    }
+
+    async fn login_with_scopes(
+        client: &HelixClient<'_, reqwest::Client>,
+        twitch: &TwitchConfig,
+        scopes: Vec<Scope>,
+    ) -> Result<Self, Report> {
+        let mut user = User {
+            user_id: UserId::new(String::new()),
+            twitch_name: UserName::new(String::new()),
+            access_token: None,
+            refresh_token: None,
+            expires_in: None,
+            user_token: None,
+        };
+        let token = user.new_user_token(client, twitch, scopes).await?;
+        user.user_id = token.user_id.clone();
+        user.twitch_name = token.login.clone();
+        // The device flow token has no client secret, so rebuild it to be able to refresh it.
+        user.ensure_token(client, twitch).await?;
+        Ok(user)
+    }
+
+    #[tracing::instrument(skip(self, client, twitch))]
+    pub async fn new_user_token(
+        &mut self,
+        client: &HelixClient<'_, reqwest::Client>,
+        twitch: &TwitchConfig,
+        scopes: Vec<Scope>,
+    ) -> Result<UserToken, Report> {
+        let mut builder =
+            twitch_oauth2::tokens::DeviceUserTokenBuilder::new(twitch.client_id.clone(), scopes);
         let code = builder
             .start(client)
             .await
@@ -224,13 +495,12 @@ impl User {
         Ok(token)
     }
 
-    #[tracing::instrument(skip(self, client))]
+    #[tracing::instrument(skip(self, client, twitch))]
     pub async fn ensure_token(
         &mut self,
         client: &HelixClient<'_, reqwest::Client>,
+        twitch: &TwitchConfig,
     ) -> Result<(), UserError> {
-        let client_id = env::var("CLIENT_ID").expect("CLIENT_ID not set");
-        let client_secret = env::var("CLIENT_SECRET").expect("CLIENT_SECRET not set");
         if self.access_token.is_none() && self.refresh_token.is_none() {
             return Err(UserError::NoTokens);
         }
@@ -239,8 +509,8 @@ impl User {
                 client,
                 self.access_token.clone().unwrap(),
                 self.refresh_token.clone().unwrap(),
-                ClientId::new(client_id),
-                ClientSecret::new(client_secret),
+                ClientId::new(twitch.client_id.clone()),
+                ClientSecret::new(twitch.client_secret.clone()),
             )
             .await
             {
@@ -272,7 +542,7 @@ impl User {
                 }
             };
         }
-        Err(UserError::NoTokens)
+        Ok(())
     }
 }
 
@@ -0,0 +1,138 @@
+use crate::config::DonorsConfig;
+use crate::routes::tiltify::TiltifyDonation;
+
+/// Longest run of one repeated character kept in a display name.
+const MAX_REPEATED_CHARS: usize = 3;
+
+/// The name a donation is announced under, following `[donors]`.
+pub fn display_name(config: &DonorsConfig, donation: &TiltifyDonation) -> String {
+    let anonymous = || config.anonymous_name.clone();
+    if let Ok(amount) = donation.amount.value.parse::<f64>()
+        && (config.anonymous_below.is_some_and(|below| amount < below)
+            || config.anonymous_above.is_some_and(|above| amount > above))
+    {
+        return anonymous();
+    }
+    let Some(name) = donation.name.as_deref().map(str::trim) else {
+        return anonymous();
+    };
+    if let Some((_, alias)) = config
+        .aliases
+        .iter()
+        .find(|(donor, _)| donor.eq_ignore_ascii_case(name))
+    {
+        return alias.clone();
+    }
+    let name = sanitize_name(name, config.max_name_length);
+    if name.is_empty() { anonymous() } else { name }
+}
+
+/// Makes a donor-chosen name safe to post: drops control characters, links and @mentions,
+/// collapses emote spam and whitespace and cuts it to `max_length` characters.
+pub fn sanitize_name(name: &str, max_length: usize) -> String {
+    let without_controls: String = name
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    let mut words: Vec<String> = Vec::new();
+    for word in without_controls.split_whitespace() {
+        if word.starts_with('@') || is_link(word) {
+            continue;
+        }
+        let word = collapse_repeats(word);
+        // The same word over and over is emote spam.
+        if words.last().is_some_and(|last| last.eq_ignore_ascii_case(&word)) {
+            continue;
+        }
+        words.push(word);
+    }
+    let name = words.join(" ");
+    // Leading slashes and dots could be read as chat commands.
+    let name = name.trim_start_matches(['/', '.']).trim_start();
+    name.chars()
+        .take(max_length)
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+fn collapse_repeats(word: &str) -> String {
+    let mut collapsed = String::with_capacity(word.len());
+    let mut run = 0;
+    let mut previous = None;
+    for c in word.chars() {
+        run = if previous == Some(c) { run + 1 } else { 1 };
+        previous = Some(c);
+        if run <= MAX_REPEATED_CHARS {
+            collapsed.push(c);
+        }
+    }
+    collapsed
+}
+
+/// Whether a word looks like a link, with or without a scheme.
+pub fn is_link(word: &str) -> bool {
+    if word.contains("://") || word.to_lowercase().starts_with("www.") {
+        return true;
+    }
+    let host = word
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .trim_matches(|c: char| !c.is_alphanumeric());
+    let labels: Vec<&str> = host.split('.').collect();
+    labels.len() > 1
+        && labels
+            .iter()
+            .all(|l| !l.is_empty() && l.chars().all(|c| c.is_alphanumeric() || c == '-'))
+        && labels.last().is_some_and(|tld| {
+            (2..=24).contains(&tld.len()) && tld.chars().all(char::is_alphabetic)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitizes_names() {
+        assert_eq!(sanitize_name("  Carol \t Smith ", 25), "Carol Smith");
+        assert_eq!(sanitize_name("Carol\u{0}\u{7}x", 25), "Carol x");
+        assert_eq!(sanitize_name("visit twitch.tv/carol @bob now", 25), "visit now");
+        assert_eq!(sanitize_name("Kappa Kappa kappa Kappa", 25), "Kappa");
+        assert_eq!(sanitize_name("LOOOOOOOL", 25), "LOOOL");
+        assert_eq!(sanitize_name("/ban carol", 25), "ban carol");
+        assert_eq!(sanitize_name("A very long name indeed", 6), "A very");
+        assert_eq!(sanitize_name("@everyone", 25), "");
+    }
+
+    #[test]
+    fn recognises_links() {
+        assert!(is_link("https://example.com"));
+        assert!(is_link("twitch.tv/alice"));
+        assert!(is_link("WWW.example"));
+        assert!(!is_link("e.g."));
+        assert!(!is_link("3.50"));
+    }
+
+    #[test]
+    fn picks_display_names() {
+        let config = DonorsConfig {
+            aliases: [("carol smith".to_string(), "Carol".to_string())].into(),
+            anonymous_above: Some(1000.0),
+            anonymous_below: Some(1.0),
+            ..DonorsConfig::default()
+        };
+        let donation = |amount, name: Option<&str>| TiltifyDonation {
+            name: name.map(str::to_string),
+            ..TiltifyDonation::test("donation", amount)
+        };
+
+        assert_eq!(display_name(&config, &donation("5.00", Some("Carol Smith"))), "Carol");
+        assert_eq!(display_name(&config, &donation("5.00", Some("Bob"))), "Bob");
+        assert_eq!(display_name(&config, &donation("5.00", None)), "an anonymous user");
+        assert_eq!(display_name(&config, &donation("5.00", Some("@x"))), "an anonymous user");
+        assert_eq!(display_name(&config, &donation("0.50", Some("Bob"))), "an anonymous user");
+        assert_eq!(display_name(&config, &donation("5000", Some("Bob"))), "an anonymous user");
+    }
+}
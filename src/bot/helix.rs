@@ -0,0 +1,118 @@
+use crate::config::TwitchConfig;
+use axum::http::Uri;
+use twitch_api::client::ResponseExt;
+use twitch_api::helix::{
+    ClientRequestError, CreateRequestError, HelixRequestBody, InvalidUri, Request, RequestDelete,
+    RequestGet, RequestPost, Response,
+};
+use twitch_api::{HelixClient, HttpClient};
+use twitch_oauth2::TwitchToken;
+
+type HelixResult<R, D> = Result<Response<R, D>, ClientRequestError<reqwest::Error>>;
+
+/// Like [`HelixClient::req_get`], but against `twitch.helix_url`.
+pub async fn get<R, D, T>(
+    client: &HelixClient<'_, reqwest::Client>,
+    twitch: &TwitchConfig,
+    request: R,
+    token: &T,
+) -> HelixResult<R, D>
+where
+    R: Request<Response = D> + RequestGet,
+    D: serde::de::DeserializeOwned + PartialEq,
+    T: TwitchToken + ?Sized,
+{
+    let mut req = request.create_request(token.token().secret(), token.client_id().as_str())?;
+    *req.uri_mut() = rebase(twitch, req.uri())?;
+    let uri = req.uri().clone();
+    let response = client
+        .get_client()
+        .req(req)
+        .await
+        .map_err(ClientRequestError::RequestError)?
+        .into_response_vec();
+    <R>::parse_response(Some(request), &uri, response).map_err(Into::into)
+}
+
+/// Like [`HelixClient::req_post`], but against `twitch.helix_url`.
+pub async fn post<R, B, D, T>(
+    client: &HelixClient<'_, reqwest::Client>,
+    twitch: &TwitchConfig,
+    request: R,
+    body: B,
+    token: &T,
+) -> HelixResult<R, D>
+where
+    R: Request<Response = D> + RequestPost<Body = B>,
+    B: HelixRequestBody,
+    D: serde::de::DeserializeOwned + PartialEq,
+    T: TwitchToken + ?Sized,
+{
+    let mut req =
+        request.create_request(body, token.token().secret(), token.client_id().as_str())?;
+    *req.uri_mut() = rebase(twitch, req.uri())?;
+    let uri = req.uri().clone();
+    let response = client
+        .get_client()
+        .req(req)
+        .await
+        .map_err(ClientRequestError::RequestError)?
+        .into_response_vec();
+    <R>::parse_response(Some(request), &uri, response).map_err(Into::into)
+}
+
+/// Like [`HelixClient::req_delete`], but against `twitch.helix_url`.
+pub async fn delete<R, D, T>(
+    client: &HelixClient<'_, reqwest::Client>,
+    twitch: &TwitchConfig,
+    request: R,
+    token: &T,
+) -> HelixResult<R, D>
+where
+    R: Request<Response = D> + RequestDelete,
+    D: serde::de::DeserializeOwned + PartialEq,
+    T: TwitchToken + ?Sized,
+{
+    let mut req = request.create_request(token.token().secret(), token.client_id().as_str())?;
+    *req.uri_mut() = rebase(twitch, req.uri())?;
+    let uri = req.uri().clone();
+    let response = client
+        .get_client()
+        .req(req)
+        .await
+        .map_err(ClientRequestError::RequestError)?
+        .into_response_vec();
+    <R>::parse_response(Some(request), &uri, response).map_err(Into::into)
+}
+
+/// Moves a request twitch_api built for its own Helix URL to `twitch.helix_url`.
+fn rebase(twitch: &TwitchConfig, uri: &Uri) -> Result<Uri, CreateRequestError> {
+    let uri = uri.to_string();
+    let Some(rest) = uri.strip_prefix(twitch_api::TWITCH_HELIX_URL.as_str()) else {
+        return Ok(uri.parse().map_err(InvalidUri::from)?);
+    };
+    let base = twitch.helix_url.trim_end_matches('/');
+    Ok(format!("{base}/{rest}").parse().map_err(InvalidUri::from)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebases_requests_onto_the_configured_url() {
+        let twitch = TwitchConfig {
+            helix_url: "http://127.0.0.1:8080/helix".to_string(),
+            ..TwitchConfig::default()
+        };
+        let uri: Uri = format!("{}streams?user_id=1", twitch_api::TWITCH_HELIX_URL.as_str())
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            rebase(&twitch, &uri).unwrap(),
+            "http://127.0.0.1:8080/helix/streams?user_id=1"
+        );
+        assert_eq!(rebase(&TwitchConfig::default(), &uri).unwrap(), uri);
+    }
+}
@@ -0,0 +1,366 @@
+use crate::bot::donors::is_link;
+use crate::config::{Config, HoldsConfig};
+use crate::db::Store;
+use crate::db::models::{Donation, Hold};
+use crate::metrics;
+use crate::routes::tiltify::{TiltifyDonation, TiltifyEventType};
+use crate::routes::webhook::Amount;
+use eyre::{Report, eyre};
+use thiserror::Error;
+use tracing::info;
+
+#[derive(Debug, Error)]
+pub enum HoldError {
+    #[error("hold {0} not found")]
+    NotFound(i32),
+    #[error("hold {0} is already {1}")]
+    AlreadySettled(i32, String),
+    #[error(transparent)]
+    Internal(#[from] Report),
+}
+
+/// Why a donation has to wait for a moderator. Empty when it can be announced right away.
+pub fn hold_reasons(
+    config: &HoldsConfig,
+    donation: &TiltifyDonation,
+    first_time: bool,
+) -> Vec<String> {
+    let texts: Vec<String> = [donation.name.as_deref(), donation.message.as_deref()]
+        .into_iter()
+        .flatten()
+        .map(str::to_lowercase)
+        .collect();
+    let mut reasons = Vec::new();
+    if let Some(word) = config
+        .blocked_words
+        .iter()
+        .find(|word| texts.iter().any(|t| t.contains(&word.to_lowercase())))
+    {
+        reasons.push(format!("contains blocked word {word:?}"));
+    }
+    if config.urls && texts.iter().any(|t| contains_url(t)) {
+        reasons.push("contains a link".to_string());
+    }
+    if let Some(limit) = config.amount_above
+        && donation
+            .amount
+            .value
+            .parse::<f64>()
+            .is_ok_and(|amount| amount > limit)
+    {
+        reasons.push(format!("amount above {limit}"));
+    }
+    if config.first_time_donors && first_time {
+        reasons.push("first-time donor".to_string());
+    }
+    reasons
+}
+
+/// A hold reason without the term it quotes, so chat doesn't repeat what was blocked.
+pub fn redact(reason: &str) -> String {
+    match (reason.find('"'), reason.rfind('"')) {
+        (Some(start), Some(end)) if start < end => {
+            format!("{}\"***\"{}", &reason[..start], &reason[end + 1..])
+        }
+        _ => reason.to_string(),
+    }
+}
+
+fn contains_url(text: &str) -> bool {
+    text.split_whitespace().any(is_link)
+}
+
+/// Approves or rejects a pending hold, returning the donation to announce when it was approved.
+///
+/// Approving also approves a comment that was held, so it is posted with the donation. For a
+/// hold of only the comment, the donation was already announced and only its comment is left.
+pub async fn settle(
+    store: &Store,
+    config: &Config,
+    hold: i32,
+    approve: bool,
+    by: &str,
+) -> Result<(Hold, Option<TiltifyDonation>), HoldError> {
+    let existing = store.hold(hold).await?.ok_or(HoldError::NotFound(hold))?;
+    let status = if approve { "approved" } else { "rejected" };
+    let settled = store
+        .decide_hold(hold, status, by)
+        .await?
+        .ok_or_else(|| HoldError::AlreadySettled(hold, existing.status))?;
+    let donation = stored_donation(store, &settled.donation_id).await?;
+    metrics::HOLDS
+        .with_label_values(&[campaign_label(config, &donation), status])
+        .inc();
+    info!(
+        "Hold {hold} of donation {} {status} by {by}",
+        settled.donation_id
+    );
+    if !approve {
+        return Ok((settled, None));
+    }
+    let with_comment = match donation.comment_status.as_deref() {
+        Some("approved") => true,
+        Some("held") => {
+            store
+                .set_comment_status(&donation.id, "approved", None)
+                .await?;
+            true
+        }
+        _ => false,
+    };
+    Ok((settled, Some(release(donation, with_comment))))
+}
+
+/// Settles holds that waited longer than `holds.timeout_secs`, returning the donations to
+/// announce when they are released. Held comments are never released without a moderator, so
+/// holds of only a comment leave nothing to announce.
+pub async fn expire(store: &Store, config: &Config) -> Result<Vec<TiltifyDonation>, Report> {
+    let timeout = config.holds.timeout_secs.try_into().unwrap_or(i64::MAX);
+    let cutoff = chrono::Utc::now().naive_utc() - chrono::Duration::seconds(timeout);
+    let status = config.holds.on_timeout.status();
+    let mut released = Vec::new();
+    for hold in store.expired_holds(cutoff).await? {
+        let Some(hold) = store.decide_hold(hold.id, status, "timeout").await? else {
+            continue;
+        };
+        let donation = stored_donation(store, &hold.donation_id).await?;
+        metrics::HOLDS
+            .with_label_values(&[campaign_label(config, &donation), status])
+            .inc();
+        info!(
+            "Hold {} of donation {} timed out and was {status}",
+            hold.id, hold.donation_id
+        );
+        if status == "released" && !hold.comment_only {
+            let with_comment = donation.comment_status.as_deref() == Some("approved");
+            released.push(release(donation, with_comment));
+        }
+    }
+    Ok(released)
+}
+
+async fn stored_donation(store: &Store, id: &str) -> Result<Donation, Report> {
+    store
+        .donation(id)
+        .await?
+        .ok_or_else(|| eyre!("held donation {id} is missing"))
+}
+
+fn campaign_label<'a>(config: &'a Config, donation: &Donation) -> &'a str {
+    metrics::donation_campaign_label(
+        config,
+        donation.campaign_id.as_deref(),
+        donation.team_event_id.as_deref(),
+    )
+}
+
+/// Rebuilds a stored donation for announcement, keeping its comment only when `with_comment`.
+fn release(donation: Donation, with_comment: bool) -> TiltifyDonation {
+    TiltifyDonation {
+        id: donation.id,
+        campaign_id: donation.campaign_id,
+        event_type: TiltifyEventType::DonationUpdated,
+        amount: Amount {
+            currency: donation.amount_currency,
+            value: donation.amount_value,
+        },
+        name: donation.donor_name,
+        message: donation.donor_comment.filter(|_| with_comment),
+        target_id: donation.target_id,
+        reward_id: None,
+        team_event_id: donation.team_event_id,
+        reward_claims: Vec::new(),
+        poll_id: donation.poll_id,
+        poll_option_id: donation.poll_option_id,
+        simulated: donation.simulated,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holds_donations_matching_rules() {
+        let config = HoldsConfig {
+            blocked_words: vec!["Scam".to_string()],
+            urls: true,
+            amount_above: Some(100.0),
+            first_time_donors: true,
+            ..HoldsConfig::default()
+        };
+
+        let cheer = TiltifyDonation {
+            name: Some("Carol".to_string()),
+            message: Some("Go!".to_string()),
+            ..TiltifyDonation::test("donation", "5.00")
+        };
+        assert!(hold_reasons(&config, &cheer, false).is_empty());
+        let scammer = TiltifyDonation {
+            name: Some("SCAMMER".to_string()),
+            ..TiltifyDonation::test("donation", "5.00")
+        };
+        assert_eq!(
+            hold_reasons(&config, &scammer, false),
+            vec!["contains blocked word \"Scam\""]
+        );
+        let link = TiltifyDonation {
+            message: Some("see example.com/x".to_string()),
+            ..TiltifyDonation::test("donation", "500.00")
+        };
+        assert_eq!(
+            hold_reasons(&config, &link, true),
+            vec!["contains a link", "amount above 100", "first-time donor"]
+        );
+    }
+
+    #[test]
+    fn redacts_quoted_terms() {
+        assert_eq!(
+            redact("contains blocked word \"Scam\""),
+            "contains blocked word \"***\""
+        );
+        assert_eq!(redact("first-time donor"), "first-time donor");
+    }
+
+    #[tokio::test]
+    async fn approving_releases_held_comment() {
+        let store = Store::open(":memory:").unwrap();
+        let config = Config::default();
+        let held = TiltifyDonation {
+            name: Some("Carol".to_string()),
+            message: Some("A comment".to_string()),
+            ..TiltifyDonation::test("donation", "5.00")
+        };
+        store.insert_donation(&held).await.unwrap();
+        store
+            .set_comment_status(&held.id, "held", Some("rejected by AutoMod"))
+            .await
+            .unwrap();
+        let hold = store
+            .create_hold(&held.id, "comment rejected", false)
+            .await
+            .unwrap();
+
+        let (settled, released) = settle(&store, &config, hold.id, true, "alice").await.unwrap();
+
+        assert_eq!(settled.status, "approved");
+        assert_eq!(released.unwrap().message.as_deref(), Some("A comment"));
+        assert!(matches!(
+            settle(&store, &config, hold.id, false, "bob").await,
+            Err(HoldError::AlreadySettled(_, status)) if status == "approved"
+        ));
+    }
+
+    #[tokio::test]
+    async fn expired_holds_are_released_without_unapproved_comments() {
+        let store = Store::open(":memory:").unwrap();
+        let held = TiltifyDonation {
+            name: Some("Carol".to_string()),
+            message: Some("A comment".to_string()),
+            ..TiltifyDonation::test("donation", "500.00")
+        };
+        store.insert_donation(&held).await.unwrap();
+        store
+            .set_comment_status(&held.id, "held", None)
+            .await
+            .unwrap();
+        store
+            .create_hold(&held.id, "amount above 100", false)
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        let config = Config {
+            holds: HoldsConfig {
+                timeout_secs: 1,
+                on_timeout: crate::config::HoldTimeout::Release,
+                ..HoldsConfig::default()
+            },
+            ..Config::default()
+        };
+
+        let released = expire(&store, &config).await.unwrap();
+
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].message, None);
+        assert!(expire(&store, &config).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn held_comments_alone_wait_without_their_donation() {
+        let store = Store::open(":memory:").unwrap();
+        let held = TiltifyDonation {
+            name: Some("Carol".to_string()),
+            message: Some("A spoiler".to_string()),
+            ..TiltifyDonation::test("donation", "5.00")
+        };
+        store.insert_donation(&held).await.unwrap();
+        store
+            .set_comment_status(&held.id, "held", Some("contains blocked term"))
+            .await
+            .unwrap();
+        let timed_out = store
+            .create_hold(&held.id, "comment contains blocked term", true)
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        let config = Config {
+            holds: HoldsConfig {
+                timeout_secs: 1,
+                on_timeout: crate::config::HoldTimeout::Release,
+                ..HoldsConfig::default()
+            },
+            ..Config::default()
+        };
+
+        // The donation was announced when it came in, so a timeout has nothing to announce.
+        assert!(expire(&store, &config).await.unwrap().is_empty());
+        assert_eq!(store.hold(timed_out.id).await.unwrap().unwrap().status, "released");
+
+        let mut other = held.clone();
+        other.id = "other".to_string();
+        store.insert_donation(&other).await.unwrap();
+        store
+            .set_comment_status(&other.id, "held", Some("contains blocked term"))
+            .await
+            .unwrap();
+        let approved = store
+            .create_hold(&other.id, "comment contains blocked term", true)
+            .await
+            .unwrap();
+        let (settled, released) = settle(&store, &config, approved.id, true, "alice")
+            .await
+            .unwrap();
+        assert!(settled.comment_only);
+        assert_eq!(released.unwrap().message.as_deref(), Some("A spoiler"));
+    }
+
+    #[tokio::test]
+    async fn lists_holds_of_one_campaign() {
+        let store = Store::open(":memory:").unwrap();
+        for (id, campaign_id, team_event_id) in [
+            ("1", Some("spring"), None),
+            ("2", Some("alice-campaign"), Some("spring")),
+            ("3", Some("winter"), None),
+        ] {
+            let donation = TiltifyDonation {
+                campaign_id: campaign_id.map(str::to_string),
+                team_event_id: team_event_id.map(str::to_string),
+                ..TiltifyDonation::test(id, "5.00")
+            };
+            store.insert_donation(&donation).await.unwrap();
+            store.create_hold(id, "first-time donor", false).await.unwrap();
+        }
+
+        let store = &store;
+        let held = |campaign| async move {
+            let holds = store.recent_holds(None, 10, campaign).await.unwrap();
+            let mut ids: Vec<_> = holds.into_iter().map(|h| h.donation.id).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(held(Some("spring")).await, ["1", "2"]);
+        assert_eq!(held(Some("winter")).await, ["3"]);
+        assert_eq!(held(None).await.len(), 3);
+    }
+}
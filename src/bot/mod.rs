@@ -1,25 +1,46 @@
-use crate::Commands;
-use crate::bot::auth::{Channel, Channels, User};
-use crate::config::Config;
+use crate::{Commands, metrics};
+use crate::bot::auth::{Channel, Channels, LiveStream, User};
+use crate::bot::holds::HoldError;
+use crate::bot::raids::{LiveCache, RaidTrain};
+use crate::bot::rewards::RewardError;
+use crate::bot::settings::{ChannelSettings, SettingsCommand};
+use crate::bot::shoutouts::{Shoutout, ShoutoutError, Shoutouts};
+use crate::config::{Config, SharedConfig};
+use crate::db::{CampaignScope, Store};
+use crate::db::models::{NewRaid, NewReachedTarget, NewStream, RewardClaim};
+use crate::health::SharedHealth;
+use crate::notify::{Notification, Notifier};
+use crate::routes::tiltify::TiltifyDonation;
+use crate::routes::webhook::Amount;
+use crate::tiltify::{self, TiltifyClient};
+use crate::bot::websocket::ChatWebsocketClient;
 use eyre::{Report, WrapErr as _};
 use reqwest::Error;
+use serde_derive::{Deserialize, Serialize};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::Mutex;
 use tokio::sync::broadcast::error::{TryRecvError};
 use tokio::time::sleep;
-use tracing::{Instrument, error, info, span, warn};
+use tracing::{error, info, span, warn};
 use twitch_api::eventsub::{Event, Message, Payload};
-use twitch_api::extra::AnnouncementColor;
-use twitch_api::helix::chat::{
-    SendChatAnnouncementBody, SendChatAnnouncementRequest, SendChatMessageBody,
-    SendChatMessageRequest,
-};
 use twitch_api::helix::{ClientRequestError, Request, Response};
 use twitch_api::{HelixClient, eventsub};
 use twitch_oauth2::{TwitchToken, UserToken};
 
+pub mod attribution;
 pub mod auth;
+pub mod donors;
+pub mod helix;
+pub mod holds;
+pub mod moderation;
+pub mod polls;
+pub mod raids;
+pub mod rewards;
+pub mod settings;
+pub mod shoutouts;
+pub mod targets;
+pub mod template;
 pub mod websocket;
 
 // pub twitch_id: String,
@@ -31,12 +52,31 @@ pub mod websocket;
 pub struct Bot {
     pub client: HelixClient<'static, reqwest::Client>,
     pub token: Arc<Mutex<UserToken>>,
-    pub config: Config,
-    pub broadcaster: twitch_api::types::UserId,
-    pub channels: Channels,
+    pub config: SharedConfig,
+    pub channels: Arc<Mutex<Channels>>,
+    pub store: Store,
+    pub health: SharedHealth,
+    pub notifier: Notifier,
+    /// Looks up reward names for claimed rewards.
+    pub tiltify: TiltifyClient,
+    /// Live participants for `!raid`, so chat can't make the bot hammer Helix.
+    pub live: LiveCache,
+    pub shoutouts: Mutex<Shoutouts>,
+    pub tx: tokio::sync::broadcast::Sender<Commands>,
     pub rx: tokio::sync::broadcast::Receiver<Commands>,
 }
 
+/// What the bot would have announced while paused, kept until it is resumed.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Deferred {
+    /// A donation cleared for announcing, with only the comment that may be shown.
+    Donation(TiltifyDonation),
+    /// An approved comment on a donation that was announced without it.
+    Comment(TiltifyDonation),
+    Milestone(Notification),
+}
+
 impl Bot {
     //noinspection RsUnreachableCode
     pub async fn start(&mut self) -> Result<(), Report> {
@@ -56,6 +96,7 @@ impl Bot {
                 let _enter = span.enter();
 
                 interval.tick().await;
+                self.health.refresh_ticked();
                 info!("Interval ticked, checking token");
                 // let mut token = token.lock().await;
                 if token_cloned.expires_in() < Duration::from_secs(3600) {
@@ -63,35 +104,39 @@ impl Bot {
                         "Token expires in {} seconds, refreshing",
                         token_cloned.expires_in().as_secs()
                     );
-                    token_cloned
+                    let refreshed = token_cloned
                         .refresh_token(&self.client.clone())
                         .await
-                        .wrap_err("couldn't refresh token")
-                        .expect("couldn't refresh token");
-                    info!(
-                        "Token refreshed, new expiration is in {} seconds",
-                        token_cloned.expires_in().as_secs()
-                    );
+                        .wrap_err("couldn't refresh token");
+                    metrics::TOKEN_REFRESHES
+                        .with_label_values(&[metrics::result_label(&refreshed)])
+                        .inc();
+                    match refreshed {
+                        Ok(_) => info!(
+                            "Token refreshed, new expiration is in {} seconds",
+                            token_cloned.expires_in().as_secs()
+                        ),
+                        Err(e) => error!("{e:?}"),
+                    }
                 }
-                match token_cloned
+                let valid = token_cloned
                     .validate_token(&self.client.clone())
                     .await
                     .wrap_err("couldn't validate token")
-                {
-                    Ok(_) => {
-                        info!(
-                            "Token {} still valid, expiration is in {} seconds",
-                            token_cloned.access_token,
-                            token_cloned.expires_in().as_secs(),
-                        );
-                        *self.token.lock().await = token_cloned.clone();
+                    .is_ok();
+                self.health.token_validated(valid);
+                if valid {
+                    info!(
+                        "Token {} still valid, expiration is in {} seconds",
+                        token_cloned.access_token,
+                        token_cloned.expires_in().as_secs(),
+                    );
+                    *self.token.lock().await = token_cloned.clone();
 
-                        let bot = User::from(token_cloned.clone());
-                        bot.save(&self.config.storage.bot)
-                            .expect("couldn't save bot");
-                    }
-                    Err(_) => {}
-                };
+                    let bot = User::from(token_cloned.clone());
+                    bot.save(&self.config.read().await.storage.bot)
+                        .expect("couldn't save bot");
+                }
             }
         };
 
@@ -100,75 +145,94 @@ impl Bot {
             // We check constantly if the token is valid.
             // We also need to refresh the token if it's about to be expired.
             let span = span!(tracing::Level::INFO, "broadcast_handler");
+            // Resuming while the bot was down leaves what was deferred until the next start.
+            self.replay_deferred().await;
 
             loop {
                 let _span = span.enter();
-                let token = self.token.lock().await;
                 match rx.try_recv() {
                     Ok(cmd) => match cmd {
                         Commands::Shutdown => break,
                         Commands::DonationReceived(donation) => {
                             info!("Donation received: {:#?}", donation);
-
-                            let moderated_live_channels = self
-                                .channels
-                                .clone()
-                                .get_moderated_live_channels(&self.client.clone(), &token.clone())
-                                .await;
-                            info!("Live channels: {:?}", moderated_live_channels);
-                            let message = format!("!donation_received {}", donation.amount.value);
-
-                            let announcement = format!(
-                                "A donation of ${} has been made by {}!",
-                                donation.amount.value, donation.name.unwrap_or_else(|| "an anonymous user".to_string())
-                            );
-                            let mut channels_sent_messages_to: Vec<Channel> = Vec::new();
-                            for live_channel in &moderated_live_channels {
-                                match Self::send_chat_message(
-                                    self.client.clone(),
-                                    &token.clone(),
-                                    live_channel,
-                                    message.as_str(),
-                                )
-                                .await
-                                {
-                                    Ok(_) => {
-                                        channels_sent_messages_to.push(live_channel.clone());
-                                        info!(
-                                            "Announcement sent to channel: {}",
-                                            live_channel.name
-                                        );
-                                    }
-                                    Err(e) => {
-                                        error!("Error sending message: {e:?}");
-                                    }
-                                };
-                                match Self::send_chat_announcement(
-                                    self.client.clone(),
-                                    &token.clone(),
-                                    live_channel,
-                                    announcement.as_str(),
-                                )
-                                .await
-                                {
-                                    Ok(_) => {
-                                        channels_sent_messages_to.push(live_channel.clone());
-                                        info!("Message sent to channel: {}", live_channel.name);
-                                    }
-                                    Err(e) => {
-                                        error!("Error sending message: {e:?}");
-                                    }
-                                };
+                            let mut claims = Vec::new();
+                            match self.store.insert_donation(&donation).await {
+                                Ok(true) => {
+                                    self.attribute(&donation).await;
+                                    claims = self.record_claims(&donation).await;
+                                }
+                                Ok(false) => {
+                                    warn!("Donation {} was already announced, skipping", donation.id);
+                                    continue;
+                                }
+                                Err(e) => error!("Error storing donation {}: {e:?}", donation.id),
+                            }
+                            let (comment, held_comment) = match self.review_comment(&donation).await {
+                                Ok(comment) => (comment, None),
+                                Err(reason) => (None, Some(format!("comment {reason}"))),
+                            };
+                            let reasons = self.hold_reasons(&donation).await;
+                            let paused = self.is_paused().await;
+                            if !reasons.is_empty() {
+                                let reasons: Vec<String> = held_comment.into_iter().chain(reasons).collect();
+                                self.hold(&donation, &reasons, false).await;
+                            } else {
+                                // A comment waiting for a moderator doesn't hold up its donation.
+                                let announced = TiltifyDonation { message: comment, ..donation.clone() };
+                                if paused {
+                                    info!("Announcements are paused, deferring donation {}", donation.id);
+                                    self.defer(&Deferred::Donation(announced)).await;
+                                } else {
+                                    self.announce_donation(&announced, &claims).await;
+                                }
+                                if let Some(reason) = held_comment {
+                                    self.hold(&donation, &[reason], true).await;
+                                }
+                            }
+                            for milestone in self.milestones_reached(&donation).await {
+                                info!("Milestone reached: {:?}", milestone);
+                                if paused {
+                                    self.defer(&Deferred::Milestone(milestone)).await;
+                                } else {
+                                    self.notifier.dispatch(&milestone).await;
+                                }
+                            }
+                        }
+                        Commands::Announcement(announcement) => {
+                            info!("Manual announcement: {}", announcement);
+                            if let Err(e) = self.notifier.twitch().await.announce(&announcement).await {
+                                error!("Error sending announcement: {e:?}");
+                            }
+                        }
+                        Commands::DonationReleased(donation) => self.release(&donation).await,
+                        Commands::CommentReleased(donation) => self.release_comment(&donation).await,
+                        Commands::Resumed => self.replay_deferred().await,
+                        Commands::ChannelsChanged | Commands::DonationAnnounced(_) => {}
+                        Commands::RaidInitiated { from, to, viewers } => {
+                            if !self.is_paused().await {
+                                self.welcome_raid(&from, &to, viewers).await;
+                                self.shout_out(&from, &to).await;
+                                self.notifier
+                                    .dispatch(&Notification::Raid { from, to, viewers })
+                                    .await;
+                            }
+                        }
+                        Commands::StreamStarted(channel) => {
+                            if !self.is_paused().await {
+                                self.welcome_stream(&channel).await;
+                                self.notifier
+                                    .dispatch(&Notification::StreamStarted { channel })
+                                    .await;
+                            }
+                        }
+                        Commands::StreamEnded(channel) => {
+                            if !self.is_paused().await {
+                                self.summarize_stream(&channel).await;
+                                self.notifier
+                                    .dispatch(&Notification::StreamEnded { channel })
+                                    .await;
                             }
-                            info!(
-                                "Donation message sent to {} channels. Channels were: {:?}",
-                                &moderated_live_channels.len(),
-                                &moderated_live_channels
-                            );
                         }
-                        Commands::RaidInitiated(_) => {}
-                        Commands::StreamStarted(_) => {}
-                        Commands::StreamEnded(_) => {}
                     },
                     Err(e) => match e {
                         TryRecvError::Closed => {
@@ -188,68 +252,1027 @@ impl Bot {
             info!("broadcast_handler loop ended");
         };
 
-        tokio::join!(refresh_token, broadcast_handler);
-        // let ws = websocket.run(|e, ts| async { self.handle_event(e, ts).await });
-        // futures::future::try_join(refresh_token, broadcast_handler).await?;
+        let bot: &Bot = self;
+        let websocket = async {
+            loop {
+                let websocket = ChatWebsocketClient {
+                    session_id: None,
+                    token: self.token.clone(),
+                    client: self.client.clone(),
+                    connect_url: twitch_api::TWITCH_EVENTSUB_WEBSOCKET_URL.clone(),
+                    channels: self.channels.clone(),
+                    rx: self.rx.resubscribe(),
+                    health: self.health.clone(),
+                    config: self.config.clone(),
+                };
+                let res = websocket
+                    .run(move |event, timestamp| async move {
+                        if let Err(e) = bot.handle_event(event, timestamp).await {
+                            error!("Error handling event: {e:?}");
+                        }
+                        Ok(())
+                    })
+                    .await;
+                self.health.set_eventsub_connected(false);
+                match res {
+                    Ok(()) => warn!("EventSub websocket closed, reconnecting"),
+                    Err(e) => error!("EventSub websocket failed, reconnecting: {e:?}"),
+                }
+                metrics::EVENTSUB_RECONNECTS.inc();
+                sleep(Duration::from_secs(5)).await;
+            }
+        };
+
+        let expire_holds = async {
+            let mut interval = tokio::time::interval(Duration::from_secs(30));
+            loop {
+                interval.tick().await;
+                let config = self.config.read().await.clone();
+                match holds::expire(&self.store, &config).await {
+                    Ok(released) => {
+                        for donation in released {
+                            self.release(&donation).await;
+                        }
+                    }
+                    Err(e) => error!("Error expiring holds: {e:?}"),
+                }
+            }
+        };
+
+        let queued_shoutouts = async {
+            let mut interval = tokio::time::interval(Duration::from_secs(15));
+            loop {
+                interval.tick().await;
+                self.send_due_shoutouts().await;
+            }
+        };
+
+        tokio::join!(
+            refresh_token,
+            broadcast_handler,
+            websocket,
+            expire_holds,
+            queued_shoutouts
+        );
         Ok(())
     }
 
-    #[tracing::instrument(skip(client))]
-    async fn send_chat_announcement(
-        client: HelixClient<'static, reqwest::Client>,
-        token: &UserToken,
-        channel: &Channel,
-        message: &str,
-    ) -> Result<(), Report> {
-        info!("Sending announcement sent to channel: {}", channel.name);
-        let req = SendChatAnnouncementRequest::new(&channel.user_id, &token.user_id);
-        let body = SendChatAnnouncementBody::new(message, AnnouncementColor::Orange)?;
-        match client
-            .req_post(req, body.clone(), token)
-            .in_current_span()
+    async fn is_paused(&self) -> bool {
+        self.store.is_paused().await.unwrap_or_else(|e| {
+            error!("Error reading paused state: {e:?}");
+            false
+        })
+    }
+
+    /// Runs the donor comment through the local `[moderation]` checks and records the outcome,
+    /// returning the comment when it may be posted, or why it was held for a moderator.
+    async fn review_comment(&self, donation: &TiltifyDonation) -> Result<Option<String>, String> {
+        let Some(comment) = donation.message.as_deref().map(str::trim).filter(|c| !c.is_empty())
+        else {
+            return Ok(None);
+        };
+        let moderation = self.config.read().await.moderation.clone();
+        let (status, reason) = match moderation::check_comment(&moderation, comment) {
+            Ok(()) => ("approved", None),
+            Err(reason) => {
+                info!("Comment on donation {} rejected: {reason}", donation.id);
+                (moderation.rejected_comments.status(), Some(reason))
+            }
+        };
+        if let Err(e) = self
+            .store
+            .set_comment_status(&donation.id, status, reason.as_deref())
             .await
         {
-            Ok(r) => {
-                info!("SendChatAnnouncement returned: {:?}", r);
-                info!("Message sent to channel: {} {r:?}", channel.name);
+            error!("Error storing comment status of donation {}: {e:?}", donation.id);
+        }
+        match reason {
+            None => Ok(Some(comment.to_string())),
+            Some(reason) if status == "held" => Err(reason),
+            Some(_) => Ok(None),
+        }
+    }
+
+    /// Why the donation has to wait for a moderator, following `[holds]`.
+    async fn hold_reasons(&self, donation: &TiltifyDonation) -> Vec<String> {
+        let config = self.config.read().await.holds.clone();
+        let first_time = match &donation.name {
+            Some(name) if config.first_time_donors => {
+                match self.store.donated_before(name, &donation.id).await {
+                    Ok(before) => !before,
+                    Err(e) => {
+                        error!("Error looking up earlier donations of {name}: {e:?}");
+                        true
+                    }
+                }
+            }
+            _ => false,
+        };
+        holds::hold_reasons(&config, donation, first_time)
+    }
+
+    /// Queues a donation, or with `comment_only` just its comment, for moderators and lists it in
+    /// the control channel.
+    async fn hold(&self, donation: &TiltifyDonation, reasons: &[String], comment_only: bool) {
+        let redacted: Vec<String> = reasons.iter().map(|reason| holds::redact(reason)).collect();
+        let reasons = reasons.join("; ");
+        let hold = match self.store.create_hold(&donation.id, &reasons, comment_only).await {
+            Ok(hold) => hold,
+            Err(e) => {
+                error!("Error holding donation {}: {e:?}", donation.id);
+                return;
             }
+        };
+        let config = self.config.read().await.clone();
+        let campaign = metrics::donation_campaign_label(
+            &config,
+            donation.campaign_id.as_deref(),
+            donation.team_event_id.as_deref(),
+        );
+        metrics::HOLDS.with_label_values(&[campaign, "pending"]).inc();
+        info!("Donation {} held as #{}: {reasons}", donation.id, hold.id);
+        let name = donors::display_name(&config.donors, donation);
+        let id = hold.id.to_string();
+        let template = if comment_only {
+            "The comment on donation #{id} of {amount} {currency} by {name} is held ({reasons}). !approve {id} or !reject {id}"
+        } else {
+            "Donation #{id} of {amount} {currency} by {name} is held ({reasons}). !approve {id} or !reject {id}"
+        };
+        let messages = match template::render_chat(
+            template,
+            &[
+                ("id", &id),
+                ("amount", &donation.amount.value),
+                ("currency", &donation.amount.currency),
+                ("name", &name),
+                ("reasons", &redacted.join("; ")),
+            ],
+            "reasons",
+            template::CHAT_LIMIT,
+        ) {
+            Ok(messages) => messages,
             Err(e) => {
-                error!("Error sending message: {e:?}");
+                error!("Hold message for donation {} doesn't fit in chat: {e:?}", donation.id);
+                return;
             }
+        };
+        for message in messages {
+            self.tell_control_channel(&message).await;
         }
+    }
 
-        info!("Announcement sent to channel: {}", channel.name);
-        Ok(())
+    /// Announces a donation whose hold was approved or released.
+    async fn release(&self, donation: &TiltifyDonation) {
+        if self.is_paused().await {
+            info!("Announcements are paused, deferring released donation {}", donation.id);
+            self.defer(&Deferred::Donation(donation.clone())).await;
+            return;
+        }
+        let claims = match self.store.donation_reward_claims(&donation.id).await {
+            Ok(claims) => claims,
+            Err(e) => {
+                error!("Error reading rewards claimed with donation {}: {e:?}", donation.id);
+                Vec::new()
+            }
+        };
+        self.announce_donation(donation, &claims).await;
+    }
+
+    /// Posts a comment a moderator approved after its donation was announced without it.
+    async fn release_comment(&self, donation: &TiltifyDonation) {
+        if self.is_paused().await {
+            info!("Announcements are paused, deferring comment on donation {}", donation.id);
+            self.defer(&Deferred::Comment(donation.clone())).await;
+            return;
+        }
+        let donors = self.config.read().await.donors.clone();
+        let notification = Notification::donation(donation, &donors, donation.message.clone());
+        if let Err(e) = self.notifier.twitch().await.comment_on(&notification).await {
+            error!("Error posting comment on donation {}: {e:?}", donation.id);
+        }
+    }
+
+    async fn defer(&self, deferred: &Deferred) {
+        if let Err(e) = self.store.defer(deferred).await {
+            error!("Error deferring {deferred:?}: {e:?}");
+        }
+    }
+
+    /// Announces what was deferred while announcements were paused, in the order it happened.
+    async fn replay_deferred(&self) {
+        if self.is_paused().await {
+            return;
+        }
+        let deferred = match self.store.take_deferred::<Deferred>().await {
+            Ok(deferred) => deferred,
+            Err(e) => {
+                error!("Error reading what was deferred while paused: {e:?}");
+                return;
+            }
+        };
+        if !deferred.is_empty() {
+            info!("Announcing {} deferred notifications", deferred.len());
+        }
+        for deferred in deferred {
+            match deferred {
+                Deferred::Donation(donation) => self.release(&donation).await,
+                Deferred::Comment(donation) => self.release_comment(&donation).await,
+                Deferred::Milestone(milestone) => self.notifier.dispatch(&milestone).await,
+            }
+        }
+    }
+
+    /// Announces a donation with the rewards it claimed, the vote it cast and the target it
+    /// counts toward, and tells webhooks about it. Its `message` is the comment that may be shown.
+    async fn announce_donation(&self, donation: &TiltifyDonation, claims: &[RewardClaim]) {
+        let donors = self.config.read().await.donors.clone();
+        self.notifier
+            .dispatch(&Notification::donation(donation, &donors, donation.message.clone()))
+            .await;
+        self.count_announced(donation).await;
+        self.announce_claims(donation, claims).await;
+        self.announce_vote(donation).await;
+        self.announce_target(donation).await;
+        let _ = self.tx.send(Commands::DonationAnnounced(donation.clone()));
+    }
+
+    async fn count_announced(&self, donation: &TiltifyDonation) {
+        let config = self.config.read().await;
+        let campaign = config.campaign_key(
+            donation.campaign_id.as_deref(),
+            donation.team_event_id.as_deref(),
+        );
+        metrics::DONATIONS_ANNOUNCED
+            .with_label_values(&[metrics::campaign_label(campaign)])
+            .inc();
+    }
+
+    async fn tell_control_channel(&self, message: &str) {
+        let Some(name) = self.config.read().await.holds.control_channel.clone() else {
+            return;
+        };
+        let Some(channel) = self.channels.lock().await.find(&name).cloned() else {
+            warn!("Control channel {name} isn't a participating channel");
+            return;
+        };
+        if let Err(e) = self.notifier.twitch().await.say(&channel, message).await {
+            error!("Error writing to control channel {name}: {e:?}");
+        }
+    }
+
+    /// Handles `!approve <id>` and `!reject <id>` from moderators of the control channel.
+    async fn settle_from_chat(
+        &self,
+        payload: &eventsub::channel::ChannelChatMessageV1Payload,
+        approve: bool,
+        hold: Option<&str>,
+    ) -> Result<(), Report> {
+        let control_channel = self.config.read().await.holds.control_channel.clone();
+        if !control_channel
+            .is_some_and(|c| payload.broadcaster_user_login.as_str().eq_ignore_ascii_case(&c))
+        {
+            return Ok(());
+        }
+        if !is_moderator(payload) {
+            return Ok(());
+        }
+        let channel = Channel {
+            user_id: payload.broadcaster_user_id.clone(),
+            name: payload.broadcaster_user_login.clone(),
+            settings: ChannelSettings::default(),
+        };
+        let Some(hold) = hold.and_then(|h| h.trim_start_matches('#').parse::<i32>().ok()) else {
+            let usage = if approve { "Usage: !approve <id>" } else { "Usage: !reject <id>" };
+            return self.notifier.twitch().await.say(&channel, usage).await;
+        };
+        let by = format!("chat:{}", payload.chatter_user_login);
+        let config = self.config.read().await.clone();
+        let reply = match holds::settle(&self.store, &config, hold, approve, &by).await {
+            Ok((settled, released)) => {
+                match released {
+                    Some(donation) if settled.comment_only => self.release_comment(&donation).await,
+                    Some(donation) => self.release(&donation).await,
+                    None => {}
+                }
+                format!("Donation #{} {}", settled.id, settled.status)
+            }
+            Err(HoldError::Internal(e)) => return Err(e),
+            Err(e) => e.to_string(),
+        };
+        self.notifier.twitch().await.say(&channel, &reply).await
     }
 
-    #[tracing::instrument(skip(client))]
-    async fn send_chat_message(
-        client: HelixClient<'static, reqwest::Client>,
-        token: &UserToken,
-        channel: &Channel,
-        message: &str,
+    /// Handles `!warbot` from the broadcaster or moderators of a participating channel, saving
+    /// changes to that channel's settings right away.
+    async fn settings_from_chat(
+        &self,
+        payload: &eventsub::channel::ChannelChatMessageV1Payload,
+        args: &[&str],
     ) -> Result<(), Report> {
-        info!("Sending message to channel: {}", channel.name);
-        let req = SendChatMessageRequest::new();
-        let body = SendChatMessageBody::new(&channel.user_id, &token.user_id, message);
-        match client
-            .req_post(req, body.clone(), &token.clone())
-            .in_current_span()
+        if !is_moderator(payload) {
+            return Ok(());
+        }
+        let (channel, reply) = {
+            let mut channels = self.channels.lock().await;
+            let Some(channel) = channels.find_mut(payload.broadcaster_user_id.as_str()) else {
+                return Ok(());
+            };
+            let Some(command) = SettingsCommand::parse(args) else {
+                let channel = channel.clone();
+                return self
+                    .notifier
+                    .twitch()
+                    .await
+                    .say(&channel, SettingsCommand::USAGE)
+                    .await;
+            };
+            let changed = command.apply(&mut channel.settings);
+            let reply = format!("Warbot is {}", channel.settings.summary());
+            let channel = channel.clone();
+            if changed {
+                channels.save(&self.config.read().await.storage.channels)?;
+                info!(
+                    "{} changed settings of {}: {command:?}",
+                    payload.chatter_user_login, channel.name
+                );
+            }
+            (channel, reply)
+        };
+        self.notifier.twitch().await.say(&channel, &reply).await
+    }
+
+    /// Records a raid from one participant to another and passes it on as `RaidInitiated`.
+    async fn raided(&self, raid: eventsub::channel::ChannelRaidV1Payload) -> Result<(), Report> {
+        let from = raid.from_broadcaster_user_login.as_str();
+        let to = raid.to_broadcaster_user_login.as_str();
+        if self
+            .channels
+            .lock()
+            .await
+            .find(raid.to_broadcaster_user_id.as_str())
+            .is_none()
+        {
+            info!("{from} raided {to}, who isn't participating");
+            return Ok(());
+        }
+        let recorded = self
+            .store
+            .record_raid(NewRaid {
+                from_id: raid.from_broadcaster_user_id.as_str(),
+                from_name: from,
+                to_id: raid.to_broadcaster_user_id.as_str(),
+                to_name: to,
+                viewers: raid.viewers,
+                raided_at: chrono::Utc::now().naive_utc(),
+            })
+            .await?;
+        metrics::RAIDS.inc();
+        info!("Raid #{}: {from} raided {to} with {} viewers", recorded.id, raid.viewers);
+        let _ = self.tx.send(Commands::RaidInitiated {
+            from: from.to_string(),
+            to: to.to_string(),
+            viewers: raid.viewers,
+        });
+        Ok(())
+    }
+
+    /// Announces the campaign and its total in a participating channel that was raided.
+    async fn welcome_raid(&self, from: &str, to: &str, viewers: i64) {
+        let Some(channel) = self.channels.lock().await.find(to).cloned() else {
+            return;
+        };
+        let config = self.config.read().await.clone();
+        let campaign = config.channel_campaign(&channel);
+        let Some(total) = self.channel_campaign_total(&config, &channel).await else {
+            return;
+        };
+        let welcome = template::render_chat(
+            &channel.settings.templates(&config, campaign).raid_welcome,
+            &[
+                ("from", from),
+                ("to", to),
+                ("viewers", &viewers.to_string()),
+                ("total", &format!("{total:.2}")),
+                ("campaign", &campaign.name),
+            ],
+            "campaign",
+            template::CHAT_LIMIT,
+        );
+        let twitch = self.notifier.twitch().await;
+        let result = match welcome {
+            Ok(parts) => twitch.announce_parts_in(&channel, &parts).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            error!("Error welcoming raid from {from} in {to}: {e:?}");
+        }
+    }
+
+    /// The total of what a channel raises for, see [`Config::channel_scope`].
+    async fn channel_campaign_total(&self, config: &Config, channel: &Channel) -> Option<f64> {
+        match self.store.campaign_total(&config.channel_scope(channel)).await {
+            Ok(total) => Some(total),
+            Err(e) => {
+                error!("Error reading campaign total: {e:?}");
+                None
+            }
+        }
+    }
+
+    /// Queues a shoutout for the raider in the raided channel and sends whatever is due.
+    async fn shout_out(&self, from: &str, to: &str) {
+        if !self.config.read().await.raids.shoutouts {
+            return;
+        }
+        let (raider, raided) = {
+            let channels = self.channels.lock().await;
+            (channels.find(from).cloned(), channels.find(to).cloned())
+        };
+        let (Some(raider), Some(raided)) = (raider, raided) else {
+            return;
+        };
+        self.shoutouts
+            .lock()
+            .await
+            .push(Shoutout::new(raided, raider, std::time::Instant::now()));
+        self.send_due_shoutouts().await;
+    }
+
+    /// Sends the queued shoutouts that Twitch's cooldowns allow, each with the campaign's
+    /// `raid_shoutout` message.
+    async fn send_due_shoutouts(&self) {
+        let due = self.shoutouts.lock().await.due(std::time::Instant::now());
+        if due.is_empty() {
+            return;
+        }
+        let config = self.config.read().await.clone();
+        for shoutout in due {
+            let token = self
+                .notifier
+                .streamers
+                .lock()
+                .await
+                .token_for(&self.client, &config.twitch, &config.storage.streamers, &shoutout.from)
+                .await;
+            let result = match token {
+                Ok(token) => shoutouts::send(&self.client, &config.twitch, &token, &shoutout).await,
+                Err(e) => Err(ShoutoutError::Failed(e)),
+            };
+            let (from, to) = (&shoutout.from, &shoutout.to);
+            match result {
+                Ok(()) => {
+                    info!("{from} gave {to} a shoutout", from = from.name, to = to.name);
+                    metrics::SHOUTOUTS.with_label_values(&["sent"]).inc();
+                    self.shoutouts
+                        .lock()
+                        .await
+                        .sent(&shoutout, std::time::Instant::now());
+                    if from.settings.posts_messages(chrono::Utc::now()) {
+                        let campaign = config.channel_campaign(from);
+                        let message = template::render_chat(
+                            &from.settings.templates(&config, campaign).raid_shoutout,
+                            &[
+                                ("from", to.name.as_str()),
+                                ("to", from.name.as_str()),
+                                ("campaign", &campaign.name),
+                            ],
+                            "campaign",
+                            template::CHAT_LIMIT,
+                        );
+                        let twitch = self.notifier.twitch().await;
+                        let result = match message {
+                            Ok(parts) => twitch.say_parts(from, &parts).await,
+                            Err(e) => Err(e.into()),
+                        };
+                        if let Err(e) = result {
+                            error!("Error posting shoutout message in {}: {e:?}", from.name);
+                        }
+                    }
+                }
+                Err(ShoutoutError::Cooldown) => {
+                    metrics::SHOUTOUTS.with_label_values(&["queued"]).inc();
+                    let mut shoutouts = self.shoutouts.lock().await;
+                    info!(
+                        "Shoutout for {} in {} is on cooldown, {} shoutouts waiting",
+                        to.name,
+                        from.name,
+                        shoutouts.queued() + 1
+                    );
+                    shoutouts.cooling_down(shoutout, std::time::Instant::now());
+                }
+                Err(ShoutoutError::Failed(e)) => {
+                    error!("Error giving {} a shoutout in {}: {e:?}", to.name, from.name);
+                    metrics::SHOUTOUTS.with_label_values(&["failed"]).inc();
+                }
+            }
+        }
+    }
+
+    /// Announces the campaign, its total and where to donate in a channel that went live.
+    async fn welcome_stream(&self, name: &str) {
+        let config = self.config.read().await.clone();
+        let Some(channel) = self.channels.lock().await.find(name).cloned() else {
+            return;
+        };
+        let campaign = config.channel_campaign(&channel);
+        let Some(url) = campaign.url.as_deref().filter(|_| config.streams.welcome) else {
+            return;
+        };
+        let Some(total) = self.channel_campaign_total(&config, &channel).await else {
+            return;
+        };
+        let welcome = template::render_chat(
+            &channel.settings.templates(&config, campaign).stream_welcome,
+            &[
+                ("channel", name),
+                ("campaign", &campaign.name),
+                ("total", &format!("{total:.2}")),
+                ("url", url),
+            ],
+            "campaign",
+            template::CHAT_LIMIT,
+        );
+        let twitch = self.notifier.twitch().await;
+        let result = match welcome {
+            Ok(parts) => twitch.announce_parts_in(&channel, &parts).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            error!("Error welcoming stream of {name}: {e:?}");
+        }
+    }
+
+    /// Posts how long a channel that went offline was live and what it raised meanwhile, in
+    /// the channel and in `streams.ops_channel`.
+    async fn summarize_stream(&self, name: &str) {
+        let config = self.config.read().await.clone();
+        if !config.streams.summaries {
+            return;
+        }
+        let (channel, ops) = {
+            let channels = self.channels.lock().await;
+            let ops = config
+                .streams
+                .ops_channel
+                .as_deref()
+                .and_then(|ops| channels.find(ops).cloned());
+            (channels.find(name).cloned(), ops)
+        };
+        let Some(channel) = channel else {
+            return;
+        };
+        let stream = match self.store.latest_stream(channel.user_id.as_str()).await {
+            Ok(Some(stream)) => stream,
+            Ok(None) => return,
+            Err(e) => {
+                error!("Error reading stream of {name}: {e:?}");
+                return;
+            }
+        };
+        let Some(ended_at) = stream.ended_at else {
+            return;
+        };
+        let (donations, raised) = match self
+            .store
+            .donations_between(
+                &config.channel_scope(&channel),
+                channel.user_id.as_str(),
+                stream.started_at,
+                ended_at,
+            )
             .await
         {
-            Ok(r) => {
-                info!("SendChatAnnouncement returned: {:?}", r);
-                info!("Message sent to channel: {} {r:?}", channel.name);
-                Ok(())
+            Ok(between) => between,
+            Err(e) => {
+                error!("Error reading donations during stream of {name}: {e:?}");
+                return;
+            }
+        };
+        let summary = match template::render_chat(
+            &channel
+                .settings
+                .templates(&config, config.channel_campaign(&channel))
+                .stream_summary,
+            &[
+                ("channel", name),
+                ("duration", &template::duration(ended_at - stream.started_at)),
+                ("donations", &donations.to_string()),
+                ("raised", &format!("{raised:.2}")),
+            ],
+            "channel",
+            template::CHAT_LIMIT,
+        ) {
+            Ok(summary) => summary,
+            Err(e) => {
+                error!("Stream summary of {name} doesn't fit in chat: {e:?}");
+                return;
+            }
+        };
+        let twitch = self.notifier.twitch().await;
+        if channel.settings.posts_messages(chrono::Utc::now())
+            && let Err(e) = twitch.say_parts(&channel, &summary).await
+        {
+            error!("Error posting stream summary in {name}: {e:?}");
+        }
+        if let Some(ops) = ops
+            && let Err(e) = twitch.say_parts(&ops, &summary).await
+        {
+            error!("Error posting stream summary of {name} in {}: {e:?}", ops.name);
+        }
+    }
+
+    /// Answers `!raidtrain` with the latest raid train through the channel, or the latest one.
+    async fn raid_train_from_chat(
+        &self,
+        payload: &eventsub::channel::ChannelChatMessageV1Payload,
+    ) -> Result<(), Report> {
+        let channel_id = payload.broadcaster_user_id.as_str();
+        let Some(channel) = self.channels.lock().await.find(channel_id).cloned() else {
+            return Ok(());
+        };
+        let since = self.config.read().await.raids.history_start();
+        let trains = raids::trains(self.store.raids_since(since).await?);
+        let reply = trains
+            .iter()
+            .rev()
+            .find(|train| train.involves(channel_id))
+            .or(trains.last())
+            .map_or_else(|| "No raid train yet".to_string(), RaidTrain::summary);
+        self.notifier.twitch().await.say(&channel, &reply).await
+    }
+
+    /// Answers `!raised` with what the channel, or the one named, raised while live.
+    async fn raised_from_chat(
+        &self,
+        payload: &eventsub::channel::ChannelChatMessageV1Payload,
+        name: Option<&str>,
+    ) -> Result<(), Report> {
+        let (channel, asked) = {
+            let channels = self.channels.lock().await;
+            let Some(channel) = channels.find(payload.broadcaster_user_id.as_str()).cloned() else {
+                return Ok(());
+            };
+            let asked = match name {
+                Some(name) => channels.find(name.trim_start_matches('@')).cloned(),
+                None => Some(channel.clone()),
+            };
+            (channel, asked)
+        };
+        let reply = match asked {
+            Some(asked) => self
+                .store
+                .raised_by_channel(self.config.read().await.channel_campaign_key(&asked))
+                .await?
+                .into_iter()
+                .find(|r| r.channel_id == asked.user_id.as_str())
+                .map_or_else(
+                    || format!("Nothing raised while {} was live yet", asked.name),
+                    |raised| raised.summary(),
+                ),
+            None => format!("{} isn't participating", name.unwrap_or_default()),
+        };
+        self.notifier.twitch().await.say(&channel, &reply).await
+    }
+
+    /// Participants that are live, from Helix at most every `raids.live_cache_secs`.
+    async fn live_streams(&self) -> Vec<LiveStream> {
+        let max_age = Duration::from_secs(self.config.read().await.raids.live_cache_secs);
+        self.live
+            .get(max_age, || async {
+                let token = self.token.lock().await.clone();
+                let channels = self.channels.lock().await.clone();
+                let twitch = self.config.read().await.twitch.clone();
+                channels.get_live_streams(&self.client, &twitch, &token).await
+            })
+            .await
+    }
+
+    /// Attributes a newly stored donation to the live participants and the one it is credited to.
+    async fn attribute(&self, donation: &TiltifyDonation) {
+        let live = self.live_streams().await;
+        let channels = self.channels.lock().await.0.clone();
+        let team = self.config.read().await.team.clone();
+        let attributions = attribution::attribute(donation, &channels, &team, &live);
+        if let Err(e) = self.store.attribute(&attributions).await {
+            error!("Error attributing donation {}: {e:?}", donation.id);
+        }
+    }
+
+    /// Queues the rewards claimed with a newly stored donation for the streamers to fulfil.
+    async fn record_claims(&self, donation: &TiltifyDonation) -> Vec<RewardClaim> {
+        let config = self.config.read().await.clone();
+        let channels = self.channels.lock().await.0.clone();
+        let donor = donors::display_name(&config.donors, donation);
+        rewards::record(&self.store, &self.tiltify, donation, &donor, &channels, &config)
+            .await
+            .unwrap_or_else(|e| {
+                error!("Error recording rewards claimed with donation {}: {e:?}", donation.id);
+                Vec::new()
+            })
+    }
+
+    /// Announces claimed rewards in the channel each is credited to, or in every channel of the
+    /// donation's campaign when nobody offers it.
+    async fn announce_claims(&self, donation: &TiltifyDonation, claims: &[RewardClaim]) {
+        if claims.is_empty() {
+            return;
+        }
+        let config = self.config.read().await.clone();
+        let channels = self.channels.lock().await.clone();
+        let campaign = config.campaign_key(
+            donation.campaign_id.as_deref(),
+            donation.team_event_id.as_deref(),
+        );
+        let notification = Notification::donation(donation, &config.donors, None);
+        let twitch = self.notifier.twitch().await;
+        for claim in claims {
+            let result = match claim.channel_id.as_deref().and_then(|id| channels.find(id)) {
+                Some(channel) => {
+                    let templates = channel.settings.templates(&config, config.campaign(campaign));
+                    let announcement = rewards::announcement(&templates, claim, donation);
+                    twitch.announce_in(channel, &announcement).await
+                }
+                None => {
+                    twitch
+                        .announce_for_donation(&notification, |templates| {
+                            rewards::announcement(templates, claim, donation)
+                        })
+                        .await
+                }
+            };
+            if let Err(e) = result {
+                error!("Error announcing reward #{}: {e:?}", claim.id);
             }
+        }
+    }
+
+    /// Announces the poll option a donation voted for and the standings, in every channel of its
+    /// campaign.
+    async fn announce_vote(&self, donation: &TiltifyDonation) {
+        let (Some(poll_id), Some(option_id)) = (&donation.poll_id, &donation.poll_option_id) else {
+            return;
+        };
+        if !self.tiltify.is_configured().await {
+            return;
+        }
+        let ids: Vec<&str> = [&donation.campaign_id, &donation.team_event_id]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        let found = match self.tiltify.polls(&ids, Duration::ZERO).await {
+            Ok(found) => found,
             Err(e) => {
-                error!("Error sending message: {e:?}");
-                Err(e.into())
+                error!("Error fetching poll {poll_id} from Tiltify: {e:?}");
+                return;
             }
+        };
+        let Some((poll, option)) = found.iter().find(|p| &p.id == poll_id).and_then(|poll| {
+            Some((poll, poll.options.iter().find(|o| &o.id == option_id)?))
+        }) else {
+            warn!("Donation {} voted in poll {poll_id}, which Tiltify doesn't know", donation.id);
+            return;
+        };
+        let config = self.config.read().await.clone();
+        let donor = donors::display_name(&config.donors, donation);
+        let notification = Notification::donation(donation, &config.donors, None);
+        if let Err(e) = self
+            .notifier
+            .twitch()
+            .await
+            .announce_for_donation(&notification, |templates| {
+                polls::vote_announcement(templates, &donor, &donation.amount, poll, option)
+            })
+            .await
+        {
+            error!("Error announcing vote of donation {}: {e:?}", donation.id);
+        }
+    }
+
+    /// Announces how far a donation took the target it counts toward, in the channel the target
+    /// belongs to or else in every channel of its campaign. A reached target is announced once.
+    async fn announce_target(&self, donation: &TiltifyDonation) {
+        let Some(target_id) = &donation.target_id else {
+            return;
+        };
+        if !self.tiltify.is_configured().await {
+            return;
+        }
+        let ids: Vec<&str> = [&donation.campaign_id, &donation.team_event_id]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        let target = match self.tiltify.targets(&ids, Duration::ZERO).await {
+            Ok(found) => found.into_iter().find(|t| &t.id == target_id),
+            Err(e) => {
+                error!("Error fetching target {target_id} from Tiltify: {e:?}");
+                return;
+            }
+        };
+        let Some(target) = target else {
+            warn!("Donation {} counts toward target {target_id}, which Tiltify doesn't know", donation.id);
+            return;
+        };
+        if targets::is_reached(&target) {
+            let reached = self
+                .store
+                .reach_target(NewReachedTarget {
+                    target_id: &target.id,
+                    target_name: &target.name,
+                    donation_id: Some(&donation.id),
+                    reached_at: chrono::Utc::now().naive_utc(),
+                })
+                .await;
+            match reached {
+                Ok(true) => info!("Target {} was reached by donation {}", target.name, donation.id),
+                Ok(false) => return,
+                Err(e) => {
+                    error!("Error recording that target {} was reached: {e:?}", target.name);
+                    return;
+                }
+            }
+        }
+        let config = self.config.read().await.clone();
+        let owner = self
+            .channels
+            .lock()
+            .await
+            .0
+            .iter()
+            .find(|c| c.settings.targets.contains(target_id))
+            .cloned();
+        let donor = donors::display_name(&config.donors, donation);
+        let campaign = config.campaign_key(
+            donation.campaign_id.as_deref(),
+            donation.team_event_id.as_deref(),
+        );
+        let notification = Notification::donation(donation, &config.donors, None);
+        let twitch = self.notifier.twitch().await;
+        let result = match owner {
+            Some(channel) => {
+                let templates = channel.settings.templates(&config, config.campaign(campaign));
+                let announcement =
+                    targets::announcement(&templates, &donor, &donation.amount, &target);
+                twitch.announce_in(&channel, &announcement).await
+            }
+            None => {
+                twitch
+                    .announce_for_donation(&notification, |templates| {
+                        targets::announcement(templates, &donor, &donation.amount, &target)
+                    })
+                    .await
+            }
+        };
+        if let Err(e) = result {
+            error!("Error announcing target {}: {e:?}", target.name);
+        }
+    }
+
+    /// Answers `!targets` with the open targets the channel follows and what they still need.
+    async fn targets_from_chat(
+        &self,
+        payload: &eventsub::channel::ChannelChatMessageV1Payload,
+    ) -> Result<(), Report> {
+        let Some(channel) = self
+            .channels
+            .lock()
+            .await
+            .find(payload.broadcaster_user_id.as_str())
+            .cloned()
+        else {
+            return Ok(());
+        };
+        if !self.tiltify.is_configured().await {
+            return Ok(());
+        }
+        let ids: Vec<String> = {
+            let config = self.config.read().await;
+            let ids = config.channel_tiltify_ids(&channel);
+            ids.into_iter().map(str::to_string).collect()
+        };
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        let found = self.tiltify.targets(&ids, tiltify::CHAT_MAX_AGE).await?;
+        self.notifier
+            .twitch()
+            .await
+            .say(&channel, &targets::open_summary(&found))
+            .await
+    }
+
+    /// Answers `!poll` with the standings of the polls the channel follows.
+    async fn poll_from_chat(
+        &self,
+        payload: &eventsub::channel::ChannelChatMessageV1Payload,
+    ) -> Result<(), Report> {
+        let Some(channel) = self
+            .channels
+            .lock()
+            .await
+            .find(payload.broadcaster_user_id.as_str())
+            .cloned()
+        else {
+            return Ok(());
+        };
+        if !self.tiltify.is_configured().await {
+            return Ok(());
+        }
+        let ids: Vec<String> = {
+            let config = self.config.read().await;
+            let ids = config.channel_tiltify_ids(&channel);
+            ids.into_iter().map(str::to_string).collect()
+        };
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        let found = self.tiltify.polls(&ids, tiltify::CHAT_MAX_AGE).await?;
+        self.notifier
+            .twitch()
+            .await
+            .say(&channel, &polls::summary(&found))
+            .await
+    }
+
+    /// Answers `!rewards` with the rewards still to do in the channel.
+    async fn rewards_from_chat(
+        &self,
+        payload: &eventsub::channel::ChannelChatMessageV1Payload,
+    ) -> Result<(), Report> {
+        let channel_id = payload.broadcaster_user_id.as_str();
+        let Some(channel) = self.channels.lock().await.find(channel_id).cloned() else {
+            return Ok(());
+        };
+        let pending = self.store.pending_reward_claims(Some(channel_id)).await?;
+        let reply = rewards::queue_summary(&pending);
+        self.notifier.twitch().await.say(&channel, &reply).await
+    }
+
+    /// Handles `!done <id>` from the broadcaster or moderators of the channel a reward is for.
+    async fn complete_reward_from_chat(
+        &self,
+        payload: &eventsub::channel::ChannelChatMessageV1Payload,
+        claim: Option<&str>,
+    ) -> Result<(), Report> {
+        if !is_moderator(payload) {
+            return Ok(());
         }
-        // self.handle_client_post(res).expect("couldn't send message");
-        // info!("Message sent to channel: {}", channel.name);
-        // Ok(())
+        let Some(channel) = self
+            .channels
+            .lock()
+            .await
+            .find(payload.broadcaster_user_id.as_str())
+            .cloned()
+        else {
+            return Ok(());
+        };
+        let Some(claim) = claim.and_then(|c| c.trim_start_matches('#').parse::<i32>().ok()) else {
+            return self.notifier.twitch().await.say(&channel, "Usage: !done <id>").await;
+        };
+        let by = format!("chat:{}", payload.chatter_user_login);
+        let config = self.config.read().await.clone();
+        let done = rewards::complete(&self.store, &config, claim, Some(&channel), &by).await;
+        let reply = match done {
+            Ok(done) => format!("Reward #{} {} for {} is done", done.id, done.reward_name, done.donor_name),
+            Err(RewardError::Internal(e)) => return Err(e),
+            Err(e) => e.to_string(),
+        };
+        self.notifier.twitch().await.say(&channel, &reply).await
+    }
+
+    /// Answers `!raid` and `!whoslive` with the other participants that are live right now.
+    async fn suggest_raid_from_chat(
+        &self,
+        payload: &eventsub::channel::ChannelChatMessageV1Payload,
+    ) -> Result<(), Report> {
+        let channel_id = payload.broadcaster_user_id.as_str();
+        let Some(channel) = self.channels.lock().await.find(channel_id).cloned() else {
+            return Ok(());
+        };
+        let config = self.config.read().await.clone();
+        let campaign = config.channel_campaign(&channel);
+        let mut live = self.live_streams().await;
+        live.retain(|s| campaign.includes(&s.channel));
+        let last_raided = self.store.last_raided().await?;
+        let suggested = raids::suggest(
+            &live,
+            &last_raided,
+            &config.raids.suggest_order,
+            channel_id,
+            config.raids.suggestions,
+        );
+        let reply = if suggested.is_empty() {
+            format!("Nobody else from {} is live right now", campaign.name)
+        } else {
+            let streams: Vec<String> = suggested
+                .iter()
+                .map(|s| format!("{} ({} viewers)", s.channel.name, s.viewers))
+                .collect();
+            format!("Live for {}: {}", campaign.name, streams.join(", "))
+        };
+        self.notifier.twitch().await.say(&channel, &reply).await
+    }
+
+    async fn milestones_reached(&self, donation: &TiltifyDonation) -> Vec<Notification> {
+        let config = self.config.read().await;
+        reached_milestones(&self.store, &config, donation).await
     }
 
     #[tracing::instrument(skip(self))]
@@ -258,7 +1281,7 @@ impl Bot {
         event: Event,
         timestamp: twitch_api::types::Timestamp,
     ) -> Result<(), Report> {
-        let token = self.token.lock().await;
+        let token = self.token.lock().await.clone();
         match event {
             Event::ChannelChatMessageV1(Payload {
                 message: Message::Notification(payload),
@@ -269,15 +1292,61 @@ impl Bot {
                     "[{}] {}: {}",
                     timestamp, payload.chatter_user_name, payload.message.text
                 );
-                if let Some(command) = payload.message.text.strip_prefix("!") {
-                    let mut split_whitespace = command.split_whitespace();
-                    let command = split_whitespace.next().unwrap();
-                    let rest = split_whitespace.next();
-
-                    self.command(&payload, &subscription, command, rest, &token)
+                if let Some((command, args)) = chat_command(
+                    &payload.message.text,
+                    payload.chatter_user_id.as_str(),
+                    token.user_id.as_str(),
+                ) {
+                    self.command(&payload, &subscription, command, &args, &token)
                         .await?;
                 }
             }
+            Event::ChannelRaidV1(Payload {
+                message: Message::Notification(payload),
+                ..
+            }) => self.raided(payload).await?,
+            Event::StreamOnlineV1(Payload {
+                message: Message::Notification(payload),
+                ..
+            }) => {
+                let stream = self
+                    .store
+                    .start_stream(NewStream {
+                        channel_id: payload.broadcaster_user_id.as_str(),
+                        channel_name: payload.broadcaster_user_login.as_str(),
+                        started_at: chrono::Utc::now().naive_utc(),
+                    })
+                    .await?;
+                info!("{} went live, stream #{}", payload.broadcaster_user_login, stream.id);
+                let _ = self
+                    .tx
+                    .send(Commands::StreamStarted(payload.broadcaster_user_login.to_string()));
+            }
+            Event::StreamOfflineV1(Payload {
+                message: Message::Notification(payload),
+                ..
+            }) => {
+                let ended = self
+                    .store
+                    .end_stream(
+                        payload.broadcaster_user_id.as_str(),
+                        chrono::Utc::now().naive_utc(),
+                    )
+                    .await?;
+                match ended {
+                    Some(stream) => info!(
+                        "{} went offline, stream #{} ended",
+                        payload.broadcaster_user_login, stream.id
+                    ),
+                    None => warn!(
+                        "{} went offline without a recorded stream",
+                        payload.broadcaster_user_login
+                    ),
+                }
+                let _ = self
+                    .tx
+                    .send(Commands::StreamEnded(payload.broadcaster_user_login.to_string()));
+            }
             Event::ChannelChatNotificationV1(Payload {
                 message: Message::Notification(payload),
                 ..
@@ -303,15 +1372,28 @@ impl Bot {
     #[tracing::instrument(skip(self))]
     async fn command(
         &self,
-        _payload: &eventsub::channel::ChannelChatMessageV1Payload,
+        payload: &eventsub::channel::ChannelChatMessageV1Payload,
         _subscription: &eventsub::EventSubscriptionInformation<
             eventsub::channel::ChannelChatMessageV1,
         >,
         command: &str,
-        _rest: Option<&str>,
+        args: &[&str],
         _token: &UserToken,
     ) -> Result<(), Report> {
         info!("Command: {}", command);
+        match command {
+            "approve" => self.settle_from_chat(payload, true, args.first().copied()).await?,
+            "reject" => self.settle_from_chat(payload, false, args.first().copied()).await?,
+            "warbot" => self.settings_from_chat(payload, args).await?,
+            "raidtrain" => self.raid_train_from_chat(payload).await?,
+            "raid" | "whoslive" => self.suggest_raid_from_chat(payload).await?,
+            "raised" => self.raised_from_chat(payload, args.first().copied()).await?,
+            "rewards" => self.rewards_from_chat(payload).await?,
+            "poll" => self.poll_from_chat(payload).await?,
+            "targets" => self.targets_from_chat(payload).await?,
+            "done" => self.complete_reward_from_chat(payload, args.first().copied()).await?,
+            _ => {}
+        }
         // if let Some(response) = self.config.command.iter().find(|c| c.trigger == command) {
         //     self.client
         //         .send_chat_message_reply(
@@ -344,3 +1426,270 @@ impl Bot {
         }
     }
 }
+
+/// Updates the total of the donation's campaign and returns the milestones it passed. Simulated
+/// donations don't count toward the total, so they pass none.
+async fn reached_milestones(
+    store: &Store,
+    config: &Config,
+    donation: &TiltifyDonation,
+) -> Vec<Notification> {
+    if donation.simulated {
+        return Vec::new();
+    }
+    let key = config.campaign_key(
+        donation.campaign_id.as_deref(),
+        donation.team_event_id.as_deref(),
+    );
+    let total = match store.campaign_total(&config.campaign_scope(key)).await {
+        Ok(total) => total,
+        Err(e) => {
+            error!("Error reading campaign total: {e:?}");
+            return Vec::new();
+        }
+    };
+    metrics::CAMPAIGN_RAISED
+        .with_label_values(&[metrics::campaign_label(key)])
+        .set(total);
+    milestones_passed(config, donation, total)
+}
+
+/// Milestones and the goal of the donation's campaign that it passed, `total` being the
+/// campaign's total with it. Donations to supporting campaigns count toward the team event.
+fn milestones_passed(config: &Config, donation: &TiltifyDonation, total: f64) -> Vec<Notification> {
+    let Ok(amount) = donation.amount.value.parse::<f64>() else {
+        return Vec::new();
+    };
+    let key = config.campaign_key(
+        donation.campaign_id.as_deref(),
+        donation.team_event_id.as_deref(),
+    );
+    let campaign_id = match config.campaign_scope(key) {
+        CampaignScope::Id(id) => Some(id.to_string()),
+        CampaignScope::Except(_) => donation.campaign_id.clone(),
+    };
+    let before = total - amount;
+    let amount = |value: f64| Amount {
+        currency: donation.amount.currency.clone(),
+        value: format!("{value:.2}"),
+    };
+    config
+        .campaign(key)
+        .milestones()
+        .into_iter()
+        .filter(|&milestone| before < milestone && milestone <= total)
+        .map(|milestone| Notification::Milestone {
+            campaign_id: campaign_id.clone(),
+            milestone: amount(milestone),
+            total: amount(total),
+        })
+        .collect()
+}
+
+/// The command and arguments of a chat message starting with "!". Messages the bot sent itself
+/// are never commands, it would otherwise answer its own replies.
+fn chat_command<'a>(
+    text: &'a str,
+    chatter_id: &str,
+    bot_id: &str,
+) -> Option<(&'a str, Vec<&'a str>)> {
+    if chatter_id == bot_id {
+        return None;
+    }
+    let mut words = text.strip_prefix('!')?.split_whitespace();
+    Some((words.next()?, words.collect()))
+}
+
+/// Whether the sender of a chat message is the broadcaster or a moderator of that channel.
+fn is_moderator(payload: &eventsub::channel::ChannelChatMessageV1Payload) -> bool {
+    payload
+        .badges
+        .iter()
+        .any(|b| matches!(b.set_id.as_str(), "broadcaster" | "moderator"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignores_its_own_messages() {
+        assert_eq!(
+            chat_command("!warbot minimum 5", "1", "99"),
+            Some(("warbot", vec!["minimum", "5"]))
+        );
+        assert_eq!(chat_command("!warbot pause", "99", "99"), None);
+        // Even if another bot echoes it, the usage reply isn't read as a command.
+        assert_eq!(chat_command(SettingsCommand::USAGE, "99", "99"), None);
+        assert_eq!(chat_command(SettingsCommand::USAGE, "1", "99"), None);
+        assert_eq!(chat_command("!", "1", "99"), None);
+    }
+
+    #[tokio::test]
+    async fn keeps_what_was_deferred_while_paused_until_resumed() {
+        let store = Store::open(":memory:").unwrap();
+        let mut config = Config::default();
+        config.campaign.milestones = vec![10.0];
+        let donation = TiltifyDonation {
+            message: Some("Go!".to_string()),
+            ..TiltifyDonation::test("1", "15.00")
+        };
+        let milestones = milestones_passed(&config, &donation, 15.0);
+        assert_eq!(milestones.len(), 1);
+
+        store.defer(&Deferred::Donation(donation)).await.unwrap();
+        store.defer(&Deferred::Milestone(milestones[0].clone())).await.unwrap();
+
+        let deferred = store.take_deferred::<Deferred>().await.unwrap();
+        assert!(matches!(
+            &deferred[..],
+            [Deferred::Donation(d), Deferred::Milestone(m)]
+                if d.id == "1" && d.message.as_deref() == Some("Go!") && *m == milestones[0]
+        ));
+        assert!(store.take_deferred::<Deferred>().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn totals_the_default_campaign_without_an_id() {
+        let store = Store::open(":memory:").unwrap();
+        for (id, campaign_id, amount) in [
+            ("1", Some("spring"), "10.00"),
+            ("2", None, "5.00"),
+            ("3", Some("winter"), "20.00"),
+        ] {
+            let donation = TiltifyDonation {
+                campaign_id: campaign_id.map(str::to_string),
+                ..TiltifyDonation::test(id, amount)
+            };
+            store.insert_donation(&donation).await.unwrap();
+        }
+        let alice: Channel =
+            serde_json::from_value(serde_json::json!({ "user_id": "1", "name": "alice" })).unwrap();
+        let mut config = Config::default();
+
+        let total = store.campaign_total(&config.channel_scope(&alice)).await.unwrap();
+        assert_eq!(total, 35.0);
+        config.campaigns.insert("winter".to_string(), Default::default());
+        let total = store.campaign_total(&config.channel_scope(&alice)).await.unwrap();
+        assert_eq!(total, 15.0);
+        config.campaign.id = Some("spring".to_string());
+        let total = store.campaign_total(&config.channel_scope(&alice)).await.unwrap();
+        assert_eq!(total, 10.0);
+    }
+
+    #[tokio::test]
+    async fn leaves_simulated_donations_out_of_totals() {
+        let store = Store::open(":memory:").unwrap();
+        let config = Config::default();
+        let real = TiltifyDonation {
+            name: Some("Carol".to_string()),
+            ..TiltifyDonation::test("1", "10.00")
+        };
+        let simulated = TiltifyDonation {
+            simulated: true,
+            ..TiltifyDonation::test("2", "5.00")
+        };
+        store.insert_donation(&simulated).await.unwrap();
+        store.insert_donation(&real).await.unwrap();
+
+        let total = store.campaign_total(&config.campaign_scope(None)).await.unwrap();
+        assert_eq!(total, 10.0);
+        let earlier = TiltifyDonation {
+            simulated: true,
+            name: Some("Dave".to_string()),
+            ..TiltifyDonation::test("3", "1.00")
+        };
+        store.insert_donation(&earlier).await.unwrap();
+        assert!(!store.donated_before("Dave", "4").await.unwrap());
+        assert!(store.donated_before("Carol", "4").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn summarizes_donations_attributed_to_the_channel() {
+        let store = Store::open(":memory:").unwrap();
+        let mut config = Config::default();
+        config.campaigns.insert("winter".to_string(), Default::default());
+        let channels: Vec<Channel> = serde_json::from_value(serde_json::json!([
+            { "user_id": "1", "name": "alice" },
+            { "user_id": "2", "name": "bob" }
+        ]))
+        .unwrap();
+        let live = |names: &[&str]| -> Vec<LiveStream> {
+            channels
+                .iter()
+                .filter(|c| names.contains(&c.name.as_str()))
+                .map(|c| LiveStream {
+                    channel: c.clone(),
+                    viewers: 1,
+                })
+                .collect()
+        };
+        for (id, campaign_id, amount, live) in [
+            ("1", "spring", "10.00", live(&["alice", "bob"])),
+            ("2", "spring", "5.00", live(&["bob"])),
+            ("3", "winter", "20.00", live(&["alice"])),
+        ] {
+            let donation = TiltifyDonation {
+                campaign_id: Some(campaign_id.to_string()),
+                ..TiltifyDonation::test(id, amount)
+            };
+            store.insert_donation(&donation).await.unwrap();
+            let attributions = attribution::attribute(&donation, &channels, &config.team, &live);
+            store.attribute(&attributions).await.unwrap();
+        }
+        let now = chrono::Utc::now().naive_utc();
+        let hour = chrono::TimeDelta::hours(1);
+
+        let alice = &channels[0];
+        let scope = config.channel_scope(alice);
+        let during = store
+            .donations_between(&scope, alice.user_id.as_str(), now - hour, now + hour)
+            .await
+            .unwrap();
+        assert_eq!(during, (1, 10.0));
+    }
+
+    #[tokio::test]
+    async fn simulated_donations_pass_no_milestones() {
+        let store = Store::open(":memory:").unwrap();
+        let mut config = Config::default();
+        config.campaign.milestones = vec![50.0];
+        let real = TiltifyDonation::test("1", "100.00");
+        store.insert_donation(&real).await.unwrap();
+        assert_eq!(reached_milestones(&store, &config, &real).await.len(), 1);
+
+        let simulated = TiltifyDonation {
+            simulated: true,
+            ..TiltifyDonation::test("2", "60.00")
+        };
+        store.insert_donation(&simulated).await.unwrap();
+        assert!(reached_milestones(&store, &config, &simulated).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn team_milestones_count_every_participant() {
+        let store = Store::open(":memory:").unwrap();
+        let mut config = Config::default();
+        config.team.event_id = Some("team".to_string());
+        config.campaign.milestones = vec![25.0];
+        let mut reached = Vec::new();
+
+        for (id, participant) in [("1", "alice-campaign"), ("2", "bob-campaign")] {
+            let donation = TiltifyDonation {
+                campaign_id: Some(participant.to_string()),
+                team_event_id: Some("team".to_string()),
+                ..TiltifyDonation::test(id, "15.00")
+            };
+            store.insert_donation(&donation).await.unwrap();
+            let total = store.campaign_total(&config.campaign_scope(None)).await.unwrap();
+            reached.push(milestones_passed(&config, &donation, total));
+        }
+
+        assert!(reached[0].is_empty());
+        let [Notification::Milestone { campaign_id, milestone, total }] = &reached[1][..] else {
+            panic!("expected one milestone, got {:?}", reached[1]);
+        };
+        assert_eq!(campaign_id.as_deref(), Some("team"));
+        assert_eq!((milestone.value.as_str(), total.value.as_str()), ("25.00", "30.00"));
+    }
+}
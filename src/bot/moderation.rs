@@ -0,0 +1,117 @@
+use crate::bot::auth::{Channel, Streamers};
+use crate::bot::helix;
+use crate::config::{ModerationConfig, TwitchConfig};
+use crate::metrics;
+use eyre::Report;
+use std::time::Instant;
+use tracing::{Instrument, warn};
+use twitch_api::HelixClient;
+use twitch_api::helix::moderation::{CheckAutoModStatusBody, CheckAutoModStatusRequest};
+use twitch_oauth2::UserToken;
+
+/// Checks a donor comment against the local rules in `[moderation]`, returning why it was
+/// rejected.
+pub fn check_comment(config: &ModerationConfig, comment: &str) -> Result<(), String> {
+    if comment.chars().count() > config.max_comment_length {
+        return Err(format!(
+            "longer than {} characters",
+            config.max_comment_length
+        ));
+    }
+    let lowercase = comment.to_lowercase();
+    if let Some(term) = config
+        .blocklist
+        .iter()
+        .find(|term| lowercase.contains(&term.to_lowercase()))
+    {
+        return Err(format!("contains blocked term {term:?}"));
+    }
+    Ok(())
+}
+
+/// Checks a donor comment against the AutoMod settings of `channel`, returning why it was
+/// rejected.
+///
+/// AutoMod can only be asked with the broadcaster's own token, so the streamer has to be logged
+/// in through `auth streamer`. Channels whose streamer isn't are skipped with a warning, but a
+/// check that fails rejects the comment.
+pub async fn check_automod(
+    client: &HelixClient<'static, reqwest::Client>,
+    twitch: &TwitchConfig,
+    streamers: &mut Streamers,
+    streamers_path: &str,
+    channel: &Channel,
+    comment: &str,
+) -> Result<(), String> {
+    let token = match streamers
+        .token_for(client, twitch, streamers_path, channel)
+        .await
+    {
+        Ok(token) => token,
+        Err(e) => {
+            warn!("Not checking comment against AutoMod in {}: {e:?}", channel.name);
+            return Ok(());
+        }
+    };
+    match automod_permits(client, twitch, &token, comment).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err("rejected by AutoMod".to_string()),
+        Err(e) => {
+            warn!("AutoMod check in {} failed: {e:?}", channel.name);
+            Err(format!("AutoMod check failed: {e}"))
+        }
+    }
+}
+
+#[tracing::instrument(skip(client, twitch, token, comment))]
+async fn automod_permits(
+    client: &HelixClient<'static, reqwest::Client>,
+    twitch: &TwitchConfig,
+    token: &UserToken,
+    comment: &str,
+) -> Result<bool, Report> {
+    let req = CheckAutoModStatusRequest::broadcaster_id(&token.user_id);
+    let body = CheckAutoModStatusBody::new("comment", comment);
+    let bodies = [&body];
+    let started = Instant::now();
+    let res = helix::post(client, twitch, req, &bodies[..], token)
+        .in_current_span()
+        .await;
+    metrics::observe_helix("check_automod_status", started, &res);
+    Ok(res?.data.iter().all(|status| status.is_permitted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ModerationConfig {
+        ModerationConfig {
+            max_comment_length: 20,
+            blocklist: vec!["Spoiler".to_string()],
+            ..ModerationConfig::default()
+        }
+    }
+
+    #[test]
+    fn accepts_short_clean_comments() {
+        assert_eq!(check_comment(&config(), "Good luck!"), Ok(()));
+        assert_eq!(check_comment(&config(), "ünïcödé ünïcödé üü"), Ok(()));
+    }
+
+    #[test]
+    fn rejects_long_comments() {
+        assert_eq!(
+            check_comment(&config(), "This comment is far too long"),
+            Err("longer than 20 characters".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_blocked_terms_ignoring_case() {
+        assert_eq!(
+            check_comment(&config(), "big SPOILERS ahead"),
+            Err("contains blocked term \"Spoiler\"".to_string())
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use crate::bot::template;
+use crate::config::TemplatesConfig;
+use crate::routes::webhook::Amount;
+use crate::tiltify::{Poll, PollOption};
+
+fn raised(option: &PollOption) -> f64 {
+    option.amount_raised.value.parse().unwrap_or_default()
+}
+
+/// Each option with what it raised and its share, most raised first, in at most `limit`
+/// characters.
+pub fn standings(poll: &Poll, limit: usize) -> String {
+    let total: f64 = poll.options.iter().map(raised).sum();
+    let mut options: Vec<&PollOption> = poll.options.iter().collect();
+    options.sort_by(|a, b| raised(b).total_cmp(&raised(a)));
+    let mut standings = String::new();
+    for (listed, option) in options.iter().enumerate() {
+        let share = if total > 0.0 {
+            raised(option) / total * 100.0
+        } else {
+            0.0
+        };
+        let separator = if listed > 0 { ", " } else { "" };
+        let entry = format!(
+            "{separator}{} ${:.2} ({share:.0}%)",
+            option.name,
+            raised(option)
+        );
+        let more = format!(" and {} more", options.len() - listed);
+        if standings.chars().count() + entry.chars().count() + more.chars().count() > limit {
+            standings.push_str(&more);
+            return standings.trim_start().to_string();
+        }
+        standings.push_str(&entry);
+    }
+    standings
+}
+
+/// Fills `poll_vote` for a donation to `option`, with as much of the standings as chat allows.
+pub fn vote_announcement(
+    templates: &TemplatesConfig,
+    donor: &str,
+    amount: &Amount,
+    poll: &Poll,
+    option: &PollOption,
+) -> String {
+    let template = template::unsplit(&templates.poll_vote);
+    let render = |standings: &str| {
+        template::render(
+            &template,
+            &[
+                ("name", donor),
+                ("amount", &amount.value),
+                ("currency", &amount.currency),
+                ("poll", &poll.name),
+                ("option", &option.name),
+                ("standings", standings),
+            ],
+        )
+    };
+    let room = template::CHAT_LIMIT.saturating_sub(render("").chars().count());
+    render(&standings(poll, room))
+}
+
+/// The standings of every running poll as a single chat message.
+pub fn summary(polls: &[Poll]) -> String {
+    let running: Vec<&Poll> = polls.iter().filter(|p| p.active).collect();
+    if running.is_empty() {
+        return "No poll is running".to_string();
+    }
+    const SEPARATOR: &str = " | ";
+    let room = (template::CHAT_LIMIT / running.len()).saturating_sub(SEPARATOR.len());
+    let mut summary = String::new();
+    for (listed, poll) in running.iter().enumerate() {
+        let separator = if listed > 0 { SEPARATOR } else { "" };
+        let name = format!("{}: ", poll.name);
+        let standings = standings(poll, room.saturating_sub(name.chars().count()));
+        let entry = format!("{separator}{name}{standings}");
+        let more = format!(" and {} more", running.len() - listed);
+        if summary.chars().count() + entry.chars().count() + more.chars().count()
+            > template::CHAT_LIMIT
+        {
+            summary.push_str(&more);
+            return summary.trim_start().to_string();
+        }
+        summary.push_str(&entry);
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poll(name: &str, active: bool, options: &[(&str, &str)]) -> Poll {
+        Poll {
+            id: name.to_string(),
+            name: name.to_string(),
+            active,
+            options: options
+                .iter()
+                .map(|(name, raised)| PollOption {
+                    id: name.to_string(),
+                    name: name.to_string(),
+                    amount_raised: Amount {
+                        currency: "USD".to_string(),
+                        value: raised.to_string(),
+                    },
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn ranks_options_by_what_they_raised() {
+        let game = poll(
+            "Next game",
+            true,
+            &[("Tetris", "20.00"), ("Doom", "60.00"), ("Myst", "0")],
+        );
+
+        assert_eq!(
+            standings(&game, 500),
+            "Doom $60.00 (75%), Tetris $20.00 (25%), Myst $0.00 (0%)"
+        );
+        assert_eq!(standings(&game, 40), "Doom $60.00 (75%) and 2 more");
+        assert_eq!(
+            vote_announcement(
+                &TemplatesConfig::default(),
+                "Carol",
+                &Amount {
+                    currency: "USD".to_string(),
+                    value: "20.00".to_string(),
+                },
+                &game,
+                &game.options[0],
+            ),
+            "Carol voted for Tetris with $20.00! Next game: Doom $60.00 (75%), Tetris $20.00 (25%), Myst $0.00 (0%)"
+        );
+    }
+
+    #[test]
+    fn summarises_running_polls() {
+        let polls = [
+            poll("Next game", true, &[("Doom", "5.00")]),
+            poll("Old poll", false, &[("Yes", "1.00")]),
+            poll("Hat", true, &[("Red", "0"), ("Blue", "0")]),
+        ];
+
+        assert_eq!(
+            summary(&polls),
+            "Next game: Doom $5.00 (100%) | Hat: Red $0.00 (0%), Blue $0.00 (0%)"
+        );
+        assert_eq!(summary(&polls[1..2]), "No poll is running");
+        let crowded: Vec<Poll> = (0..10)
+            .map(|_| {
+                poll(
+                    "Crowded",
+                    true,
+                    &[("An option with a long name", "1.00"); 10],
+                )
+            })
+            .collect();
+        assert!(summary(&crowded).chars().count() <= template::CHAT_LIMIT);
+        let long_names: Vec<Poll> = (0..20)
+            .map(|i| {
+                let name = format!("{i} {}", "A poll with a long name ".repeat(3));
+                poll(&name, true, &[("Yes", "1.00")])
+            })
+            .collect();
+        let cut = summary(&long_names);
+        assert!(cut.chars().count() <= template::CHAT_LIMIT);
+        assert!(cut.starts_with("0 A poll") && cut.ends_with(" more"));
+    }
+}
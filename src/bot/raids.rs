@@ -0,0 +1,254 @@
+use crate::bot::auth::LiveStream;
+use crate::bot::template;
+use crate::config::RaidOrder;
+use crate::db::models::Raid;
+use chrono::NaiveDateTime;
+use serde_derive::Serialize;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Raids between participants where each one left from the channel the previous one went to.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RaidTrain {
+    pub raids: Vec<Raid>,
+}
+
+impl RaidTrain {
+    /// The channels in the order the train passed them.
+    pub fn channels(&self) -> Vec<&str> {
+        let mut channels: Vec<&str> = self.raids.iter().map(|r| r.from_name.as_str()).collect();
+        channels.extend(self.raids.last().map(|r| r.to_name.as_str()));
+        channels
+    }
+
+    pub fn involves(&self, channel_id: &str) -> bool {
+        self.raids
+            .iter()
+            .any(|r| r.from_id == channel_id || r.to_id == channel_id)
+    }
+
+    /// A one line summary for `!raidtrain`, naming as many channels as fit in a chat message.
+    pub fn summary(&self) -> String {
+        let raids = self.raids.len();
+        let totals = format!(
+            " ({raids} {}, {} viewers carried)",
+            if raids == 1 { "raid" } else { "raids" },
+            self.raids.iter().map(|r| r.viewers).sum::<i64>()
+        );
+        let channels = self.channels();
+        let mut summary = "Raid train:".to_string();
+        for (listed, channel) in channels.iter().enumerate() {
+            let entry = if listed > 0 {
+                format!(" → {channel}")
+            } else {
+                format!(" {channel}")
+            };
+            let more = format!(" and {} more", channels.len() - listed);
+            if summary.chars().count()
+                + entry.chars().count()
+                + more.chars().count()
+                + totals.chars().count()
+                > template::CHAT_LIMIT
+            {
+                summary.push_str(&more);
+                break;
+            }
+            summary.push_str(&entry);
+        }
+        summary.push_str(&totals);
+        summary
+    }
+}
+
+/// Groups raids, oldest first, into trains. A raid continues the latest train that ended in
+/// the channel it left from, otherwise it starts a new one. Trains keep the order they started in.
+pub fn trains(raids: Vec<Raid>) -> Vec<RaidTrain> {
+    let mut trains: Vec<RaidTrain> = Vec::new();
+    for raid in raids {
+        let continued = trains.iter_mut().rev().find(|train| {
+            train
+                .raids
+                .last()
+                .is_some_and(|last| last.to_id == raid.from_id)
+        });
+        match continued {
+            Some(train) => train.raids.push(raid),
+            None => trains.push(RaidTrain { raids: vec![raid] }),
+        }
+    }
+    trains
+}
+
+/// Live participants to raid, ordered by `order`, leaving out the channel asking.
+pub fn suggest<'a>(
+    live: &'a [LiveStream],
+    last_raided: &HashMap<String, NaiveDateTime>,
+    order: &[RaidOrder],
+    caller_id: &str,
+    limit: usize,
+) -> Vec<&'a LiveStream> {
+    let mut candidates: Vec<&LiveStream> = live
+        .iter()
+        .filter(|s| s.channel.user_id.as_str() != caller_id)
+        .collect();
+    let raided = |s: &LiveStream| last_raided.get(s.channel.user_id.as_str());
+    candidates.sort_by(|a, b| {
+        order
+            .iter()
+            .map(|rule| match rule {
+                RaidOrder::FewestViewers => a.viewers.cmp(&b.viewers),
+                RaidOrder::MostViewers => b.viewers.cmp(&a.viewers),
+                // `None` sorts first, channels nobody raided yet are the longest wait.
+                RaidOrder::LongestSinceRaided => raided(a).cmp(&raided(b)),
+            })
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+    candidates.truncate(limit);
+    candidates
+}
+
+/// Live participants from Helix, reused until they are older than the given age.
+#[derive(Default)]
+pub struct LiveCache {
+    fetched: Mutex<Option<(Instant, Vec<LiveStream>)>>,
+}
+
+impl LiveCache {
+    pub async fn get<F>(&self, max_age: Duration, fetch: impl FnOnce() -> F) -> Vec<LiveStream>
+    where
+        F: Future<Output = Vec<LiveStream>>,
+    {
+        let mut fetched = self.fetched.lock().await;
+        if let Some((at, live)) = fetched.as_ref()
+            && at.elapsed() < max_age
+        {
+            return live.clone();
+        }
+        let live = fetch().await;
+        *fetched = Some((Instant::now(), live.clone()));
+        live
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RaidsConfig;
+    use crate::db::Store;
+    use crate::db::models::NewRaid;
+
+    fn raid(id: i32, from: &str, to: &str, viewers: i64) -> Raid {
+        Raid {
+            id,
+            from_id: from.to_string(),
+            from_name: from.to_string(),
+            to_id: to.to_string(),
+            to_name: to.to_string(),
+            viewers,
+            raided_at: chrono::DateTime::from_timestamp(i64::from(id) * 60, 0)
+                .unwrap()
+                .naive_utc(),
+        }
+    }
+
+    #[test]
+    fn chains_raids_into_trains() {
+        let trains = trains(vec![
+            raid(1, "alice", "bob", 10),
+            raid(2, "carol", "dave", 5),
+            raid(3, "bob", "carol", 20),
+            raid(4, "dave", "erin", 7),
+        ]);
+
+        assert_eq!(trains.len(), 2);
+        assert_eq!(trains[0].channels(), ["alice", "bob", "carol"]);
+        assert_eq!(trains[1].channels(), ["carol", "dave", "erin"]);
+        assert!(trains[1].involves("erin"));
+        assert!(!trains[1].involves("alice"));
+        assert_eq!(
+            trains[0].summary(),
+            "Raid train: alice → bob → carol (2 raids, 30 viewers carried)"
+        );
+
+        let names: Vec<String> = (0..40).map(|i| format!("participant_{i:02}")).collect();
+        let long = RaidTrain {
+            raids: names
+                .windows(2)
+                .zip(1..)
+                .map(|(pair, id)| raid(id, &pair[0], &pair[1], 1))
+                .collect(),
+        };
+        let summary = long.summary();
+        assert!(summary.chars().count() <= template::CHAT_LIMIT);
+        assert!(summary.starts_with("Raid train: participant_00 → participant_01"));
+        assert!(summary.ends_with("more (39 raids, 39 viewers carried)"));
+    }
+
+    #[test]
+    fn suggests_live_participants_in_order() {
+        let stream = |name: &str, viewers| LiveStream {
+            channel: serde_json::from_value(serde_json::json!({ "user_id": name, "name": name }))
+                .unwrap(),
+            viewers,
+        };
+        let live = [
+            stream("alice", 5),
+            stream("bob", 50),
+            stream("carol", 20),
+            stream("dave", 20),
+        ];
+        let last_raided = HashMap::from([(
+            "carol".to_string(),
+            chrono::Utc::now().naive_utc(),
+        )]);
+        let names = |suggested: Vec<&LiveStream>| -> Vec<String> {
+            suggested.iter().map(|s| s.channel.name.to_string()).collect()
+        };
+
+        let order = [RaidOrder::LongestSinceRaided, RaidOrder::FewestViewers];
+        assert_eq!(
+            names(suggest(&live, &last_raided, &order, "alice", 5)),
+            ["dave", "bob", "carol"]
+        );
+        let order = [RaidOrder::MostViewers];
+        assert_eq!(
+            names(suggest(&live, &last_raided, &order, "bob", 2)),
+            ["carol", "dave"]
+        );
+    }
+
+    #[tokio::test]
+    async fn builds_trains_from_stored_raids() {
+        let store = Store::open(":memory:").unwrap();
+        let old = chrono::Utc::now().naive_utc() - chrono::Duration::days(2);
+        for (from, to, raided_at) in [
+            ("alice", "bob", old),
+            ("bob", "carol", chrono::Utc::now().naive_utc()),
+        ] {
+            store
+                .record_raid(NewRaid {
+                    from_id: from,
+                    from_name: from,
+                    to_id: to,
+                    to_name: to,
+                    viewers: 10,
+                    raided_at,
+                })
+                .await
+                .unwrap();
+        }
+
+        let since = RaidsConfig::default().history_start();
+        let trains = trains(store.raids_since(since).await.unwrap());
+
+        assert_eq!(trains.len(), 1);
+        assert_eq!(trains[0].channels(), ["bob", "carol"]);
+        let last_raided = store.last_raided().await.unwrap();
+        assert_eq!(last_raided.len(), 2);
+        assert_eq!(last_raided["bob"], old);
+    }
+}
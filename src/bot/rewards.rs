@@ -0,0 +1,297 @@
+use crate::bot::attribution;
+use crate::bot::auth::Channel;
+use crate::bot::template;
+use crate::config::{Config, TemplatesConfig};
+use crate::db::Store;
+use crate::db::models::{NewRewardClaim, RewardClaim};
+use crate::metrics;
+use crate::routes::tiltify::TiltifyDonation;
+use crate::tiltify::TiltifyClient;
+use eyre::Report;
+use thiserror::Error;
+use tracing::{info, warn};
+
+#[derive(Debug, Error)]
+pub enum RewardError {
+    #[error("reward #{0} not found")]
+    NotFound(i32),
+    #[error("reward #{0} is already done")]
+    AlreadyDone(i32),
+    #[error("reward #{0} is for {1}")]
+    OtherChannel(i32, String),
+    #[error(transparent)]
+    Internal(#[from] Report),
+}
+
+/// Queues the rewards claimed with a donation under the names Tiltify knows them by. Each one
+/// is credited to the participant offering it, else to the one the donation is credited to.
+pub async fn record(
+    store: &Store,
+    tiltify: &TiltifyClient,
+    donation: &TiltifyDonation,
+    donor_name: &str,
+    channels: &[Channel],
+    config: &Config,
+) -> Result<Vec<RewardClaim>, Report> {
+    let claimed = donation.claimed_rewards();
+    let mut names = Vec::new();
+    for reward_id in claimed.iter().filter_map(|c| c.reward_id.as_deref()) {
+        let name = match tiltify.reward_name(donation, reward_id).await {
+            Ok(name) => name,
+            Err(e) => {
+                warn!("Couldn't look up reward {reward_id}: {e:?}");
+                None
+            }
+        };
+        names.push(name.unwrap_or_else(|| reward_id.to_string()));
+    }
+    let now = chrono::Utc::now().naive_utc();
+    let claims: Vec<NewRewardClaim> = claimed
+        .iter()
+        .filter_map(|claim| Some((claim, claim.reward_id.as_deref()?)))
+        .zip(names)
+        .map(|((claim, reward_id), reward_name)| {
+            let channel = channels
+                .iter()
+                .find(|c| c.settings.rewards.iter().any(|r| r == reward_id))
+                .or_else(|| attribution::credited(donation, channels, &config.team));
+            NewRewardClaim {
+                donation_id: &donation.id,
+                donor_name,
+                reward_id,
+                reward_name,
+                quantity: claim.quantity.try_into().unwrap_or(i32::MAX),
+                response: claim.custom_question_response.as_deref(),
+                channel_id: channel.map(|c| c.user_id.as_str()),
+                channel_name: channel.map(|c| c.name.as_str()),
+                status: "pending",
+                claimed_at: now,
+            }
+        })
+        .collect();
+    if claims.is_empty() {
+        return Ok(Vec::new());
+    }
+    let recorded = store.record_reward_claims(&claims).await?;
+    let campaign = metrics::donation_campaign_label(
+        config,
+        donation.campaign_id.as_deref(),
+        donation.team_event_id.as_deref(),
+    );
+    for claim in &recorded {
+        metrics::REWARD_CLAIMS
+            .with_label_values(&[campaign, "pending"])
+            .inc();
+        info!(
+            "Donation {} claimed reward {} as #{}",
+            claim.donation_id, claim.reward_name, claim.id
+        );
+    }
+    Ok(recorded)
+}
+
+/// Marks a pending reward claim done. Only claims credited to `channel` or to nobody can be
+/// completed from a channel.
+pub async fn complete(
+    store: &Store,
+    config: &Config,
+    claim: i32,
+    channel: Option<&Channel>,
+    by: &str,
+) -> Result<RewardClaim, RewardError> {
+    let existing = store
+        .reward_claim(claim)
+        .await?
+        .ok_or(RewardError::NotFound(claim))?;
+    if let Some(channel) = channel
+        && let (Some(id), Some(name)) = (&existing.channel_id, &existing.channel_name)
+        && id != channel.user_id.as_str()
+    {
+        return Err(RewardError::OtherChannel(claim, name.clone()));
+    }
+    let done = store
+        .complete_reward_claim(claim, by)
+        .await?
+        .ok_or(RewardError::AlreadyDone(claim))?;
+    let donation = store.donation(&done.donation_id).await?;
+    let campaign = metrics::donation_campaign_label(
+        config,
+        donation.as_ref().and_then(|d| d.campaign_id.as_deref()),
+        donation.as_ref().and_then(|d| d.team_event_id.as_deref()),
+    );
+    metrics::REWARD_CLAIMS
+        .with_label_values(&[campaign, "done"])
+        .inc();
+    info!("Reward #{claim} ({}) done by {by}", done.reward_name);
+    Ok(done)
+}
+
+/// Fills `reward_claim` for a claim made with `donation`.
+pub fn announcement(
+    templates: &TemplatesConfig,
+    claim: &RewardClaim,
+    donation: &TiltifyDonation,
+) -> String {
+    template::render(
+        &template::unsplit(&templates.reward_claim),
+        &[
+            ("id", &claim.id.to_string()),
+            ("name", &claim.donor_name),
+            ("reward", &claim.reward_name),
+            ("quantity", &claim.quantity.to_string()),
+            ("amount", &donation.amount.value),
+            ("currency", &donation.amount.currency),
+        ],
+    )
+}
+
+/// The pending claims as a single chat message, oldest first, with what doesn't fit counted.
+pub fn queue_summary(claims: &[RewardClaim]) -> String {
+    if claims.is_empty() {
+        return "No rewards are waiting".to_string();
+    }
+    let mut summary = "Rewards to do:".to_string();
+    for (listed, claim) in claims.iter().enumerate() {
+        let quantity = if claim.quantity > 1 {
+            format!(" x{}", claim.quantity)
+        } else {
+            String::new()
+        };
+        let entry = format!(
+            " #{} {}{quantity} for {};",
+            claim.id, claim.reward_name, claim.donor_name
+        );
+        let more = format!(" and {} more", claims.len() - listed);
+        if summary.chars().count() + entry.chars().count() + more.chars().count()
+            > template::CHAT_LIMIT
+        {
+            summary.pop();
+            summary.push_str(&more);
+            return summary;
+        }
+        summary.push_str(&entry);
+    }
+    summary.pop();
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::webhook::RewardClaim as Claimed;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    fn channel(name: &str, settings: serde_json::Value) -> Channel {
+        serde_json::from_value(serde_json::json!({
+            "user_id": name,
+            "name": name,
+            "settings": settings,
+        }))
+        .unwrap()
+    }
+
+    fn claimed(reward_id: &str, quantity: u32) -> Claimed {
+        Claimed {
+            id: None,
+            reward_id: Some(reward_id.to_string()),
+            quantity,
+            custom_question_response: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn queues_claims_until_they_are_done() {
+        let store = Store::open(":memory:").unwrap();
+        let config = Config::default();
+        // Without `[tiltify]` credentials rewards keep their ids as names.
+        let tiltify = TiltifyClient::new(Arc::new(RwLock::new(config.clone())));
+        let channels = [
+            channel(
+                "alice",
+                serde_json::json!({ "campaign_id": "alice-campaign" }),
+            ),
+            channel("bob", serde_json::json!({ "rewards": ["song"] })),
+        ];
+        let donation = TiltifyDonation {
+            campaign_id: Some("alice-campaign".to_string()),
+            name: Some("Carol".to_string()),
+            reward_claims: vec![claimed("song", 2), claimed("hat", 1)],
+            ..TiltifyDonation::test("1", "25.00")
+        };
+        store.insert_donation(&donation).await.unwrap();
+
+        let claims = record(
+            &store,
+            &tiltify,
+            &donation,
+            "Carol",
+            &channels,
+            &config,
+        )
+        .await
+        .unwrap();
+
+        let credited: Vec<_> = claims.iter().map(|c| c.channel_name.as_deref()).collect();
+        assert_eq!(credited, [Some("bob"), Some("alice")]);
+        assert_eq!(
+            announcement(&TemplatesConfig::default(), &claims[0], &donation),
+            format!(
+                "Carol claimed song! It is reward #{} in the queue",
+                claims[0].id
+            )
+        );
+        let (song, hat) = (claims[0].id, claims[1].id);
+        assert!(matches!(
+            complete(&store, &config, song, Some(&channels[0]), "alice").await,
+            Err(RewardError::OtherChannel(..))
+        ));
+        let done = complete(&store, &config, song, Some(&channels[1]), "bob")
+            .await
+            .unwrap();
+        assert_eq!(done.status, "done");
+        assert!(matches!(
+            complete(&store, &config, song, None, "admin").await,
+            Err(RewardError::AlreadyDone(_))
+        ));
+        assert!(matches!(
+            complete(&store, &config, 99, None, "admin").await,
+            Err(RewardError::NotFound(99))
+        ));
+        let pending = store.pending_reward_claims(Some("alice")).await.unwrap();
+        assert_eq!(pending.iter().map(|c| c.id).collect::<Vec<_>>(), [hat]);
+        assert_eq!(
+            queue_summary(&pending),
+            format!("Rewards to do: #{hat} hat for Carol")
+        );
+    }
+
+    #[test]
+    fn summarises_queues_that_dont_fit_into_chat() {
+        let claim = |id| RewardClaim {
+            id,
+            donation_id: id.to_string(),
+            donor_name: "Carol".to_string(),
+            reward_id: "song".to_string(),
+            reward_name: "A very long song request that takes up room".to_string(),
+            quantity: 2,
+            response: None,
+            channel_id: None,
+            channel_name: None,
+            status: "pending".to_string(),
+            claimed_at: chrono::Utc::now().naive_utc(),
+            done_at: None,
+            done_by: None,
+        };
+        let claims: Vec<RewardClaim> = (1..=20).map(claim).collect();
+
+        let summary = queue_summary(&claims);
+
+        assert!(summary.chars().count() <= template::CHAT_LIMIT);
+        assert!(summary.starts_with(
+            "Rewards to do: #1 A very long song request that takes up room x2 for Carol;"
+        ));
+        assert!(summary.ends_with("for Carol and 13 more"), "{summary}");
+        assert_eq!(queue_summary(&[]), "No rewards are waiting");
+    }
+}
@@ -0,0 +1,330 @@
+use crate::config::{CampaignConfig, Config, TemplateOverrides, TemplatesConfig};
+use crate::notify::Notification;
+use chrono::{DateTime, FixedOffset, NaiveTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use twitch_api::extra::AnnouncementColor;
+
+/// How the bot treats a single channel, stored with it in `channels.json`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ChannelSettings {
+    /// Whether announcements (and donor comments) are posted.
+    pub announcements: bool,
+    /// Whether the plain `donation_message` is posted.
+    pub messages: bool,
+    /// Donations below this amount aren't mentioned.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_amount: Option<f64>,
+    pub color: AnnouncementColor,
+    /// Picks the templates from `[translations.<language>]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Templates for this channel only, on top of its language.
+    #[serde(skip_serializing_if = "TemplateOverrides::is_empty")]
+    pub templates: TemplateOverrides,
+    /// Nothing is posted during these hours.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quiet_hours: Option<QuietHours>,
+    /// The Tiltify campaign of this channel.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub campaign_id: Option<String>,
+    /// Only mention donations and milestones of this channel's own campaign.
+    pub home_only: bool,
+    /// Tiltify targets whose donations are credited to this channel.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<String>,
+    /// Tiltify rewards whose donations are credited to this channel.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rewards: Vec<String>,
+    /// Set with `!warbot pause`, nothing is posted until `!warbot resume`.
+    pub paused: bool,
+}
+
+impl Default for ChannelSettings {
+    fn default() -> Self {
+        Self {
+            announcements: true,
+            messages: true,
+            min_amount: None,
+            color: AnnouncementColor::Orange,
+            language: None,
+            templates: TemplateOverrides::default(),
+            quiet_hours: None,
+            campaign_id: None,
+            home_only: false,
+            targets: Vec::new(),
+            rewards: Vec::new(),
+            paused: false,
+        }
+    }
+}
+
+impl ChannelSettings {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether the channel wants to hear about a notification at all.
+    pub fn wants(&self, notification: &Notification) -> bool {
+        let campaign = match notification {
+            Notification::Donation {
+                amount,
+                campaign_id,
+                ..
+            } => {
+                if let Some(min) = self.min_amount
+                    && amount.value.parse::<f64>().is_ok_and(|amount| amount < min)
+                {
+                    return false;
+                }
+                campaign_id
+            }
+            Notification::Milestone { campaign_id, .. } => campaign_id,
+            _ => return true,
+        };
+        !self.home_only || (self.campaign_id.is_some() && *campaign == self.campaign_id)
+    }
+
+    /// Whether the channel is paused or in its quiet hours at `now`.
+    pub fn is_quiet(&self, now: DateTime<Utc>) -> bool {
+        self.paused || self.quiet_hours.as_ref().is_some_and(|q| q.contains(now))
+    }
+
+    /// Whether plain messages may be posted at `now`.
+    pub fn posts_messages(&self, now: DateTime<Utc>) -> bool {
+        self.messages && !self.is_quiet(now)
+    }
+
+    /// A one line summary for `!warbot status`.
+    pub fn summary(&self) -> String {
+        let mut parts = vec![if self.paused { "paused" } else { "active" }.to_string()];
+        if let Some(min) = self.min_amount {
+            parts.push(format!("minimum {min}"));
+        }
+        if !self.announcements {
+            parts.push("no announcements".to_string());
+        }
+        if !self.messages {
+            parts.push("no messages".to_string());
+        }
+        if let Some(quiet) = &self.quiet_hours {
+            parts.push(format!("quiet {}-{}", quiet.from.format("%H:%M"), quiet.to.format("%H:%M")));
+        }
+        if let Some(language) = &self.language {
+            parts.push(format!("language {language}"));
+        }
+        if self.home_only {
+            parts.push("home campaign only".to_string());
+        }
+        parts.join(", ")
+    }
+
+    /// The templates for this channel posting for `campaign`: `[templates]`, then the
+    /// campaign's, then its language, then its own.
+    pub fn templates(&self, config: &Config, campaign: &CampaignConfig) -> TemplatesConfig {
+        let mut templates = config.templates.overridden(&campaign.templates);
+        if let Some(translation) = self
+            .language
+            .as_ref()
+            .and_then(|language| config.translations.get(language))
+        {
+            templates = templates.overridden(translation);
+        }
+        templates.overridden(&self.templates)
+    }
+
+    /// Problems with these settings, for rejecting them before they are stored.
+    pub fn problems(&self, channel: &str) -> Vec<String> {
+        let mut problems = self.templates.problems(&format!("{channel}.templates"));
+        if self.min_amount.is_some_and(|min| !min.is_finite() || min < 0.0) {
+            problems.push(format!("{channel}.min_amount must not be negative"));
+        }
+        if self.home_only && self.campaign_id.is_none() {
+            problems.push(format!("{channel}.home_only needs a campaign_id"));
+        }
+        if let Some(quiet) = &self.quiet_hours
+            && quiet.offset().is_none()
+        {
+            problems.push(format!("{channel}.quiet_hours.utc_offset_minutes is out of range"));
+        }
+        problems
+    }
+}
+
+/// A `!warbot` command broadcasters and moderators use in their own chat.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SettingsCommand {
+    Pause,
+    Resume,
+    /// Sets or, with `off`, clears the minimum amount.
+    Minimum(Option<f64>),
+    Status,
+}
+
+impl SettingsCommand {
+    pub const USAGE: &str = "Usage: !warbot pause | resume | minimum <amount|off> | status";
+
+    /// Parses the words after `!warbot`.
+    pub fn parse(args: &[&str]) -> Option<Self> {
+        match args {
+            ["pause"] => Some(SettingsCommand::Pause),
+            ["resume"] => Some(SettingsCommand::Resume),
+            ["status"] => Some(SettingsCommand::Status),
+            ["minimum", "off"] => Some(SettingsCommand::Minimum(None)),
+            ["minimum", amount] => {
+                let amount = amount.trim_start_matches('$').parse::<f64>().ok()?;
+                (amount.is_finite() && amount >= 0.0)
+                    .then_some(SettingsCommand::Minimum((amount > 0.0).then_some(amount)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Applies the command, returning whether the settings changed.
+    pub fn apply(&self, settings: &mut ChannelSettings) -> bool {
+        let before = settings.clone();
+        match self {
+            SettingsCommand::Pause => settings.paused = true,
+            SettingsCommand::Resume => settings.paused = false,
+            SettingsCommand::Minimum(min) => settings.min_amount = *min,
+            SettingsCommand::Status => {}
+        }
+        *settings != before
+    }
+}
+
+/// A daily window, in the channel's UTC offset. It may wrap past midnight.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QuietHours {
+    pub from: NaiveTime,
+    pub to: NaiveTime,
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+}
+
+impl QuietHours {
+    fn offset(&self) -> Option<FixedOffset> {
+        FixedOffset::east_opt(self.utc_offset_minutes.checked_mul(60)?)
+    }
+
+    pub fn contains(&self, now: DateTime<Utc>) -> bool {
+        let Some(offset) = self.offset() else {
+            return false;
+        };
+        let time = now.with_timezone(&offset).time();
+        if self.from <= self.to {
+            self.from <= time && time < self.to
+        } else {
+            time >= self.from || time < self.to
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::webhook::Amount;
+    use chrono::TimeZone;
+
+    fn donation(amount: &str, campaign_id: Option<&str>) -> Notification {
+        Notification::Donation {
+            id: "donation".to_string(),
+            campaign_id: campaign_id.map(str::to_string),
+            team_event_id: None,
+            amount: Amount {
+                currency: "USD".to_string(),
+                value: amount.to_string(),
+            },
+            name: "Carol".to_string(),
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn filters_small_and_foreign_donations() {
+        let settings = ChannelSettings {
+            min_amount: Some(50.0),
+            campaign_id: Some("home".to_string()),
+            home_only: true,
+            ..ChannelSettings::default()
+        };
+
+        assert!(settings.wants(&donation("50.00", Some("home"))));
+        assert!(!settings.wants(&donation("49.99", Some("home"))));
+        assert!(!settings.wants(&donation("500.00", Some("other"))));
+        assert!(settings.wants(&Notification::StreamStarted {
+            channel: "alice".to_string()
+        }));
+    }
+
+    #[test]
+    fn parses_and_applies_commands() {
+        let mut settings = ChannelSettings::default();
+
+        let minimum = SettingsCommand::parse(&["minimum", "$10"]).unwrap();
+        assert!(minimum.apply(&mut settings));
+        assert!(!minimum.apply(&mut settings));
+        assert!(SettingsCommand::parse(&["pause"]).unwrap().apply(&mut settings));
+        assert_eq!(settings.summary(), "paused, minimum 10");
+        assert!(settings.is_quiet(Utc::now()));
+
+        assert_eq!(
+            SettingsCommand::parse(&["minimum", "0"]),
+            Some(SettingsCommand::Minimum(None))
+        );
+        assert_eq!(SettingsCommand::parse(&["minimum", "-5"]), None);
+        assert_eq!(SettingsCommand::parse(&["minimum"]), None);
+        assert_eq!(SettingsCommand::parse(&["dance"]), None);
+    }
+
+    #[test]
+    fn quiet_hours_wrap_past_midnight() {
+        let quiet = QuietHours {
+            from: NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+            to: NaiveTime::from_hms_opt(6, 0, 0).unwrap(),
+            utc_offset_minutes: 120,
+        };
+        let at = |hour| Utc.with_ymd_and_hms(2026, 10, 18, hour, 30, 0).unwrap();
+
+        assert!(quiet.contains(at(20)));
+        assert!(quiet.contains(at(3)));
+        assert!(!quiet.contains(at(4)));
+        assert!(!quiet.contains(at(12)));
+    }
+
+    #[test]
+    fn layers_templates() {
+        let mut config = Config::default();
+        let campaign = CampaignConfig {
+            templates: TemplateOverrides {
+                donation_message: Some("!winter {amount}".to_string()),
+                donation_announcement: Some("Winter!".to_string()),
+                ..TemplateOverrides::default()
+            },
+            ..CampaignConfig::default()
+        };
+        config.translations.insert(
+            "de".to_string(),
+            TemplateOverrides {
+                donation_announcement: Some("Danke {name}!".to_string()),
+                raid_announcement: Some("Raid!".to_string()),
+                ..TemplateOverrides::default()
+            },
+        );
+        let settings = ChannelSettings {
+            language: Some("de".to_string()),
+            templates: TemplateOverrides {
+                raid_announcement: Some("{from} raidet {to}!".to_string()),
+                ..TemplateOverrides::default()
+            },
+            ..ChannelSettings::default()
+        };
+
+        let templates = settings.templates(&config, &campaign);
+
+        assert_eq!(templates.donation_announcement, "Danke {name}!");
+        assert_eq!(templates.raid_announcement, "{from} raidet {to}!");
+        assert_eq!(templates.donation_message, "!winter {amount}");
+        assert_eq!(templates.milestone_announcement, config.templates.milestone_announcement);
+    }
+}
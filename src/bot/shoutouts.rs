@@ -0,0 +1,201 @@
+use crate::bot::auth::Channel;
+use crate::bot::helix;
+use crate::config::TwitchConfig;
+use crate::metrics;
+use eyre::Report;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tracing::{Instrument, info};
+use twitch_api::HelixClient;
+use twitch_api::helix::{ClientRequestError, HelixRequestPostError};
+use twitch_api::helix::chat::SendAShoutoutRequest;
+use twitch_oauth2::UserToken;
+
+/// Twitch lets a channel give one shoutout every two minutes.
+const CHANNEL_COOLDOWN: Duration = Duration::from_secs(2 * 60);
+/// Twitch lets a channel shout out the same streamer once an hour.
+const TARGET_COOLDOWN: Duration = Duration::from_secs(60 * 60);
+/// Queued shoutouts are given up once every cooldown must have passed and they still fail.
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(65 * 60);
+
+#[derive(Debug, Error)]
+pub enum ShoutoutError {
+    #[error("shoutout is on cooldown")]
+    Cooldown,
+    #[error(transparent)]
+    Failed(#[from] Report),
+}
+
+/// A shoutout the raided channel `from` gives the raider `to`.
+#[derive(Debug, Clone)]
+pub struct Shoutout {
+    pub from: Channel,
+    pub to: Channel,
+    queued_at: Instant,
+}
+
+impl Shoutout {
+    pub fn new(from: Channel, to: Channel, now: Instant) -> Self {
+        Self {
+            from,
+            to,
+            queued_at: now,
+        }
+    }
+
+    fn key(&self) -> (String, String) {
+        (self.from.user_id.to_string(), self.to.user_id.to_string())
+    }
+}
+
+/// Shoutouts waiting for Twitch's cooldowns, and when the cooldowns started.
+#[derive(Debug, Default)]
+pub struct Shoutouts {
+    by_channel: HashMap<String, Instant>,
+    by_target: HashMap<(String, String), Instant>,
+    queue: VecDeque<Shoutout>,
+}
+
+impl Shoutouts {
+    /// Queues a shoutout, unless the same one is waiting already.
+    pub fn push(&mut self, shoutout: Shoutout) {
+        if !self.queue.iter().any(|s| s.key() == shoutout.key()) {
+            self.queue.push_back(shoutout);
+        }
+    }
+
+    /// Takes the queued shoutouts whose cooldowns passed, at most one per channel.
+    /// Shoutouts that waited too long are dropped.
+    pub fn due(&mut self, now: Instant) -> Vec<Shoutout> {
+        let mut due: Vec<Shoutout> = Vec::new();
+        let mut waiting = VecDeque::new();
+        for shoutout in self.queue.drain(..) {
+            if now.duration_since(shoutout.queued_at) > QUEUE_TIMEOUT {
+                info!(
+                    "Giving up on shoutout for {} in {}",
+                    shoutout.to.name, shoutout.from.name
+                );
+                metrics::SHOUTOUTS.with_label_values(&["expired"]).inc();
+                continue;
+            }
+            let cooling = |since: Option<&Instant>, cooldown| {
+                since.is_some_and(|since| now.duration_since(*since) < cooldown)
+            };
+            let must_wait = due.iter().any(|d| d.from.user_id == shoutout.from.user_id)
+                || cooling(
+                    self.by_channel.get(shoutout.from.user_id.as_str()),
+                    CHANNEL_COOLDOWN,
+                )
+                || cooling(self.by_target.get(&shoutout.key()), TARGET_COOLDOWN);
+            if must_wait {
+                waiting.push_back(shoutout);
+            } else {
+                due.push(shoutout);
+            }
+        }
+        self.queue = waiting;
+        due
+    }
+
+    /// Starts the cooldowns of a shoutout that was given.
+    pub fn sent(&mut self, shoutout: &Shoutout, now: Instant) {
+        self.by_channel
+            .insert(shoutout.from.user_id.to_string(), now);
+        self.by_target.insert(shoutout.key(), now);
+    }
+
+    /// Queues a shoutout Twitch turned down for a cooldown the bot didn't know about, such as
+    /// one a moderator gave by hand.
+    pub fn cooling_down(&mut self, shoutout: Shoutout, now: Instant) {
+        self.by_channel
+            .insert(shoutout.from.user_id.to_string(), now);
+        self.queue.push_front(shoutout);
+    }
+
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+}
+
+/// Sends a shoutout with the token of the broadcaster giving it.
+#[tracing::instrument(skip(client, twitch, token))]
+pub async fn send(
+    client: &HelixClient<'static, reqwest::Client>,
+    twitch: &TwitchConfig,
+    token: &UserToken,
+    shoutout: &Shoutout,
+) -> Result<(), ShoutoutError> {
+    let req = SendAShoutoutRequest::new(
+        &shoutout.from.user_id,
+        &shoutout.to.user_id,
+        &token.user_id,
+    );
+    let started = Instant::now();
+    let res = helix::post(client, twitch, req, Default::default(), token)
+        .in_current_span()
+        .await;
+    metrics::observe_helix("send_a_shoutout", started, &res);
+    match res {
+        Ok(_) => Ok(()),
+        Err(ClientRequestError::HelixRequestPostError(
+            HelixRequestPostError::Error { status, .. }
+            | HelixRequestPostError::InvalidResponse { status, .. },
+        )) if status.as_u16() == 429 => Err(ShoutoutError::Cooldown),
+        Err(e) => Err(Report::from(e).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(name: &str) -> Channel {
+        serde_json::from_value(serde_json::json!({ "user_id": name, "name": name })).unwrap()
+    }
+
+    fn names(shoutouts: &[Shoutout]) -> Vec<(String, String)> {
+        shoutouts.iter().map(Shoutout::key).collect()
+    }
+
+    #[test]
+    fn waits_for_cooldowns() {
+        let start = Instant::now();
+        let mut shoutouts = Shoutouts::default();
+        shoutouts.push(Shoutout::new(channel("bob"), channel("alice"), start));
+        shoutouts.push(Shoutout::new(channel("bob"), channel("alice"), start));
+        shoutouts.push(Shoutout::new(channel("bob"), channel("carol"), start));
+        assert_eq!(shoutouts.queued(), 2);
+
+        let due = shoutouts.due(start);
+        assert_eq!(names(&due), [("bob".to_string(), "alice".to_string())]);
+        shoutouts.sent(&due[0], start);
+
+        // Bob has to wait two minutes before the next shoutout.
+        assert!(shoutouts.due(start + Duration::from_secs(60)).is_empty());
+        let later = start + CHANNEL_COOLDOWN;
+        let due = shoutouts.due(later);
+        assert_eq!(names(&due), [("bob".to_string(), "carol".to_string())]);
+        shoutouts.sent(&due[0], later);
+
+        // And an hour before shouting out Alice again.
+        let again = later + CHANNEL_COOLDOWN;
+        shoutouts.push(Shoutout::new(channel("bob"), channel("alice"), again));
+        assert!(shoutouts.due(again).is_empty());
+        assert_eq!(shoutouts.due(start + TARGET_COOLDOWN).len(), 1);
+    }
+
+    #[test]
+    fn requeues_and_expires_turned_down_shoutouts() {
+        let start = Instant::now();
+        let mut shoutouts = Shoutouts::default();
+        shoutouts.cooling_down(Shoutout::new(channel("bob"), channel("alice"), start), start);
+
+        assert!(shoutouts.due(start).is_empty());
+        assert_eq!(shoutouts.due(start + CHANNEL_COOLDOWN).len(), 1);
+
+        shoutouts.push(Shoutout::new(channel("bob"), channel("carol"), start));
+        assert!(shoutouts.due(start + QUEUE_TIMEOUT * 2).is_empty());
+        assert_eq!(shoutouts.queued(), 0);
+    }
+}
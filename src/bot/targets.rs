@@ -0,0 +1,150 @@
+use crate::bot::template;
+use crate::config::TemplatesConfig;
+use crate::routes::webhook::Amount;
+use crate::tiltify::Target;
+
+fn value(amount: &Amount) -> f64 {
+    amount.value.parse().unwrap_or_default()
+}
+
+/// What is left to raise for a target, never below zero.
+pub fn remaining(target: &Target) -> f64 {
+    (value(&target.amount) - value(&target.amount_raised)).max(0.0)
+}
+
+pub fn is_reached(target: &Target) -> bool {
+    value(&target.amount_raised) >= value(&target.amount)
+}
+
+/// Fills `target_reached` when the donation reached the target, else `target_progress`.
+pub fn announcement(
+    templates: &TemplatesConfig,
+    donor: &str,
+    amount: &Amount,
+    target: &Target,
+) -> String {
+    let template = if is_reached(target) {
+        &templates.target_reached
+    } else {
+        &templates.target_progress
+    };
+    template::render(
+        &template::unsplit(template),
+        &[
+            ("name", donor),
+            ("amount", &amount.value),
+            ("currency", &amount.currency),
+            ("target", &target.name),
+            ("raised", &format!("{:.2}", value(&target.amount_raised))),
+            ("goal", &format!("{:.2}", value(&target.amount))),
+            ("remaining", &format!("{:.2}", remaining(target))),
+        ],
+    )
+}
+
+/// The active targets that aren't reached yet as a single chat message, closest first.
+pub fn open_summary(targets: &[Target]) -> String {
+    let mut open: Vec<&Target> = targets
+        .iter()
+        .filter(|t| t.active && !is_reached(t))
+        .collect();
+    if open.is_empty() {
+        return "No targets are open".to_string();
+    }
+    open.sort_by(|a, b| remaining(a).total_cmp(&remaining(b)));
+    let mut summary = "Open targets:".to_string();
+    for (listed, target) in open.iter().enumerate() {
+        let entry = format!(
+            " {} ${:.2} to go of ${:.2};",
+            target.name,
+            remaining(target),
+            value(&target.amount)
+        );
+        let more = format!(" and {} more", open.len() - listed);
+        if summary.chars().count() + entry.chars().count() + more.chars().count()
+            > template::CHAT_LIMIT
+        {
+            summary.pop();
+            summary.push_str(&more);
+            return summary;
+        }
+        summary.push_str(&entry);
+    }
+    summary.pop();
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usd(value: &str) -> Amount {
+        Amount {
+            currency: "USD".to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    fn target(name: &str, active: bool, raised: &str, goal: &str) -> Target {
+        Target {
+            id: name.to_string(),
+            name: name.to_string(),
+            active,
+            amount: usd(goal),
+            amount_raised: usd(raised),
+        }
+    }
+
+    #[test]
+    fn announces_progress_until_a_target_is_reached() {
+        let templates = TemplatesConfig::default();
+        let blindfold = target("Blindfold run", true, "380.00", "500.00");
+
+        assert_eq!(
+            announcement(&templates, "Carol", &usd("30.00"), &blindfold),
+            "Carol put $30.00 toward Blindfold run, $120.00 to go!"
+        );
+        let funded = target("Blindfold run", true, "510.00", "500.00");
+        assert_eq!(remaining(&funded), 0.0);
+        assert_eq!(
+            announcement(&templates, "Dave", &usd("130.00"), &funded),
+            "Blindfold run is funded! Dave took it past $500.00"
+        );
+    }
+
+    #[tokio::test]
+    async fn records_reached_targets_once() {
+        let store = crate::db::Store::open(":memory:").unwrap();
+        let reached = || crate::db::models::NewReachedTarget {
+            target_id: "blindfold",
+            target_name: "Blindfold run",
+            donation_id: None,
+            reached_at: chrono::Utc::now().naive_utc(),
+        };
+
+        assert!(store.reach_target(reached()).await.unwrap());
+        assert!(!store.reach_target(reached()).await.unwrap());
+    }
+
+    #[test]
+    fn lists_open_targets_closest_first() {
+        let targets = [
+            target("Karaoke", true, "0", "300"),
+            target("Blindfold run", true, "380.00", "500.00"),
+            target("Funded", true, "100", "100"),
+            target("Retired", false, "0", "50"),
+        ];
+
+        assert_eq!(
+            open_summary(&targets),
+            "Open targets: Blindfold run $120.00 to go of $500.00; Karaoke $300.00 to go of $300.00"
+        );
+        assert_eq!(open_summary(&targets[2..]), "No targets are open");
+        let many: Vec<Target> = (0..30)
+            .map(|_| target("A target with a long name", true, "0", "100"))
+            .collect();
+        let summary = open_summary(&many);
+        assert!(summary.chars().count() <= template::CHAT_LIMIT);
+        assert!(summary.ends_with(" more"), "{summary}");
+    }
+}
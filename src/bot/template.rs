@@ -0,0 +1,231 @@
+/// Fills `{placeholder}`s in a message template. Unknown placeholders are left as they are.
+/// Values are inserted in a single pass, so placeholders inside them stay as they are too.
+pub fn render(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let filled = rest.find('}').and_then(|end| {
+            let name = &rest[1..end];
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| (*value, end + 1))
+        });
+        match filled {
+            Some((value, length)) => {
+                rendered.push_str(value);
+                rest = &rest[length..];
+            }
+            None => {
+                rendered.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Names of the `{placeholder}`s used in a template.
+pub fn placeholders(template: &str) -> Vec<&str> {
+    let mut found = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rest = &rest[start + 1..];
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+        let name = &rest[..end];
+        if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            found.push(name);
+        }
+        rest = &rest[end + 1..];
+    }
+    found
+}
+
+/// Longest chat message or announcement Twitch accepts, in characters.
+pub const CHAT_LIMIT: usize = 500;
+
+/// Marks where a template may be broken into separate chat messages when it gets too long.
+pub const SPLIT: &str = "{split}";
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+#[error("message is {length} characters long, chat allows {limit}")]
+pub struct TooLong {
+    pub length: usize,
+    pub limit: usize,
+}
+
+/// The parts of a template between its `{split}` markers.
+fn parts(template: &str) -> Vec<&str> {
+    template
+        .split(SPLIT)
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+/// A template with its `{split}` markers removed, for sinks without a length limit.
+pub fn unsplit(template: &str) -> String {
+    parts(template).join(" ")
+}
+
+/// Checks that a finished message fits into a single chat message.
+pub fn check_length(message: &str, limit: usize) -> Result<(), TooLong> {
+    let length = message.chars().count();
+    if length > limit {
+        return Err(TooLong { length, limit });
+    }
+    Ok(())
+}
+
+/// Fills a template into chat messages of at most `limit` characters.
+///
+/// A template that doesn't fit is broken up at its `{split}` markers, if it has any. Parts that
+/// are still too long get the `shrink` placeholder cut short with an ellipsis. Anything that
+/// can't be made to fit is an error, so nothing is sent half-way.
+pub fn render_chat(
+    template: &str,
+    vars: &[(&str, &str)],
+    shrink: &str,
+    limit: usize,
+) -> Result<Vec<String>, TooLong> {
+    let parts = parts(template);
+    let whole = render(&parts.join(" "), vars);
+    if check_length(&whole, limit).is_ok() {
+        return Ok(vec![whole]);
+    }
+    parts
+        .iter()
+        .map(|part| fit(part, vars, shrink, limit))
+        .collect()
+}
+
+/// Renders a template, shortening the `shrink` placeholder until the result fits.
+fn fit(
+    template: &str,
+    vars: &[(&str, &str)],
+    shrink: &str,
+    limit: usize,
+) -> Result<String, TooLong> {
+    let value = vars
+        .iter()
+        .find(|(name, _)| *name == shrink)
+        .map(|(_, value)| *value)
+        .unwrap_or_default();
+    let mut keep = value.chars().count();
+    loop {
+        let shortened = shorten(value, keep);
+        let vars: Vec<(&str, &str)> = vars
+            .iter()
+            .map(|&(name, v)| {
+                if name == shrink {
+                    (name, shortened.as_str())
+                } else {
+                    (name, v)
+                }
+            })
+            .collect();
+        let rendered = render(template, &vars);
+        let length = rendered.chars().count();
+        if length <= limit {
+            return Ok(rendered);
+        }
+        if keep == 0 || !placeholders(template).contains(&shrink) {
+            return Err(TooLong { length, limit });
+        }
+        keep = keep.saturating_sub(length - limit).min(keep - 1);
+    }
+}
+
+/// A duration for chat, like `2h 5m`, `12m` or `40s`.
+pub fn duration(duration: chrono::TimeDelta) -> String {
+    let seconds = duration.num_seconds().max(0);
+    let (hours, minutes) = (seconds / 3600, seconds % 3600 / 60);
+    match (hours, minutes) {
+        (0, 0) => format!("{seconds}s"),
+        (0, minutes) => format!("{minutes}m"),
+        (hours, minutes) => format!("{hours}h {minutes}m"),
+    }
+}
+
+/// The first `keep` characters of `value`, with an ellipsis when anything was cut.
+fn shorten(value: &str, keep: usize) -> String {
+    if value.chars().count() <= keep {
+        return value.to_string();
+    }
+    let mut shortened: String = value.chars().take(keep).collect();
+    shortened.truncate(shortened.trim_end().len());
+    shortened.push('…');
+    shortened
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaves_placeholders_in_values_alone() {
+        let vars = [("name", "{comment}"), ("comment", "a blocked comment")];
+
+        assert_eq!(render("{name} donated! {{name}}", &vars), "{comment} donated! {{comment}}");
+        assert_eq!(render("{unknown} {name", &vars), "{unknown} {name");
+    }
+
+    #[test]
+    fn keeps_short_messages_whole() {
+        let vars = [("name", "Carol"), ("comment", "Go!")];
+        assert_eq!(
+            render_chat("{name}: {split} {comment}", &vars, "comment", 20),
+            Ok(vec!["Carol: Go!".to_string()])
+        );
+    }
+
+    #[test]
+    fn shortens_the_comment_first() {
+        let vars = [
+            ("name", "Carol"),
+            ("comment", "This is a rather long comment"),
+        ];
+        assert_eq!(
+            render_chat("{name} says: {comment}", &vars, "comment", 20),
+            Ok(vec!["Carol says: This is…".to_string()])
+        );
+    }
+
+    #[test]
+    fn splits_at_markers() {
+        let vars = [
+            ("name", "Carol"),
+            ("comment", "This is a rather long comment"),
+        ];
+        assert_eq!(
+            render_chat("Thanks {name}!{split}{comment}", &vars, "comment", 20),
+            Ok(vec![
+                "Thanks Carol!".to_string(),
+                "This is a rather lo…".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(duration(chrono::TimeDelta::seconds(40)), "40s");
+        assert_eq!(duration(chrono::TimeDelta::minutes(12)), "12m");
+        assert_eq!(duration(chrono::TimeDelta::minutes(125)), "2h 5m");
+    }
+
+    #[test]
+    fn fails_when_nothing_can_be_shortened() {
+        let vars = [("name", "Carol Carolson")];
+        assert_eq!(
+            render_chat("Thanks a lot {name}", &vars, "comment", 20),
+            Err(TooLong {
+                length: 27,
+                limit: 20
+            })
+        );
+    }
+}
@@ -1,7 +1,15 @@
+use crate::bot::auth::Channels;
+use crate::bot::helix;
+use crate::config::{SharedConfig, TwitchConfig};
+use crate::health::SharedHealth;
+use crate::{Commands, metrics};
 use eyre::WrapErr;
-use futures::TryStreamExt;
+use std::borrow::Cow;
+use std::collections::HashSet;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Instant;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{Mutex, broadcast};
 use tokio_tungstenite::tungstenite;
 use tracing::Instrument;
 
@@ -11,6 +19,10 @@ use twitch_api::{
         event::websocket::{EventsubWebsocketData, ReconnectPayload, SessionData, WelcomePayload},
         Event,
     },
+    helix::eventsub::{
+        CreateEventSubSubscriptionBody, CreateEventSubSubscriptionRequest,
+        DeleteEventSubSubscriptionRequest, GetEventSubSubscriptionsRequest,
+    },
     types::{self},
     HelixClient,
 };
@@ -25,8 +37,14 @@ pub struct ChatWebsocketClient {
     pub client: HelixClient<'static, reqwest::Client>,
     /// The url to use for websocket
     pub connect_url: url::Url,
-    /// Chats to connect to.
-    pub chats: Vec<twitch_api::types::UserId>,
+    /// Channels whose chats to subscribe to, kept in sync when they change
+    pub channels: Arc<Mutex<Channels>>,
+    /// Commands from the rest of the bot, used to pick up channel changes
+    pub rx: broadcast::Receiver<Commands>,
+    /// Where the connection state is reported for readiness checks
+    pub health: SharedHealth,
+    /// The configuration, read for `twitch.helix_url` on every sync
+    pub config: SharedConfig,
 }
 
 impl ChatWebsocketClient {
@@ -66,7 +84,25 @@ impl ChatWebsocketClient {
             .await
             .context("when establishing connection")?;
         // Loop over the stream, processing messages as they come in.
-        while let Some(msg) = futures::StreamExt::next(&mut s).await {
+        loop {
+            let msg = tokio::select! {
+                msg = futures::StreamExt::next(&mut s) => msg,
+                cmd = self.rx.recv() => {
+                    match cmd {
+                        Ok(Commands::ChannelsChanged) | Err(RecvError::Lagged(_)) => {
+                            if self.session_id.is_some() {
+                                self.sync_subscriptions().await?;
+                            }
+                        }
+                        Ok(_) => {}
+                        Err(RecvError::Closed) => return Ok(()),
+                    }
+                    continue;
+                }
+            };
+            let Some(msg) = msg else {
+                break;
+            };
             let span = tracing::debug_span!("message received", raw_message = ?msg);
             let msg = match msg {
                 Err(tungstenite::Error::Protocol(
@@ -75,6 +111,7 @@ impl ChatWebsocketClient {
                     tracing::warn!(
                         "connection was sent an unexpected frame or was reset, reestablishing it"
                     );
+                    metrics::EVENTSUB_RECONNECTS.inc();
                     s = self
                         .connect()
                         .instrument(span)
@@ -84,19 +121,29 @@ impl ChatWebsocketClient {
                 }
                 _ => msg.context("when getting message")?,
             };
-            self.process_message(msg, &mut event_fn)
-                .instrument(span)
-                .await?
+            let reconnect = self
+                .process_message(msg, &mut event_fn)
+                .instrument(span.clone())
+                .await?;
+            if reconnect {
+                tracing::info!("twitch requested a reconnect to {}", self.connect_url);
+                s = self
+                    .connect()
+                    .instrument(span)
+                    .await
+                    .context("when reconnecting")?;
+            }
         }
         Ok(())
     }
 
-    /// Process a message from the websocket
+    /// Process a message from the websocket, returning `true` when the connection has to be
+    /// reestablished at [`Self::connect_url`].
     async fn process_message<Fut>(
         &mut self,
         msg: tungstenite::Message,
         event_fn: &mut impl FnMut(Event, types::Timestamp) -> Fut,
-    ) -> Result<(), eyre::Report>
+    ) -> Result<bool, eyre::Report>
     where
         Fut: std::future::Future<Output = Result<(), eyre::Report>>,
     {
@@ -108,17 +155,25 @@ impl ChatWebsocketClient {
                     EventsubWebsocketData::Welcome {
                         payload: WelcomePayload { session },
                         ..
+                    } => {
+                        self.process_welcome_message(session).await?;
+                        Ok(false)
                     }
-                    | EventsubWebsocketData::Reconnect {
+                    EventsubWebsocketData::Reconnect {
                         payload: ReconnectPayload { session },
                         ..
                     } => {
-                        self.process_welcome_message(session).await?;
-                        Ok(())
+                        // Subscriptions carry over to the new connection, so we only move over.
+                        metrics::EVENTSUB_RECONNECTS.inc();
+                        let Some(url) = session.reconnect_url else {
+                            eyre::bail!("got reconnect message without a reconnect url")
+                        };
+                        self.connect_url = url.parse()?;
+                        Ok(true)
                     }
                     EventsubWebsocketData::Notification { metadata, payload } => {
                         event_fn(payload, metadata.message_timestamp.into_owned()).await?;
-                        Ok(())
+                        Ok(false)
                     }
                     re @ EventsubWebsocketData::Revocation { .. } => {
                         eyre::bail!("got revocation event: {re:?}")
@@ -126,12 +181,15 @@ impl ChatWebsocketClient {
                     EventsubWebsocketData::Keepalive {
                         metadata: _,
                         payload: _,
-                    } => Ok(()),
-                    _ => Ok(()),
+                    } => Ok(false),
+                    _ => Ok(false),
                 }
             }
-            tungstenite::Message::Close(_) => todo!(),
-            _ => Ok(()),
+            tungstenite::Message::Close(frame) => {
+                self.health.set_eventsub_connected(false);
+                eyre::bail!("connection closed by twitch: {frame:?}")
+            }
+            _ => Ok(false),
         }
     }
 
@@ -141,44 +199,233 @@ impl ChatWebsocketClient {
         if let Some(url) = data.reconnect_url {
             self.connect_url = url.parse()?;
         }
+        self.sync_subscriptions().await?;
+        self.health.set_eventsub_connected(true);
+        Ok(())
+    }
+
+    /// Subscribes to the chats, raids and streams of added channels and unsubscribes from removed ones.
+    ///
+    /// After a reconnect the subscriptions of the session are carried over, so only the
+    /// differences are applied.
+    async fn sync_subscriptions(&self) -> Result<(), eyre::Report> {
+        let Some(session_id) = self.session_id.clone() else {
+            return Ok(());
+        };
+        let wanted: HashSet<String> = self
+            .channels
+            .lock()
+            .await
+            .0
+            .iter()
+            .map(|c| c.user_id.to_string())
+            .collect();
+        let twitch = self.config.read().await.twitch.clone();
         let token = self.token.lock().await;
-        let transport = eventsub::Transport::websocket(data.id.clone());
-        for id in &self.chats {
-            let user_id = token.user_id().unwrap().to_owned();
-            let subs: Vec<_> = self
-                .client
-                .get_eventsub_subscriptions(Some(eventsub::Status::Enabled), None, None, &*token)
-                .map_ok(|r| {
-                    futures::stream::iter(
-                        r.subscriptions
-                            .into_iter()
-                            .filter(|s| {
-                                s.transport
-                                    .as_websocket()
-                                    .is_some_and(|t| t.session_id == data.id)
-                            })
-                            .map(Ok::<_, eyre::Report>),
-                    )
-                })
-                .try_flatten()
-                .try_collect()
-                .await?;
-            if !subs.is_empty() {
+        let mut subs = Vec::new();
+        let mut req = GetEventSubSubscriptionsRequest::status(eventsub::Status::Enabled);
+        loop {
+            let started = Instant::now();
+            let res = helix::get(&self.client, &twitch, req, &*token).await;
+            metrics::observe_helix("get_eventsub_subscriptions", started, &res);
+            let res = res?;
+            subs.extend(res.data.subscriptions.into_iter().filter(|s| {
+                s.transport
+                    .as_websocket()
+                    .is_some_and(|t| t.session_id == session_id)
+            }));
+            let (Some(cursor), Some(next)) = (res.pagination, res.request) else {
+                break;
+            };
+            req = next;
+            req.after = Some(Cow::Owned(cursor));
+        }
+
+        let mut subscribed = HashSet::new();
+        for sub in subs {
+            let Some(broadcaster) = subscription_broadcaster(&sub) else {
+                continue;
+            };
+            if wanted.contains(&broadcaster) {
+                subscribed.insert((sub.type_.to_str(), broadcaster));
                 continue;
             }
-            let message =
-                eventsub::channel::chat::ChannelChatMessageV1::new(id.clone(), user_id.clone());
-            self.client
-                .create_eventsub_subscription(message, transport.clone(), &*token)
-                .await?;
-            self.client
-                .create_eventsub_subscription(
-                    eventsub::channel::chat::ChannelChatNotificationV1::new(id.clone(), user_id),
-                    transport.clone(),
-                    &*token,
-                )
-                .await?;
+            tracing::info!("unsubscribing {:?} of removed channel {broadcaster}", sub.type_);
+            let req = DeleteEventSubSubscriptionRequest::id(sub.id.clone());
+            let started = Instant::now();
+            let res = helix::delete(&self.client, &twitch, req, &*token).await;
+            metrics::observe_helix("delete_eventsub_subscription", started, &res);
+            if let Err(e) = res {
+                tracing::error!("couldn't unsubscribe {:?} of {broadcaster}: {e:?}", sub.type_);
+            }
+        }
+
+        let transport = eventsub::Transport::websocket(session_id.clone());
+        let user_id = token.user_id().unwrap().to_owned();
+        for id in &wanted {
+            let missing = |type_: eventsub::EventType| !subscribed.contains(&(type_.to_str(), id.clone()));
+            let broadcaster = types::UserId::new(id.clone());
+            if missing(eventsub::EventType::ChannelChatMessage) {
+                let message = eventsub::channel::chat::ChannelChatMessageV1::new(
+                    broadcaster.clone(),
+                    user_id.clone(),
+                );
+                self.subscribe(&twitch, &token, &transport, message, id).await;
+            }
+            if missing(eventsub::EventType::ChannelChatNotification) {
+                let notification = eventsub::channel::chat::ChannelChatNotificationV1::new(
+                    broadcaster.clone(),
+                    user_id.clone(),
+                );
+                self.subscribe(&twitch, &token, &transport, notification, id).await;
+            }
+            // Raids leaving a participant, the bot checks whether they went to another one.
+            if missing(eventsub::EventType::ChannelRaid) {
+                let raid = eventsub::channel::ChannelRaidV1::from_broadcaster_user_id(broadcaster.clone());
+                self.subscribe(&twitch, &token, &transport, raid, id).await;
+            }
+            if missing(eventsub::EventType::StreamOnline) {
+                let online = eventsub::stream::StreamOnlineV1::broadcaster_user_id(broadcaster.clone());
+                self.subscribe(&twitch, &token, &transport, online, id).await;
+            }
+            if missing(eventsub::EventType::StreamOffline) {
+                let offline = eventsub::stream::StreamOfflineV1::broadcaster_user_id(broadcaster);
+                self.subscribe(&twitch, &token, &transport, offline, id).await;
+            }
         }
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Creates a subscription, logging failures so the other subscriptions are still made.
+    async fn subscribe<E: eventsub::EventSubscription + Send>(
+        &self,
+        twitch: &TwitchConfig,
+        token: &UserToken,
+        transport: &eventsub::Transport,
+        subscription: E,
+        channel: &str,
+    ) {
+        let body = CreateEventSubSubscriptionBody::new(subscription, transport.clone());
+        let req = CreateEventSubSubscriptionRequest::new();
+        let started = Instant::now();
+        let res = helix::post(&self.client, twitch, req, body, token).await;
+        metrics::observe_helix("create_eventsub_subscription", started, &res);
+        if let Err(e) = res {
+            tracing::error!("couldn't subscribe to {} of {channel}: {e:?}", E::EVENT_TYPE);
+        }
+    }
+}
+
+/// The channel a subscription is about, taken from its condition.
+fn subscription_broadcaster(sub: &eventsub::EventSubSubscription) -> Option<String> {
+    ["broadcaster_user_id", "from_broadcaster_user_id", "to_broadcaster_user_id"]
+        .iter()
+        .find_map(|key| sub.condition.get(key)?.as_str().filter(|id| !id.is_empty()))
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::notify::stand_in::StandIn;
+    use axum::http::{Method, StatusCode};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::RwLock;
+
+    fn subscription(id: &str, type_: &str, broadcaster: &str) -> serde_json::Value {
+        json!({
+            "id": id,
+            "status": "enabled",
+            "type": type_,
+            "version": "1",
+            "condition": { "broadcaster_user_id": broadcaster, "user_id": "99" },
+            "created_at": "2026-10-18T12:00:00Z",
+            "transport": {
+                "method": "websocket",
+                "session_id": "session",
+                "connected_at": "2026-10-18T12:00:00Z",
+            },
+            "cost": 0,
+        })
+    }
+
+    #[tokio::test]
+    async fn syncs_subscriptions_through_the_configured_helix_url() {
+        let pages = Arc::new(AtomicUsize::new(0));
+        let stand_in = StandIn::start(move |request| {
+            if request.method != Method::GET {
+                return (StatusCode::ACCEPTED, json!({ "data": [] }).to_string());
+            }
+            let (data, pagination) = match pages.fetch_add(1, Ordering::Relaxed) {
+                0 => (
+                    subscription("bob-chat", "channel.chat.message", "2"),
+                    json!({ "cursor": "next" }),
+                ),
+                _ => (
+                    subscription("alice-chat", "channel.chat.message", "1"),
+                    json!({}),
+                ),
+            };
+            let page = json!({
+                "data": [data],
+                "total": 2,
+                "total_cost": 0,
+                "max_total_cost": 10000,
+                "pagination": pagination,
+            });
+            (StatusCode::OK, page.to_string())
+        })
+        .await;
+        let mut config = Config::default();
+        config.twitch.helix_url = stand_in.url("/helix/");
+        let token = UserToken::from_existing_unchecked(
+            "access",
+            None,
+            "client",
+            None,
+            "warbot".into(),
+            "99".into(),
+            None,
+            None,
+        );
+        let channels: Channels =
+            serde_json::from_value(json!([{ "user_id": "1", "name": "alice" }])).unwrap();
+        let websocket = ChatWebsocketClient {
+            session_id: Some("session".to_string()),
+            token: Arc::new(Mutex::new(token)),
+            client: HelixClient::default(),
+            connect_url: twitch_api::TWITCH_EVENTSUB_WEBSOCKET_URL.clone(),
+            channels: Arc::new(Mutex::new(channels)),
+            rx: broadcast::channel(1).1,
+            health: SharedHealth::default(),
+            config: Arc::new(RwLock::new(config)),
+        };
+
+        websocket.sync_subscriptions().await.unwrap();
+
+        let requests = stand_in.requests().await;
+        assert!(
+            requests
+                .iter()
+                .all(|r| r.path == "/helix/eventsub/subscriptions")
+        );
+        let count = |method: Method| requests.iter().filter(|r| r.method == method).count();
+        assert_eq!((count(Method::GET), count(Method::DELETE)), (2, 1));
+        let created: Vec<_> = requests
+            .iter()
+            .filter(|r| r.method == Method::POST)
+            .map(|r| r.json()["type"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            created,
+            [
+                "channel.chat.notification",
+                "channel.raid",
+                "stream.online",
+                "stream.offline"
+            ]
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use crate::bot::auth::{Channel, Channels, Streamers, User};
+use crate::config::{Config, ConfigArgs, ConfigError};
+use crate::db::Store;
+use clap::{Parser, Subcommand};
+use eyre::{Report, WrapErr, bail};
+use serde_json::json;
+use std::net::Ipv4Addr;
+use twitch_api::HelixClient;
+use twitch_oauth2::{TwitchToken, UserToken};
+
+/// Announces Tiltify donations in the chats of participating Twitch channels.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(flatten)]
+    pub config: ConfigArgs,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the webhook server and the bot (default)
+    Run,
+    /// Manage the bot's Twitch login
+    #[command(subcommand)]
+    Auth(AuthCommand),
+    /// Manage participating channels
+    #[command(subcommand)]
+    Channels(ChannelsCommand),
+    /// Send synthetic events to a running bot
+    #[command(subcommand)]
+    Simulate(SimulateCommand),
+    /// Inspect the configuration
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Manage the database
+    #[command(subcommand)]
+    Db(DbCommand),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AuthCommand {
+    /// Log the bot in through the device flow
+    Login,
+    /// Show who the bot is logged in as and when its token expires
+    Status,
+    /// Log a streamer in so the bot can check AutoMod in their channel
+    Streamer,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ChannelsCommand {
+    /// List participating channels
+    List,
+    /// Add a channel by login name
+    Add { name: String },
+    /// Remove a channel by login name or user id
+    Remove { name: String },
+    /// Update login names of channels that were renamed
+    RefreshNames,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SimulateCommand {
+    /// Inject a donation into a running bot through the admin API
+    Donation {
+        #[arg(long)]
+        amount: String,
+        #[arg(long, default_value = "USD")]
+        currency: String,
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long)]
+        comment: Option<String>,
+        /// Base url of the running bot, defaults to the configured server address
+        #[arg(long)]
+        url: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Validate the configuration and print the result
+    Check,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DbCommand {
+    /// Apply pending database migrations
+    Migrate,
+}
+
+impl Cli {
+    /// Loads the config, requiring Twitch credentials only when the command talks to Twitch.
+    pub fn load_config(&self) -> Result<Config, ConfigError> {
+        if self.command.as_ref().is_none_or(Command::needs_twitch) {
+            Config::load_layered(&self.config)
+        } else {
+            Config::load_layered_without_twitch(&self.config)
+        }
+    }
+}
+
+impl Command {
+    /// Whether the command calls the Twitch API or runs the bot.
+    fn needs_twitch(&self) -> bool {
+        match self {
+            Command::Run | Command::Auth(_) => true,
+            Command::Channels(command) => matches!(
+                command,
+                ChannelsCommand::Add { .. } | ChannelsCommand::RefreshNames
+            ),
+            Command::Simulate(_) | Command::Db(_) => false,
+            // Checking the config reports everything the bot would need to run.
+            Command::Config(_) => true,
+        }
+    }
+}
+
+pub async fn execute(cli: Cli) -> Result<(), Report> {
+    if let Some(Command::Config(ConfigCommand::Check)) = cli.command {
+        return check_config(&cli.config);
+    }
+    let config = cli.load_config()?;
+    match cli.command.unwrap_or(Command::Run) {
+        Command::Run => crate::run(config, cli.config).await,
+        Command::Auth(AuthCommand::Login) => {
+            let client = HelixClient::default();
+            let user = User::login(&client, &config.twitch).await?;
+            user.check_account(&config.twitch)?;
+            user.save(&config.storage.bot)?;
+            println!("Logged in as {} ({})", user.twitch_name, user.user_id);
+        }
+        Command::Auth(AuthCommand::Status) => {
+            let client = HelixClient::default();
+            let token = bot_token(&config, &client).await?;
+            println!("Logged in as {} ({})", token.login, token.user_id);
+            println!("Token expires in {}s", token.expires_in().as_secs());
+            let scopes: Vec<String> = token.scopes().iter().map(|s| s.to_string()).collect();
+            println!("Scopes: {}", scopes.join(" "));
+        }
+        Command::Auth(AuthCommand::Streamer) => {
+            let client = HelixClient::default();
+            let streamer = User::login_streamer(&client, &config.twitch).await?;
+            println!("Logged in as {} ({})", streamer.twitch_name, streamer.user_id);
+            let mut streamers = Streamers::load(&config.storage.streamers).unwrap_or_default();
+            streamers.upsert(streamer);
+            streamers.save(&config.storage.streamers)?;
+        }
+        Command::Channels(command) => channels(&config, command).await?,
+        Command::Simulate(SimulateCommand::Donation {
+            amount,
+            currency,
+            name,
+            comment,
+            url,
+        }) => {
+            let api_key = config
+                .admin
+                .api_key
+                .clone()
+                .ok_or_else(|| eyre::eyre!("admin.api_key has to be set to simulate events"))?;
+            let url = url.unwrap_or_else(|| {
+                let mut addr = config.server.to_socket_addrs();
+                if addr.ip().is_unspecified() {
+                    addr.set_ip(Ipv4Addr::LOCALHOST.into());
+                }
+                format!("http://{addr}")
+            });
+            let res = reqwest::Client::new()
+                .post(format!("{}/admin/simulate/donation", url.trim_end_matches('/')))
+                .bearer_auth(api_key)
+                .json(&json!({
+                    "amount": amount,
+                    "currency": currency,
+                    "name": name,
+                    "comment": comment,
+                }))
+                .send()
+                .await
+                .wrap_err_with(|| format!("couldn't reach the bot at {url}"))?;
+            if !res.status().is_success() {
+                bail!("bot responded with {}: {}", res.status(), res.text().await?);
+            }
+            println!("Donation of {amount} {currency} injected");
+        }
+        Command::Config(ConfigCommand::Check) => unreachable!("handled before loading the config"),
+        Command::Db(DbCommand::Migrate) => {
+            Store::open(&config.storage.database)?;
+            println!("Database {} is up to date", config.storage.database);
+        }
+    }
+    Ok(())
+}
+
+fn check_config(args: &ConfigArgs) -> Result<(), Report> {
+    match Config::load_layered(args) {
+        Ok(_) => {
+            println!("Configuration is valid");
+            Ok(())
+        }
+        Err(e @ ConfigError::Invalid(_)) => bail!("{e}"),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn bot_token(
+    config: &Config,
+    client: &HelixClient<'static, reqwest::Client>,
+) -> Result<UserToken, Report> {
+    let mut user = User::load(&config.storage.bot)
+        .wrap_err("the bot isn't logged in, run `auth login` first")?;
+    user.ensure_token(client, &config.twitch).await?;
+    user.user_token
+        .ok_or_else(|| eyre::eyre!("the bot has no usable token, run `auth login` again"))
+}
+
+async fn channels(config: &Config, command: ChannelsCommand) -> Result<(), Report> {
+    let path = &config.storage.channels;
+    let mut channels = Channels::load(path).unwrap_or_default();
+    match command {
+        ChannelsCommand::List => {
+            for channel in &channels.0 {
+                println!("{}\t{}", channel.user_id, channel.name);
+            }
+            println!("{} channels", channels.0.len());
+            return Ok(());
+        }
+        ChannelsCommand::Add { name } => {
+            let client = HelixClient::default();
+            let token = bot_token(config, &client).await?;
+            let channel = Channel::resolve(&client, &config.twitch, &token, &name)
+                .await?
+                .ok_or_else(|| eyre::eyre!("Twitch user {name} not found"))?;
+            if !channels.add(channel.clone()) {
+                bail!("channel {} is already participating", channel.name);
+            }
+            println!("Added {} ({})", channel.name, channel.user_id);
+        }
+        ChannelsCommand::Remove { name } => {
+            let channel = channels
+                .remove(&name)
+                .ok_or_else(|| eyre::eyre!("channel {name} not found"))?;
+            println!("Removed {} ({})", channel.name, channel.user_id);
+        }
+        ChannelsCommand::RefreshNames => {
+            let client = HelixClient::default();
+            let token = bot_token(config, &client).await?;
+            let renamed = channels.refresh_names(&client, &config.twitch, &token).await?;
+            for (old, new) in &renamed {
+                println!("{old} -> {new}");
+            }
+            println!("{} channels renamed", renamed.len());
+        }
+    }
+    channels.save(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requires_twitch_credentials_only_for_commands_using_twitch() {
+        let needs_twitch = |args: &[&str]| {
+            let cli = Cli::try_parse_from([&["warbot"], args].concat()).unwrap();
+            cli.command.as_ref().is_none_or(Command::needs_twitch)
+        };
+
+        assert!(needs_twitch(&[]));
+        assert!(needs_twitch(&["auth", "status"]));
+        assert!(needs_twitch(&["channels", "add", "alice"]));
+        assert!(needs_twitch(&["config", "check"]));
+        assert!(!needs_twitch(&["channels", "list"]));
+        assert!(!needs_twitch(&["db", "migrate"]));
+        assert!(!needs_twitch(&["simulate", "donation", "--amount", "5"]));
+    }
+}
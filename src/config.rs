@@ -1,37 +1,1338 @@
-use eyre::{Context, Report};
+use crate::bot::auth::Channel;
+use crate::bot::template;
+use crate::db::CampaignScope;
+use clap::Args;
 use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::net::{Ipv4Addr, SocketAddr};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::RwLock;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// The live config, swapped out as a whole when `config.toml` is reloaded.
+pub type SharedConfig = Arc<RwLock<Config>>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
 pub struct Config {
     pub server: ServerConfig,
     pub storage: StorageConfig,
+    pub admin: AdminConfig,
+    pub twitch: TwitchConfig,
+    pub tiltify: TiltifyConfig,
+    pub templates: TemplatesConfig,
+    /// Template overrides for channels with a `language` setting, keyed by language.
+    pub translations: BTreeMap<String, TemplateOverrides>,
+    /// The campaign for donations and channels that belong to none of `campaigns`.
+    pub campaign: CampaignConfig,
+    /// Further campaigns, keyed by their Tiltify campaign or team event id.
+    pub campaigns: BTreeMap<String, CampaignConfig>,
+    pub team: TeamConfig,
+    pub donors: DonorsConfig,
+    pub moderation: ModerationConfig,
+    pub holds: HoldsConfig,
+    pub raids: RaidsConfig,
+    pub streams: StreamsConfig,
+    pub notifications: NotificationsConfig,
+    pub webhooks: Vec<WebhookConfig>,
+    pub sentry: SentryConfig,
+    pub otlp: OtlpConfig,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct StorageConfig {
     pub tokens: String,
+    pub streamers: String,
     pub bot: String,
     pub channels: String,
+    pub database: String,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            tokens: "./tokens/".to_string(),
+            streamers: "./streamers.json".to_string(),
+            bot: "./bot.json".to_string(),
+            channels: "./channels.json".to_string(),
+            database: "./warbot.sqlite".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct AdminConfig {
+    /// Bearer token required by the `/admin` API. The API is disabled when unset.
+    pub api_key: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct ServerConfig {
     pub host: Ipv4Addr,
     pub port: u16,
 }
 
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: Ipv4Addr::UNSPECIFIED,
+            port: 28257,
+        }
+    }
+}
+
 impl ServerConfig {
     pub fn to_socket_addrs(&self) -> SocketAddr {
         SocketAddr::new(self.host.into(), self.port)
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TwitchConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    /// When set, the account the bot is logged in as has to match.
+    pub bot_user_id: Option<String>,
+    pub bot_user_name: Option<String>,
+    /// Where Helix requests go, for pointing the bot at a mock API.
+    pub helix_url: String,
+}
+
+impl Default for TwitchConfig {
+    fn default() -> Self {
+        Self {
+            client_id: String::new(),
+            client_secret: String::new(),
+            bot_user_id: None,
+            bot_user_name: None,
+            helix_url: "https://api.twitch.tv/helix/".to_string(),
+        }
+    }
+}
+
+/// Access to Tiltify's public API, for reward names, polls and targets.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TiltifyConfig {
+    pub api_url: String,
+    /// Credentials of a Tiltify application. Nothing is looked up without them.
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+impl Default for TiltifyConfig {
+    fn default() -> Self {
+        Self {
+            api_url: "https://v5api.tiltify.com".to_string(),
+            client_id: None,
+            client_secret: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TemplatesConfig {
+    pub donation_message: String,
+    pub donation_announcement: String,
+    pub milestone_announcement: String,
+    pub stream_started_announcement: String,
+    pub stream_ended_announcement: String,
+    pub raid_announcement: String,
+    /// Posted after a donation announcement when the donor left a comment that passed moderation.
+    pub donation_comment: String,
+    /// Announced in a participating channel when another participant raids it.
+    pub raid_welcome: String,
+    /// Posted in a raided channel along with the shoutout for the participant who raided it.
+    pub raid_shoutout: String,
+    /// Posted in a participating channel when it goes live.
+    pub stream_welcome: String,
+    /// Posted in a participating channel and the ops channel when it goes offline.
+    pub stream_summary: String,
+    /// Announced for every reward a donor claims, in the channel the reward belongs to.
+    pub reward_claim: String,
+    /// Announced in every channel of a campaign when a donation votes in one of its polls.
+    pub poll_vote: String,
+    /// Announced when a donation counts toward a target, in the channel it belongs to.
+    pub target_progress: String,
+    /// Announced instead of `target_progress`, once, by the donation that reaches a target.
+    pub target_reached: String,
+}
+
+impl Default for TemplatesConfig {
+    fn default() -> Self {
+        Self {
+            donation_message: "!donation_received {amount}".to_string(),
+            donation_announcement: "A donation of ${amount} has been made by {name}!".to_string(),
+            milestone_announcement: "We just passed ${milestone} raised, ${total} so far!"
+                .to_string(),
+            stream_started_announcement: "{channel} just went live for the campaign!".to_string(),
+            stream_ended_announcement: "{channel} has ended their stream, thanks for watching!"
+                .to_string(),
+            raid_announcement: "{from} is raiding {to} with {viewers} viewers!".to_string(),
+            donation_comment: "{name} says:{split}{comment}".to_string(),
+            raid_welcome: "Welcome, raiders from {from}! Together we have raised ${total} for {campaign} so far."
+                .to_string(),
+            raid_shoutout: "Go check out {from}, who is raising money for {campaign} too!"
+                .to_string(),
+            stream_welcome: "Welcome! {channel} is live for {campaign}, ${total} raised so far. Donate at {url}"
+                .to_string(),
+            stream_summary: "{channel} was live for {duration} and raised ${raised} from {donations} donations. Thank you!"
+                .to_string(),
+            reward_claim: "{name} claimed {reward}! It is reward #{id} in the queue".to_string(),
+            poll_vote: "{name} voted for {option} with ${amount}! {poll}: {standings}".to_string(),
+            target_progress: "{name} put ${amount} toward {target}, ${remaining} to go!".to_string(),
+            target_reached: "{target} is funded! {name} took it past ${goal}".to_string(),
+        }
+    }
+}
+
+impl TemplatesConfig {
+    pub const DONATION_PLACEHOLDERS: &'static [&'static str] = &["amount", "currency", "name"];
+    pub const MILESTONE_PLACEHOLDERS: &'static [&'static str] = &["milestone", "total", "currency"];
+    pub const STREAM_PLACEHOLDERS: &'static [&'static str] = &["channel"];
+    pub const RAID_PLACEHOLDERS: &'static [&'static str] = &["from", "to", "viewers"];
+    pub const COMMENT_PLACEHOLDERS: &'static [&'static str] =
+        &["amount", "currency", "name", "comment"];
+    pub const RAID_WELCOME_PLACEHOLDERS: &'static [&'static str] =
+        &["from", "to", "viewers", "total", "campaign"];
+    pub const SHOUTOUT_PLACEHOLDERS: &'static [&'static str] = &["from", "to", "campaign"];
+    pub const STREAM_WELCOME_PLACEHOLDERS: &'static [&'static str] =
+        &["channel", "campaign", "total", "url"];
+    pub const STREAM_SUMMARY_PLACEHOLDERS: &'static [&'static str] =
+        &["channel", "duration", "donations", "raised"];
+    pub const REWARD_CLAIM_PLACEHOLDERS: &'static [&'static str] =
+        &["id", "name", "reward", "quantity", "amount", "currency"];
+    pub const POLL_VOTE_PLACEHOLDERS: &'static [&'static str] =
+        &["name", "amount", "currency", "poll", "option", "standings"];
+    pub const TARGET_PLACEHOLDERS: &'static [&'static str] =
+        &["name", "amount", "currency", "target", "raised", "goal", "remaining"];
+
+    /// Every template with its name and the placeholders it may use.
+    fn all(&self) -> [(&'static str, &str, &'static [&'static str]); 15] {
+        [
+            ("donation_message", &self.donation_message, Self::DONATION_PLACEHOLDERS),
+            ("donation_announcement", &self.donation_announcement, Self::DONATION_PLACEHOLDERS),
+            ("milestone_announcement", &self.milestone_announcement, Self::MILESTONE_PLACEHOLDERS),
+            ("stream_started_announcement", &self.stream_started_announcement, Self::STREAM_PLACEHOLDERS),
+            ("stream_ended_announcement", &self.stream_ended_announcement, Self::STREAM_PLACEHOLDERS),
+            ("raid_announcement", &self.raid_announcement, Self::RAID_PLACEHOLDERS),
+            ("donation_comment", &self.donation_comment, Self::COMMENT_PLACEHOLDERS),
+            ("raid_welcome", &self.raid_welcome, Self::RAID_WELCOME_PLACEHOLDERS),
+            ("raid_shoutout", &self.raid_shoutout, Self::SHOUTOUT_PLACEHOLDERS),
+            ("stream_welcome", &self.stream_welcome, Self::STREAM_WELCOME_PLACEHOLDERS),
+            ("stream_summary", &self.stream_summary, Self::STREAM_SUMMARY_PLACEHOLDERS),
+            ("reward_claim", &self.reward_claim, Self::REWARD_CLAIM_PLACEHOLDERS),
+            ("poll_vote", &self.poll_vote, Self::POLL_VOTE_PLACEHOLDERS),
+            ("target_progress", &self.target_progress, Self::TARGET_PLACEHOLDERS),
+            ("target_reached", &self.target_reached, Self::TARGET_PLACEHOLDERS),
+        ]
+    }
+
+    /// These templates with `overrides` applied on top.
+    pub fn overridden(&self, overrides: &TemplateOverrides) -> Self {
+        let pick = |overridden: &Option<String>, template: &String| {
+            overridden.clone().unwrap_or_else(|| template.clone())
+        };
+        Self {
+            donation_message: pick(&overrides.donation_message, &self.donation_message),
+            donation_announcement: pick(&overrides.donation_announcement, &self.donation_announcement),
+            milestone_announcement: pick(&overrides.milestone_announcement, &self.milestone_announcement),
+            stream_started_announcement: pick(&overrides.stream_started_announcement, &self.stream_started_announcement),
+            stream_ended_announcement: pick(&overrides.stream_ended_announcement, &self.stream_ended_announcement),
+            raid_announcement: pick(&overrides.raid_announcement, &self.raid_announcement),
+            donation_comment: pick(&overrides.donation_comment, &self.donation_comment),
+            raid_welcome: pick(&overrides.raid_welcome, &self.raid_welcome),
+            raid_shoutout: pick(&overrides.raid_shoutout, &self.raid_shoutout),
+            stream_welcome: pick(&overrides.stream_welcome, &self.stream_welcome),
+            stream_summary: pick(&overrides.stream_summary, &self.stream_summary),
+            reward_claim: pick(&overrides.reward_claim, &self.reward_claim),
+            poll_vote: pick(&overrides.poll_vote, &self.poll_vote),
+            target_progress: pick(&overrides.target_progress, &self.target_progress),
+            target_reached: pick(&overrides.target_reached, &self.target_reached),
+        }
+    }
+}
+
+/// Templates replacing some of `[templates]`, for a language or a single channel.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct TemplateOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub donation_message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub donation_announcement: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub milestone_announcement: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_started_announcement: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_ended_announcement: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raid_announcement: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub donation_comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raid_welcome: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raid_shoutout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_welcome: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_summary: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reward_claim: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poll_vote: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_progress: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_reached: Option<String>,
+}
+
+impl TemplateOverrides {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Problems with the templates that are overridden, reported under `prefix`.
+    pub fn problems(&self, prefix: &str) -> Vec<String> {
+        type T = TemplatesConfig;
+        let overridden = [
+            ("donation_message", &self.donation_message, T::DONATION_PLACEHOLDERS),
+            ("donation_announcement", &self.donation_announcement, T::DONATION_PLACEHOLDERS),
+            ("milestone_announcement", &self.milestone_announcement, T::MILESTONE_PLACEHOLDERS),
+            ("stream_started_announcement", &self.stream_started_announcement, T::STREAM_PLACEHOLDERS),
+            ("stream_ended_announcement", &self.stream_ended_announcement, T::STREAM_PLACEHOLDERS),
+            ("raid_announcement", &self.raid_announcement, T::RAID_PLACEHOLDERS),
+            ("donation_comment", &self.donation_comment, T::COMMENT_PLACEHOLDERS),
+            ("raid_welcome", &self.raid_welcome, T::RAID_WELCOME_PLACEHOLDERS),
+            ("raid_shoutout", &self.raid_shoutout, T::SHOUTOUT_PLACEHOLDERS),
+            ("stream_welcome", &self.stream_welcome, T::STREAM_WELCOME_PLACEHOLDERS),
+            ("stream_summary", &self.stream_summary, T::STREAM_SUMMARY_PLACEHOLDERS),
+            ("reward_claim", &self.reward_claim, T::REWARD_CLAIM_PLACEHOLDERS),
+            ("poll_vote", &self.poll_vote, T::POLL_VOTE_PLACEHOLDERS),
+            ("target_progress", &self.target_progress, T::TARGET_PLACEHOLDERS),
+            ("target_reached", &self.target_reached, T::TARGET_PLACEHOLDERS),
+        ];
+        template_problems(
+            prefix,
+            overridden
+                .into_iter()
+                .filter_map(|(name, value, known)| Some((name, value.as_deref()?, known))),
+        )
+    }
+}
+
+/// Checks templates for emptiness and unknown placeholders, reporting them under `prefix`.
+fn template_problems<'a>(
+    prefix: &str,
+    templates: impl IntoIterator<Item = (&'static str, &'a str, &'static [&'static str])>,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (name, value, known) in templates {
+        if value.trim().is_empty() {
+            problems.push(format!("{prefix}.{name} must not be empty"));
+        }
+        for placeholder in template::placeholders(value) {
+            // `{split}` only marks where chat messages may be broken up.
+            if placeholder != "split" && !known.contains(&placeholder) {
+                problems.push(format!("{prefix}.{name} uses unknown placeholder {{{placeholder}}}"));
+            }
+        }
+    }
+    problems
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CampaignConfig {
+    /// How the campaign is called in chat.
+    pub name: String,
+    /// The Tiltify campaign or team campaign, for its polls and targets. Campaigns in `campaigns`
+    /// default to their key.
+    pub id: Option<String>,
+    /// Where viewers can donate. Streams are only welcomed when it is set.
+    pub url: Option<String>,
+    /// The campaign's goal, announced like a milestone once it is reached.
+    pub goal: Option<f64>,
+    /// Campaign totals that trigger a milestone notification once they are passed.
+    pub milestones: Vec<f64>,
+    /// Secret Tiltify signs the campaign's webhooks with. Unsigned webhooks are accepted when unset.
+    pub signing_secret: Option<String>,
+    /// Participating channels, by name or id, that post for this campaign. Empty means all.
+    pub channels: Vec<String>,
+    /// Templates for this campaign, on top of `[templates]`.
+    pub templates: TemplateOverrides,
+}
+
+impl Default for CampaignConfig {
+    fn default() -> Self {
+        Self {
+            name: "the campaign".to_string(),
+            id: None,
+            url: None,
+            goal: None,
+            milestones: Vec::new(),
+            signing_secret: None,
+            channels: Vec::new(),
+            templates: TemplateOverrides::default(),
+        }
+    }
+}
+
+impl CampaignConfig {
+    /// Whether a participating channel posts for this campaign.
+    pub fn includes(&self, channel: &Channel) -> bool {
+        self.channels.is_empty() || self.channels.iter().any(|c| channel.matches(c))
+    }
+
+    /// The milestones, with the goal as the last one.
+    pub fn milestones(&self) -> Vec<f64> {
+        let mut milestones = self.milestones.clone();
+        if let Some(goal) = self.goal
+            && !milestones.contains(&goal)
+        {
+            milestones.push(goal);
+        }
+        milestones
+    }
+
+    fn problems(&self, prefix: &str) -> Vec<String> {
+        let mut problems = self.templates.problems(&format!("{prefix}.templates"));
+        if self.milestones.iter().any(|m| !m.is_finite() || *m <= 0.0) {
+            problems.push(format!("{prefix}.milestones must all be positive amounts"));
+        }
+        if self.goal.is_some_and(|goal| !goal.is_finite() || goal <= 0.0) {
+            problems.push(format!("{prefix}.goal must be a positive amount"));
+        }
+        if self.signing_secret.as_ref().is_some_and(|s| s.trim().is_empty()) {
+            problems.push(format!("{prefix}.signing_secret must not be empty when set"));
+        }
+        problems
+    }
+}
+
+/// How donor names are shown.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DonorsConfig {
+    /// Shown for anonymous donors and names that are empty once sanitised.
+    pub anonymous_name: String,
+    /// Longest display name in characters, longer names are cut.
+    pub max_name_length: usize,
+    /// Display names chosen by known donors, keyed by their Tiltify name ignoring case.
+    pub aliases: BTreeMap<String, String>,
+    /// Donations below this amount are shown as anonymous.
+    pub anonymous_below: Option<f64>,
+    /// Donations above this amount are shown as anonymous.
+    pub anonymous_above: Option<f64>,
+}
+
+impl Default for DonorsConfig {
+    fn default() -> Self {
+        Self {
+            anonymous_name: "an anonymous user".to_string(),
+            max_name_length: 25,
+            aliases: BTreeMap::new(),
+            anonymous_below: None,
+            anonymous_above: None,
+        }
+    }
+}
+
+/// Review of donor comments before they are posted to chat.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ModerationConfig {
+    /// Comments longer than this many characters are rejected.
+    pub max_comment_length: usize,
+    /// Terms that reject a comment when it contains them, ignoring case.
+    pub blocklist: Vec<String>,
+    /// Whether comments are also checked against each channel's AutoMod settings. Needs the
+    /// streamer to be logged in with `auth streamer`, channels whose streamer isn't are not
+    /// checked.
+    pub automod: bool,
+    /// What happens to comments rejected by `max_comment_length` or `blocklist`. AutoMod judges
+    /// each channel on its own, comments it rejects are always dropped in that channel.
+    pub rejected_comments: RejectedComments,
+}
+
+impl Default for ModerationConfig {
+    fn default() -> Self {
+        Self {
+            max_comment_length: 200,
+            blocklist: Vec::new(),
+            automod: false,
+            rejected_comments: RejectedComments::Drop,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectedComments {
+    /// The comment is never shown.
+    Drop,
+    /// The donation is announced without it, and the comment waits in the `[holds]` queue for a
+    /// moderator.
+    Hold,
+}
+
+impl RejectedComments {
+    /// The comment status recorded for a rejected comment.
+    pub fn status(&self) -> &'static str {
+        match self {
+            RejectedComments::Drop => "dropped",
+            RejectedComments::Hold => "held",
+        }
+    }
+}
+
+/// A Tiltify team event, where each participant raises through a supporting campaign.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TeamConfig {
+    /// The team event. When set, donations from other events aren't matched to a channel.
+    pub event_id: Option<String>,
+    /// Supporting campaigns by id, with the participating channel they belong to. Channels with
+    /// a `campaign_id` setting don't need to be listed.
+    pub campaigns: BTreeMap<String, String>,
+    /// What the other channels post for a donation to a participant's campaign.
+    pub others: OtherChannels,
+}
+
+/// What channels post for donations to another participant's campaign.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OtherChannels {
+    /// The same as the participant's own channel.
+    #[default]
+    Announcement,
+    /// Only the plain `donation_message`.
+    Message,
+    /// Nothing.
+    None,
+}
+
+impl TeamConfig {
+    /// Whether a donation belongs to the team event.
+    pub fn includes(&self, team_event_id: Option<&str>) -> bool {
+        self.event_id.is_none() || self.event_id.as_deref() == team_event_id
+    }
+}
+
+/// Messages when participating channels go live and offline.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct StreamsConfig {
+    /// Whether channels get `stream_welcome` when they go live.
+    pub welcome: bool,
+    /// Whether channels get `stream_summary` when they go offline.
+    pub summaries: bool,
+    /// Participating channel that gets every `stream_summary` as well.
+    pub ops_channel: Option<String>,
+}
+
+impl Default for StreamsConfig {
+    fn default() -> Self {
+        Self {
+            welcome: true,
+            summaries: true,
+            ops_channel: None,
+        }
+    }
+}
+
+/// Raids between participating channels.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RaidsConfig {
+    /// How many hours back raid trains are built from.
+    pub history_hours: u64,
+    /// How `!raid` orders live participants, later rules break ties of earlier ones.
+    pub suggest_order: Vec<RaidOrder>,
+    /// How many live participants `!raid` lists at most.
+    pub suggestions: usize,
+    /// Seconds the live participants `!raid` lists are reused before asking Helix again.
+    pub live_cache_secs: u64,
+    /// Whether raided channels give the participant who raided them a shoutout. Needs the
+    /// broadcaster of the raided channel to have logged in through `auth streamer`.
+    pub shoutouts: bool,
+}
+
+impl Default for RaidsConfig {
+    fn default() -> Self {
+        Self {
+            history_hours: 24,
+            suggest_order: vec![RaidOrder::LongestSinceRaided, RaidOrder::FewestViewers],
+            suggestions: 5,
+            live_cache_secs: 60,
+            shoutouts: true,
+        }
+    }
+}
+
+/// A rule for ordering raid suggestions.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RaidOrder {
+    FewestViewers,
+    MostViewers,
+    /// Channels that were never raided by a participant come first.
+    LongestSinceRaided,
+}
+
+impl RaidsConfig {
+    /// The oldest raid time raid trains are built from.
+    pub fn history_start(&self) -> chrono::NaiveDateTime {
+        let history = chrono::TimeDelta::try_hours(self.history_hours.try_into().unwrap_or(i64::MAX))
+            .unwrap_or(chrono::TimeDelta::MAX);
+        chrono::Utc::now()
+            .naive_utc()
+            .checked_sub_signed(history)
+            .unwrap_or(chrono::NaiveDateTime::MIN)
+    }
+}
+
+/// Rules that make donations wait for a moderator before they are announced.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct HoldsConfig {
+    /// Terms in the donor name or comment that hold a donation, ignoring case.
+    pub blocked_words: Vec<String>,
+    /// Whether donations with a link in the donor name or comment are held.
+    pub urls: bool,
+    /// Donations above this amount are held.
+    pub amount_above: Option<f64>,
+    /// Whether the first donation under every donor name is held.
+    pub first_time_donors: bool,
+    /// Participating channel whose moderators settle holds with `!approve <id>` and
+    /// `!reject <id>`. Holds are also listed there as they come in.
+    pub control_channel: Option<String>,
+    /// Seconds a hold waits for a moderator.
+    pub timeout_secs: u64,
+    /// What happens to holds nobody settled in time.
+    pub on_timeout: HoldTimeout,
+}
+
+impl Default for HoldsConfig {
+    fn default() -> Self {
+        Self {
+            blocked_words: Vec::new(),
+            urls: false,
+            amount_above: None,
+            first_time_donors: false,
+            control_channel: None,
+            timeout_secs: 900,
+            on_timeout: HoldTimeout::Drop,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldTimeout {
+    /// The donation is announced without a comment that wasn't approved.
+    Release,
+    /// The donation is never announced.
+    Drop,
+}
+
+impl HoldTimeout {
+    /// The hold status recorded when the timeout settles a hold.
+    pub fn status(&self) -> &'static str {
+        match self {
+            HoldTimeout::Release => "released",
+            HoldTimeout::Drop => "dropped",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    /// Where notifications are sent. Replaces the default Twitch chat sink when set.
+    pub sinks: Vec<SinkConfig>,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            sinks: vec![SinkConfig {
+                name: "twitch".to_string(),
+                events: vec![EventKind::Donation, EventKind::Milestone],
+                kind: SinkKind::TwitchChat,
+            }],
+        }
+    }
+}
+
+/// A notification sink and the events routed to it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SinkConfig {
+    pub name: String,
+    #[serde(default = "EventKind::all")]
+    pub events: Vec<EventKind>,
+    #[serde(flatten)]
+    pub kind: SinkKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Donation,
+    Milestone,
+    Stream,
+    Raid,
+}
+
+impl EventKind {
+    pub fn all() -> Vec<Self> {
+        vec![Self::Donation, Self::Milestone, Self::Stream, Self::Raid]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Donation => "donation",
+            EventKind::Milestone => "milestone",
+            EventKind::Stream => "stream",
+            EventKind::Raid => "raid",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkKind {
+    /// Messages and announcements in every moderated live channel.
+    TwitchChat,
+    /// A Discord (or Discord-compatible) incoming webhook.
+    Discord {
+        url: String,
+        username: Option<String>,
+    },
+    /// JSON posts signed with HMAC-SHA256 in the `X-Warbot-Signature-256` header.
+    Webhook { url: String, secret: String },
+    /// One JSON object per line appended to a file.
+    File { path: String },
+}
+
+/// An automation endpoint that receives selected bot events as signed JSON posts.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WebhookConfig {
+    pub name: String,
+    pub url: String,
+    /// Key for the HMAC-SHA256 signature in `X-Warbot-Signature-256`.
+    pub secret: String,
+    pub events: Vec<WebhookEvent>,
+    /// JSON sent instead of the default `{"event", "data"}` document. Strings in it may use the
+    /// event's placeholders.
+    pub payload: Option<serde_json::Value>,
+    /// Attempts before a delivery is given up on, retried with exponential backoff.
+    #[serde(default = "WebhookConfig::default_max_attempts")]
+    pub max_attempts: u32,
+}
+
+impl WebhookConfig {
+    fn default_max_attempts() -> u32 {
+        5
+    }
+}
+
+/// Bot events outbound webhooks can subscribe to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    DonationReceived,
+    Announcement,
+    RaidInitiated,
+    StreamStarted,
+    StreamEnded,
+    ChannelsChanged,
+}
+
+impl WebhookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::DonationReceived => "donation_received",
+            WebhookEvent::Announcement => "announcement",
+            WebhookEvent::RaidInitiated => "raid_initiated",
+            WebhookEvent::StreamStarted => "stream_started",
+            WebhookEvent::StreamEnded => "stream_ended",
+            WebhookEvent::ChannelsChanged => "channels_changed",
+        }
+    }
+
+    /// Placeholders payload templates can use for this event.
+    pub fn placeholders(&self) -> &'static [&'static str] {
+        match self {
+            WebhookEvent::DonationReceived => {
+                &["id", "campaign_id", "amount", "currency", "name"]
+            }
+            WebhookEvent::Announcement => &["message"],
+            WebhookEvent::RaidInitiated => &["from", "to", "viewers"],
+            WebhookEvent::StreamStarted | WebhookEvent::StreamEnded => &["channel"],
+            WebhookEvent::ChannelsChanged => &[],
+        }
+    }
+}
+
+/// Error and trace reporting to Sentry. Nothing is sent unless a DSN is configured.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct SentryConfig {
+    pub dsn: Option<String>,
+    pub environment: Option<String>,
+    /// Share of errors that are reported.
+    pub sample_rate: f32,
+    /// Share of transactions that are traced.
+    pub traces_sample_rate: f32,
+    /// Whether user IPs and request headers are attached to events.
+    pub send_default_pii: bool,
+}
+
+impl Default for SentryConfig {
+    fn default() -> Self {
+        Self {
+            dsn: None,
+            environment: None,
+            sample_rate: 1.0,
+            traces_sample_rate: 0.0,
+            send_default_pii: false,
+        }
+    }
+}
+
+/// Trace export to an OpenTelemetry collector over OTLP/HTTP. Disabled unless an endpoint is set.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct OtlpConfig {
+    /// Base url of the collector, e.g. `http://localhost:4318`.
+    pub endpoint: Option<String>,
+    pub service_name: String,
+    /// Share of traces that are exported.
+    pub traces_sample_rate: f64,
+}
+
+impl Default for OtlpConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            service_name: env!("CARGO_PKG_NAME").to_string(),
+            traces_sample_rate: 1.0,
+        }
+    }
+}
+
+/// Settings that can be overridden from the environment or the command line.
+///
+/// Command line flags take precedence over environment variables, which take precedence over
+/// the config file.
+#[derive(Args, Debug, Clone, Default)]
+pub struct ConfigArgs {
+    /// Path to the config file
+    #[arg(long = "config", env = "WARBOT_CONFIG", global = true)]
+    pub path: Option<PathBuf>,
+    /// Address the HTTP server binds to
+    #[arg(long, env = "WARBOT_HOST", global = true)]
+    pub host: Option<Ipv4Addr>,
+    /// Port the HTTP server listens on
+    #[arg(long, env = "WARBOT_PORT", global = true)]
+    pub port: Option<u16>,
+    /// Where the bot user and its tokens are stored
+    #[arg(long, env = "WARBOT_STORAGE_BOT", global = true)]
+    pub storage_bot: Option<String>,
+    /// Where the participating channels are stored
+    #[arg(long, env = "WARBOT_STORAGE_CHANNELS", global = true)]
+    pub storage_channels: Option<String>,
+    /// Where streamer tokens are stored
+    #[arg(long, env = "WARBOT_STORAGE_STREAMERS", global = true)]
+    pub storage_streamers: Option<String>,
+    /// Path to the SQLite database
+    #[arg(long, env = "WARBOT_DATABASE", global = true)]
+    pub database: Option<String>,
+    /// Bearer token for the admin API
+    #[arg(long, env = "WARBOT_ADMIN_API_KEY", hide_env_values = true, global = true)]
+    pub admin_api_key: Option<String>,
+    /// Twitch application client id
+    #[arg(long, env = "CLIENT_ID", global = true)]
+    pub client_id: Option<String>,
+    /// Twitch application client secret
+    #[arg(long, env = "CLIENT_SECRET", hide_env_values = true, global = true)]
+    pub client_secret: Option<String>,
+    /// Twitch user id the bot has to be logged in as
+    #[arg(long, env = "BOT_USER_ID", global = true)]
+    pub bot_user_id: Option<String>,
+    /// Twitch login the bot has to be logged in as
+    #[arg(long, env = "BOT_USER_NAME", global = true)]
+    pub bot_user_name: Option<String>,
+    /// Tiltify application client id
+    #[arg(long, env = "TILTIFY_CLIENT_ID", global = true)]
+    pub tiltify_client_id: Option<String>,
+    /// Tiltify application client secret
+    #[arg(long, env = "TILTIFY_CLIENT_SECRET", hide_env_values = true, global = true)]
+    pub tiltify_client_secret: Option<String>,
+    /// Sentry DSN errors and traces are reported to
+    #[arg(long, env = "SENTRY_DSN", hide_env_values = true, global = true)]
+    pub sentry_dsn: Option<String>,
+    /// Environment reported to Sentry
+    #[arg(long, env = "SENTRY_ENVIRONMENT", global = true)]
+    pub sentry_environment: Option<String>,
+    /// Base url of the OTLP/HTTP collector traces are exported to
+    #[arg(long, env = "OTEL_EXPORTER_OTLP_ENDPOINT", global = true)]
+    pub otlp_endpoint: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("couldn't read config file {0}: {1}")]
+    Read(PathBuf, #[source] std::io::Error),
+    #[error("couldn't parse config file {0}: {1}")]
+    Parse(PathBuf, #[source] toml::de::Error),
+    #[error("invalid configuration:\n{}", .0.iter().map(|p| format!("  - {p}")).collect::<Vec<_>>().join("\n"))]
+    Invalid(Vec<String>),
+}
+
+impl ConfigArgs {
+    pub fn config_path(&self) -> PathBuf {
+        self.path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH))
+    }
+}
 
 impl Config {
-    pub fn load(path: impl AsRef<Path>) -> Result<Self, Report> {
-        let config = std::fs::read_to_string(path)?;
-        toml::from_str(&config).wrap_err("Failed to parse config")
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let config = std::fs::read_to_string(path)
+            .map_err(|e| ConfigError::Read(path.to_path_buf(), e))?;
+        toml::from_str(&config).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))
+    }
+
+    /// Loads the config file, applies environment and command line overrides and validates the
+    /// result.
+    pub fn load_layered(args: &ConfigArgs) -> Result<Self, ConfigError> {
+        let config = Self::load_overridden(args)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Like [`Config::load_layered`], but for commands that don't talk to Twitch, so its
+    /// credentials may be missing.
+    pub fn load_layered_without_twitch(args: &ConfigArgs) -> Result<Self, ConfigError> {
+        let config = Self::load_overridden(args)?;
+        config.validate_without_twitch()?;
+        Ok(config)
+    }
+
+    fn load_overridden(args: &ConfigArgs) -> Result<Self, ConfigError> {
+        let mut config = match &args.path {
+            Some(path) => Self::load(path)?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => Self::load(DEFAULT_CONFIG_PATH)?,
+            None => Self::default(),
+        };
+        config.apply(args);
+        Ok(config)
+    }
+
+    pub fn apply(&mut self, args: &ConfigArgs) {
+        let args = args.clone();
+        if let Some(host) = args.host {
+            self.server.host = host;
+        }
+        if let Some(port) = args.port {
+            self.server.port = port;
+        }
+        if let Some(bot) = args.storage_bot {
+            self.storage.bot = bot;
+        }
+        if let Some(channels) = args.storage_channels {
+            self.storage.channels = channels;
+        }
+        if let Some(streamers) = args.storage_streamers {
+            self.storage.streamers = streamers;
+        }
+        if let Some(database) = args.database {
+            self.storage.database = database;
+        }
+        if let Some(api_key) = args.admin_api_key {
+            self.admin.api_key = Some(api_key);
+        }
+        if let Some(client_id) = args.client_id {
+            self.twitch.client_id = client_id;
+        }
+        if let Some(client_secret) = args.client_secret {
+            self.twitch.client_secret = client_secret;
+        }
+        if let Some(bot_user_id) = args.bot_user_id {
+            self.twitch.bot_user_id = Some(bot_user_id);
+        }
+        if let Some(bot_user_name) = args.bot_user_name {
+            self.twitch.bot_user_name = Some(bot_user_name);
+        }
+        if let Some(client_id) = args.tiltify_client_id {
+            self.tiltify.client_id = Some(client_id);
+        }
+        if let Some(client_secret) = args.tiltify_client_secret {
+            self.tiltify.client_secret = Some(client_secret);
+        }
+        if let Some(dsn) = args.sentry_dsn {
+            self.sentry.dsn = Some(dsn);
+        }
+        if let Some(environment) = args.sentry_environment {
+            self.sentry.environment = Some(environment);
+        }
+        if let Some(endpoint) = args.otlp_endpoint {
+            self.otlp.endpoint = Some(endpoint);
+        }
+    }
+
+    /// The key in `campaigns` of a donation's campaign or team event.
+    pub fn campaign_key(
+        &self,
+        campaign_id: Option<&str>,
+        team_event_id: Option<&str>,
+    ) -> Option<&str> {
+        [campaign_id, team_event_id]
+            .into_iter()
+            .flatten()
+            .find_map(|id| self.campaigns.get_key_value(id))
+            .map(|(key, _)| key.as_str())
+    }
+
+    /// The campaign under `key` in `campaigns`, or `[campaign]`.
+    pub fn campaign(&self, key: Option<&str>) -> &CampaignConfig {
+        key.and_then(|key| self.campaigns.get(key))
+            .unwrap_or(&self.campaign)
+    }
+
+    /// The donations counting toward the campaign under `key`. `[campaign]` counts those to its
+    /// `id` or the team event, or without either every donation that isn't to one of `campaigns`.
+    pub fn campaign_scope<'a>(&'a self, key: Option<&'a str>) -> CampaignScope<'a> {
+        match key.or(self.campaign.id.as_deref()).or(self.team.event_id.as_deref()) {
+            Some(id) => CampaignScope::Id(id),
+            None => CampaignScope::Except(self.campaigns.keys().map(String::as_str).collect()),
+        }
+    }
+
+    /// The donations counting toward what a channel raises for: the campaign in `campaigns` it
+    /// raises for, else its own supporting campaign, else `[campaign]`.
+    pub fn channel_scope<'a>(&'a self, channel: &'a Channel) -> CampaignScope<'a> {
+        match self.channel_campaign_key(channel) {
+            Some(key) => CampaignScope::Id(key),
+            None => match channel.settings.campaign_id.as_deref() {
+                Some(id) => CampaignScope::Id(id),
+                None => self.campaign_scope(None),
+            },
+        }
+    }
+
+    /// The key in `campaigns` of the campaign a channel raises for: the one its `campaign_id`
+    /// setting names, else the first one listing it.
+    pub fn channel_campaign_key(&self, channel: &Channel) -> Option<&str> {
+        channel
+            .settings
+            .campaign_id
+            .as_deref()
+            .and_then(|id| self.campaigns.get_key_value(id))
+            .or_else(|| {
+                self.campaigns
+                    .iter()
+                    .find(|(_, c)| !c.channels.is_empty() && c.includes(channel))
+            })
+            .map(|(key, _)| key.as_str())
+    }
+
+    /// The campaign a channel raises for.
+    pub fn channel_campaign(&self, channel: &Channel) -> &CampaignConfig {
+        self.campaign(self.channel_campaign_key(channel))
+    }
+
+    /// Tiltify ids of the campaigns whose polls and targets a channel follows: its own supporting
+    /// campaign, the campaign it raises for and the team event.
+    pub fn channel_tiltify_ids<'a>(&'a self, channel: &'a Channel) -> Vec<&'a str> {
+        let key = self.channel_campaign_key(channel);
+        let mut ids = Vec::new();
+        for id in [
+            channel.settings.campaign_id.as_deref(),
+            self.campaign(key).id.as_deref().or(key),
+            self.team.event_id.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        ids
+    }
+
+    /// Checks the whole config, reporting every problem at once.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.check(true)
+    }
+
+    /// Checks everything but the Twitch credentials.
+    pub fn validate_without_twitch(&self) -> Result<(), ConfigError> {
+        self.check(false)
+    }
+
+    fn check(&self, twitch: bool) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        if self.server.port == 0 {
+            problems.push("server.port must not be 0 (WARBOT_PORT, --port)".to_string());
+        }
+        for (name, value) in [
+            ("storage.bot", &self.storage.bot),
+            ("storage.channels", &self.storage.channels),
+            ("storage.database", &self.storage.database),
+        ] {
+            if value.trim().is_empty() {
+                problems.push(format!("{name} must not be empty"));
+            }
+        }
+        if self.admin.api_key.as_ref().is_some_and(|k| k.trim().is_empty()) {
+            problems.push("admin.api_key must not be empty when set (WARBOT_ADMIN_API_KEY)".to_string());
+        }
+        if twitch && self.twitch.client_id.trim().is_empty() {
+            problems.push("twitch.client_id is missing (CLIENT_ID, --client-id)".to_string());
+        }
+        if twitch && self.twitch.client_secret.trim().is_empty() {
+            problems.push("twitch.client_secret is missing (CLIENT_SECRET, --client-secret)".to_string());
+        }
+        if self.tiltify.client_id.is_some() != self.tiltify.client_secret.is_some() {
+            problems.push(
+                "tiltify.client_id and tiltify.client_secret must be set together (TILTIFY_CLIENT_ID, TILTIFY_CLIENT_SECRET)"
+                    .to_string(),
+            );
+        }
+        problems.extend(template_problems("templates", self.templates.all()));
+        for (language, overrides) in &self.translations {
+            problems.extend(overrides.problems(&format!("translations.{language}")));
+        }
+        problems.extend(self.campaign.problems("campaign"));
+        for (id, campaign) in &self.campaigns {
+            problems.extend(campaign.problems(&format!("campaigns.{id}")));
+        }
+        for (campaign, channel) in &self.team.campaigns {
+            if channel.trim().is_empty() {
+                problems.push(format!("team.campaigns has an empty channel for {campaign:?}"));
+            }
+        }
+        if self.donors.anonymous_name.trim().is_empty() {
+            problems.push("donors.anonymous_name must not be empty".to_string());
+        }
+        if self.donors.max_name_length == 0 {
+            problems.push("donors.max_name_length must be at least 1".to_string());
+        }
+        for (donor, alias) in &self.donors.aliases {
+            if alias.trim().is_empty() {
+                problems.push(format!("donors.aliases has an empty alias for {donor:?}"));
+            }
+        }
+        for (name, value) in [
+            ("donors.anonymous_below", self.donors.anonymous_below),
+            ("donors.anonymous_above", self.donors.anonymous_above),
+        ] {
+            if value.is_some_and(|v| !v.is_finite() || v < 0.0) {
+                problems.push(format!("{name} must not be negative"));
+            }
+        }
+        if self.moderation.max_comment_length == 0 {
+            problems.push("moderation.max_comment_length must be at least 1".to_string());
+        }
+        if self.moderation.blocklist.iter().any(|term| term.trim().is_empty()) {
+            problems.push("moderation.blocklist must not contain empty terms".to_string());
+        }
+        if self.holds.blocked_words.iter().any(|term| term.trim().is_empty()) {
+            problems.push("holds.blocked_words must not contain empty terms".to_string());
+        }
+        if self.holds.amount_above.is_some_and(|a| !a.is_finite() || a < 0.0) {
+            problems.push("holds.amount_above must not be negative".to_string());
+        }
+        if self.holds.timeout_secs == 0 {
+            problems.push("holds.timeout_secs must be at least 1".to_string());
+        }
+        if self.raids.history_hours == 0 {
+            problems.push("raids.history_hours must be at least 1".to_string());
+        }
+        if self.raids.suggestions == 0 {
+            problems.push("raids.suggestions must be at least 1".to_string());
+        }
+        let mut sink_names = std::collections::HashSet::new();
+        for sink in &self.notifications.sinks {
+            if !sink_names.insert(sink.name.as_str()) {
+                problems.push(format!("notification sink {} is configured twice", sink.name));
+            }
+            let (url, secret, path) = match &sink.kind {
+                SinkKind::TwitchChat => (None, None, None),
+                SinkKind::Discord { url, .. } => (Some(url), None, None),
+                SinkKind::Webhook { url, secret } => (Some(url), Some(secret), None),
+                SinkKind::File { path } => (None, None, Some(path)),
+            };
+            if url.is_some_and(|url| url::Url::parse(url).is_err()) {
+                problems.push(format!("notification sink {} has an invalid url", sink.name));
+            }
+            if secret.is_some_and(|secret| secret.trim().is_empty()) {
+                problems.push(format!("notification sink {} needs a secret", sink.name));
+            }
+            if path.is_some_and(|path| path.trim().is_empty()) {
+                problems.push(format!("notification sink {} needs a path", sink.name));
+            }
+        }
+        let mut webhook_names = std::collections::HashSet::new();
+        for webhook in &self.webhooks {
+            let name = &webhook.name;
+            if !webhook_names.insert(name.as_str()) {
+                problems.push(format!("webhook {name} is configured twice"));
+            }
+            if url::Url::parse(&webhook.url).is_err() {
+                problems.push(format!("webhook {name} has an invalid url"));
+            }
+            if webhook.secret.trim().is_empty() {
+                problems.push(format!("webhook {name} needs a secret"));
+            }
+            if webhook.events.is_empty() {
+                problems.push(format!("webhook {name} has no events"));
+            }
+            if webhook.max_attempts == 0 {
+                problems.push(format!("webhook {name} needs max_attempts of at least 1"));
+            }
+            for placeholder in webhook.payload.iter().flat_map(payload_placeholders) {
+                if !webhook.events.iter().all(|e| e.placeholders().contains(&placeholder.as_str())) {
+                    problems.push(format!(
+                        "webhook {name} payload uses placeholder {{{placeholder}}}, which not all of its events provide"
+                    ));
+                }
+            }
+        }
+        if let Some(dsn) = &self.sentry.dsn
+            && dsn.parse::<sentry::types::Dsn>().is_err()
+        {
+            problems.push("sentry.dsn is not a valid DSN (SENTRY_DSN)".to_string());
+        }
+        for (name, value) in [
+            ("sentry.sample_rate", self.sentry.sample_rate as f64),
+            ("sentry.traces_sample_rate", self.sentry.traces_sample_rate as f64),
+            ("otlp.traces_sample_rate", self.otlp.traces_sample_rate),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                problems.push(format!("{name} must be between 0 and 1"));
+            }
+        }
+        if let Some(endpoint) = &self.otlp.endpoint
+            && url::Url::parse(endpoint).is_err()
+        {
+            problems.push("otlp.endpoint is not a valid url (OTEL_EXPORTER_OTLP_ENDPOINT)".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+}
+
+/// Placeholders used in the strings of a JSON payload template.
+fn payload_placeholders(payload: &serde_json::Value) -> Vec<String> {
+    match payload {
+        serde_json::Value::String(s) => template::placeholders(s)
+            .into_iter()
+            .map(str::to_string)
+            .collect(),
+        serde_json::Value::Array(values) => values.iter().flat_map(payload_placeholders).collect(),
+        serde_json::Value::Object(map) => map.values().flat_map(payload_placeholders).collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        config: ConfigArgs,
+    }
+
+    #[test]
+    fn flags_override_env_which_overrides_the_file() {
+        let path = std::env::temp_dir().join(format!("warbot-config-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"
+            [server]
+            port = 8000
+            [storage]
+            bot = "file-bot.json"
+            database = "file.sqlite"
+            [twitch]
+            client_id = "id"
+            client_secret = "secret"
+            "#,
+        )
+        .unwrap();
+        // SAFETY: no other test reads these variables.
+        unsafe {
+            std::env::set_var("WARBOT_PORT", "9000");
+            std::env::set_var("WARBOT_DATABASE", "env.sqlite");
+        }
+        let cli = Cli::try_parse_from([
+            "warbot",
+            "--config",
+            path.to_str().unwrap(),
+            "--database",
+            "flag.sqlite",
+        ]);
+        unsafe {
+            std::env::remove_var("WARBOT_PORT");
+            std::env::remove_var("WARBOT_DATABASE");
+        }
+
+        let config = Config::load_layered(&cli.unwrap().config);
+        std::fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+        assert_eq!(config.storage.bot, "file-bot.json");
+        assert_eq!(config.server.port, 9000);
+        assert_eq!(config.storage.database, "flag.sqlite");
+    }
+
+    #[test]
+    fn reports_every_problem_at_once() {
+        let mut config = Config::default();
+        config.server.port = 0;
+        config.storage.database = " ".to_string();
+        config.holds.timeout_secs = 0;
+
+        let Err(ConfigError::Invalid(problems)) = config.validate() else {
+            panic!("the config should be invalid");
+        };
+        assert_eq!(
+            problems,
+            [
+                "server.port must not be 0 (WARBOT_PORT, --port)",
+                "storage.database must not be empty",
+                "twitch.client_id is missing (CLIENT_ID, --client-id)",
+                "twitch.client_secret is missing (CLIENT_SECRET, --client-secret)",
+                "holds.timeout_secs must be at least 1",
+            ]
+        );
+        let Err(ConfigError::Invalid(problems)) = config.validate_without_twitch() else {
+            panic!("the config should be invalid");
+        };
+        assert_eq!(problems.len(), 3);
+        assert!(!problems.iter().any(|p| p.starts_with("twitch.")));
     }
 }
@@ -0,0 +1,748 @@
+use crate::db::models::{
+    ChannelRaised, Delivery, Donation, DonationWithDeliveries, Hold, HoldWithDonation, NewDelivery, NewHold,
+    NewAttribution, NewRaid, NewReachedTarget, NewRewardClaim, NewStream, NewWebhookAttempt, NewWebhookDelivery, Raid,
+    RewardClaim, RewardClaimWithDonation, Stream, WebhookAttempt, WebhookDelivery,
+    WebhookDeliveryWithAttempts,
+};
+use crate::routes::tiltify::TiltifyDonation;
+use diesel::expression::BoxableExpression;
+use diesel::prelude::*;
+use diesel::sql_types::Bool;
+use diesel::sqlite::{Sqlite, SqliteConnection};
+use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
+use eyre::{Report, WrapErr, eyre};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::info;
+
+pub mod models;
+pub mod schema;
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+const PAUSED_KEY: &str = "paused";
+const DEFERRED_KEY: &str = "deferred";
+
+#[derive(Debug, Clone, Copy)]
+pub enum DeliveryKind {
+    Message,
+    Announcement,
+    Comment,
+}
+
+impl DeliveryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryKind::Message => "message",
+            DeliveryKind::Announcement => "announcement",
+            DeliveryKind::Comment => "comment",
+        }
+    }
+}
+
+/// The donations that count toward a campaign. Simulated donations never do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CampaignScope<'a> {
+    /// Donations to this Tiltify campaign or team event.
+    Id(&'a str),
+    /// Donations to none of these campaigns or team events, for `[campaign]` without an id.
+    Except(Vec<&'a str>),
+}
+
+impl CampaignScope<'_> {
+    fn matches(
+        &self,
+    ) -> Box<dyn BoxableExpression<schema::donations::table, Sqlite, SqlType = Bool> + '_> {
+        use schema::donations::dsl::{campaign_id, simulated, team_event_id};
+        match self {
+            CampaignScope::Id(campaign) => Box::new(
+                campaign_id
+                    .eq(*campaign)
+                    .or(team_event_id.eq(*campaign))
+                    .assume_not_null()
+                    .and(simulated.eq(false)),
+            ),
+            CampaignScope::Except(ids) => Box::new(
+                campaign_id
+                    .is_null()
+                    .or(campaign_id.ne_all(ids.clone()).assume_not_null())
+                    .and(
+                        team_event_id
+                            .is_null()
+                            .or(team_event_id.ne_all(ids.clone()).assume_not_null()),
+                    )
+                    .and(simulated.eq(false)),
+            ),
+        }
+    }
+}
+
+/// Persistent storage for donations, their chat deliveries and bot state.
+#[derive(Clone)]
+pub struct Store {
+    conn: Arc<Mutex<SqliteConnection>>,
+}
+
+impl Store {
+    #[tracing::instrument]
+    pub fn open(path: &str) -> Result<Self, Report> {
+        let mut conn = SqliteConnection::establish(path)
+            .wrap_err_with(|| format!("couldn't open database {path}"))?;
+        let applied = conn
+            .run_pending_migrations(MIGRATIONS)
+            .map_err(|e| eyre!("couldn't run migrations: {e}"))?;
+        for migration in applied {
+            info!("Applied migration {}", migration);
+        }
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Stores a donation, returning `false` when it was already stored before.
+    pub async fn insert_donation(&self, donation: &TiltifyDonation) -> Result<bool, Report> {
+        use schema::donations::dsl::*;
+        let row = Donation {
+            id: donation.id.clone(),
+            campaign_id: donation.campaign_id.clone(),
+            amount_value: donation.amount.value.clone(),
+            amount_currency: donation.amount.currency.clone(),
+            donor_name: donation.name.clone(),
+            donor_comment: donation.message.clone(),
+            received_at: chrono::Utc::now().naive_utc(),
+            comment_status: None,
+            comment_reason: None,
+            team_event_id: donation.team_event_id.clone(),
+            poll_id: donation.poll_id.clone(),
+            poll_option_id: donation.poll_option_id.clone(),
+            target_id: donation.target_id.clone(),
+            simulated: donation.simulated,
+        };
+        let mut conn = self.conn.lock().await;
+        let inserted = diesel::insert_or_ignore_into(donations)
+            .values(&row)
+            .execute(&mut *conn)?;
+        Ok(inserted == 1)
+    }
+
+    pub async fn set_comment_status(
+        &self,
+        donation: &str,
+        status: &str,
+        reason: Option<&str>,
+    ) -> Result<(), Report> {
+        use schema::donations::dsl::*;
+        let mut conn = self.conn.lock().await;
+        diesel::update(donations.find(donation))
+            .set((comment_status.eq(status), comment_reason.eq(reason)))
+            .execute(&mut *conn)?;
+        Ok(())
+    }
+
+    pub async fn donation(&self, donation: &str) -> Result<Option<Donation>, Report> {
+        let mut conn = self.conn.lock().await;
+        Ok(schema::donations::table
+            .find(donation)
+            .select(Donation::as_select())
+            .first(&mut *conn)
+            .optional()?)
+    }
+
+    /// Whether the donor gave before, under the same name, outside of `donation`.
+    pub async fn donated_before(&self, name: &str, donation: &str) -> Result<bool, Report> {
+        use schema::donations::dsl::*;
+        let mut conn = self.conn.lock().await;
+        let earlier: i64 = donations
+            .filter(donor_name.eq(name))
+            .filter(id.ne(donation))
+            .filter(simulated.eq(false))
+            .count()
+            .get_result(&mut *conn)?;
+        Ok(earlier > 0)
+    }
+
+    pub async fn record_delivery(&self, delivery: NewDelivery<'_>) -> Result<(), Report> {
+        let mut conn = self.conn.lock().await;
+        diesel::insert_into(schema::deliveries::table)
+            .values(&delivery)
+            .execute(&mut *conn)?;
+        Ok(())
+    }
+
+    /// The latest donations, only those to `campaign` or its team event when given.
+    pub async fn recent_donations(
+        &self,
+        limit: i64,
+        campaign: Option<&str>,
+    ) -> Result<Vec<DonationWithDeliveries>, Report> {
+        use schema::donations::dsl::{campaign_id, received_at, team_event_id};
+        let mut conn = self.conn.lock().await;
+        let mut query = schema::donations::table
+            .order(received_at.desc())
+            .limit(limit)
+            .select(Donation::as_select())
+            .into_boxed();
+        if let Some(campaign) = campaign {
+            query = query.filter(campaign_id.eq(campaign).or(team_event_id.eq(campaign)));
+        }
+        let donations = query.load(&mut *conn)?;
+        let deliveries = Delivery::belonging_to(&donations)
+            .select(Delivery::as_select())
+            .load(&mut *conn)?;
+        Ok(deliveries
+            .grouped_by(&donations)
+            .into_iter()
+            .zip(donations)
+            .map(|(deliveries, donation)| DonationWithDeliveries {
+                donation,
+                deliveries,
+            })
+            .collect())
+    }
+
+    /// Sum of all stored donations counting toward a campaign.
+    pub async fn campaign_total(&self, campaign: &CampaignScope<'_>) -> Result<f64, Report> {
+        use schema::donations::dsl::*;
+        let mut conn = self.conn.lock().await;
+        let amounts: Vec<String> = donations
+            .filter(campaign.matches())
+            .select(amount_value)
+            .load(&mut *conn)?;
+        Ok(amounts.iter().filter_map(|a| a.parse::<f64>().ok()).sum())
+    }
+
+    /// Stores a webhook delivery, returning `None` when one with the same webhook and event key
+    /// already exists.
+    pub async fn create_webhook_delivery(
+        &self,
+        delivery: NewWebhookDelivery<'_>,
+    ) -> Result<Option<WebhookDelivery>, Report> {
+        let mut conn = self.conn.lock().await;
+        Ok(diesel::insert_or_ignore_into(schema::webhook_deliveries::table)
+            .values(&delivery)
+            .returning(WebhookDelivery::as_returning())
+            .get_result(&mut *conn)
+            .optional()?)
+    }
+
+    pub async fn webhook_delivery(&self, delivery: i32) -> Result<Option<WebhookDelivery>, Report> {
+        let mut conn = self.conn.lock().await;
+        Ok(schema::webhook_deliveries::table
+            .find(delivery)
+            .select(WebhookDelivery::as_select())
+            .first(&mut *conn)
+            .optional()?)
+    }
+
+    pub async fn pending_webhook_deliveries(&self) -> Result<Vec<WebhookDelivery>, Report> {
+        use schema::webhook_deliveries::dsl::*;
+        let mut conn = self.conn.lock().await;
+        Ok(webhook_deliveries
+            .filter(status.eq("pending"))
+            .order(id.asc())
+            .select(WebhookDelivery::as_select())
+            .load(&mut *conn)?)
+    }
+
+    pub async fn record_webhook_attempt(&self, attempt: NewWebhookAttempt) -> Result<(), Report> {
+        let mut conn = self.conn.lock().await;
+        diesel::insert_into(schema::webhook_attempts::table)
+            .values(&attempt)
+            .execute(&mut *conn)?;
+        Ok(())
+    }
+
+    pub async fn set_webhook_delivery_status(
+        &self,
+        delivery: i32,
+        new_status: &str,
+    ) -> Result<(), Report> {
+        use schema::webhook_deliveries::dsl::*;
+        let mut conn = self.conn.lock().await;
+        diesel::update(webhook_deliveries.find(delivery))
+            .set(status.eq(new_status))
+            .execute(&mut *conn)?;
+        Ok(())
+    }
+
+    /// The latest webhook deliveries, only those of donations to `campaign` or its team event
+    /// when given.
+    pub async fn recent_webhook_deliveries(
+        &self,
+        limit: i64,
+        campaign: Option<&str>,
+    ) -> Result<Vec<WebhookDeliveryWithAttempts>, Report> {
+        use schema::donations::{campaign_id, team_event_id};
+        let mut conn = self.conn.lock().await;
+        let mut query = schema::webhook_deliveries::table
+            .order(schema::webhook_deliveries::id.desc())
+            .limit(limit)
+            .select(WebhookDelivery::as_select())
+            .into_boxed();
+        if let Some(campaign) = campaign {
+            let donations = schema::donations::table
+                .filter(campaign_id.eq(campaign).or(team_event_id.eq(campaign)))
+                .select(schema::donations::id.nullable());
+            query = query.filter(schema::webhook_deliveries::event_key.eq_any(donations));
+        }
+        let deliveries = query.load(&mut *conn)?;
+        let attempts = WebhookAttempt::belonging_to(&deliveries)
+            .order(schema::webhook_attempts::id.asc())
+            .select(WebhookAttempt::as_select())
+            .load(&mut *conn)?;
+        Ok(attempts
+            .grouped_by(&deliveries)
+            .into_iter()
+            .zip(deliveries)
+            .map(|(attempts, delivery)| WebhookDeliveryWithAttempts { delivery, attempts })
+            .collect())
+    }
+
+    pub async fn create_hold(
+        &self,
+        donation: &str,
+        reasons: &str,
+        comment_only: bool,
+    ) -> Result<Hold, Report> {
+        let mut conn = self.conn.lock().await;
+        Ok(diesel::insert_into(schema::holds::table)
+            .values(NewHold {
+                donation_id: donation,
+                reasons,
+                status: "pending",
+                held_at: chrono::Utc::now().naive_utc(),
+                comment_only,
+            })
+            .returning(Hold::as_returning())
+            .get_result(&mut *conn)?)
+    }
+
+    pub async fn hold(&self, hold: i32) -> Result<Option<Hold>, Report> {
+        let mut conn = self.conn.lock().await;
+        Ok(schema::holds::table
+            .find(hold)
+            .select(Hold::as_select())
+            .first(&mut *conn)
+            .optional()?)
+    }
+
+    /// Settles a pending hold, returning `None` when it isn't pending (anymore).
+    pub async fn decide_hold(
+        &self,
+        hold: i32,
+        decision: &str,
+        by: &str,
+    ) -> Result<Option<Hold>, Report> {
+        use schema::holds::dsl::*;
+        let mut conn = self.conn.lock().await;
+        Ok(diesel::update(holds.find(hold).filter(status.eq("pending")))
+            .set((
+                status.eq(decision),
+                decided_at.eq(chrono::Utc::now().naive_utc()),
+                decided_by.eq(by),
+            ))
+            .returning(Hold::as_returning())
+            .get_result(&mut *conn)
+            .optional()?)
+    }
+
+    /// Pending holds that were placed before `cutoff`.
+    pub async fn expired_holds(&self, cutoff: chrono::NaiveDateTime) -> Result<Vec<Hold>, Report> {
+        use schema::holds::dsl::*;
+        let mut conn = self.conn.lock().await;
+        Ok(holds
+            .filter(status.eq("pending"))
+            .filter(held_at.lt(cutoff))
+            .select(Hold::as_select())
+            .load(&mut *conn)?)
+    }
+
+    /// Most recent holds, optionally only those with `with_status` and of donations to
+    /// `campaign` or its team event.
+    pub async fn recent_holds(
+        &self,
+        with_status: Option<&str>,
+        limit: i64,
+        campaign: Option<&str>,
+    ) -> Result<Vec<HoldWithDonation>, Report> {
+        use schema::donations::{campaign_id, team_event_id};
+        let mut conn = self.conn.lock().await;
+        let mut query = schema::holds::table
+            .inner_join(schema::donations::table)
+            .order(schema::holds::held_at.desc())
+            .limit(limit)
+            .select((Hold::as_select(), Donation::as_select()))
+            .into_boxed();
+        if let Some(with_status) = with_status {
+            query = query.filter(schema::holds::status.eq(with_status));
+        }
+        if let Some(campaign) = campaign {
+            query = query.filter(campaign_id.eq(campaign).or(team_event_id.eq(campaign)));
+        }
+        Ok(query
+            .load::<(Hold, Donation)>(&mut *conn)?
+            .into_iter()
+            .map(|(hold, donation)| HoldWithDonation { hold, donation })
+            .collect())
+    }
+
+    pub async fn record_raid(&self, raid: NewRaid<'_>) -> Result<Raid, Report> {
+        let mut conn = self.conn.lock().await;
+        Ok(diesel::insert_into(schema::raids::table)
+            .values(raid)
+            .returning(Raid::as_returning())
+            .get_result(&mut *conn)?)
+    }
+
+    /// Raids since `since`, oldest first.
+    pub async fn raids_since(&self, since: chrono::NaiveDateTime) -> Result<Vec<Raid>, Report> {
+        use schema::raids::dsl::*;
+        let mut conn = self.conn.lock().await;
+        Ok(raids
+            .filter(raided_at.ge(since))
+            .order((raided_at.asc(), id.asc()))
+            .select(Raid::as_select())
+            .load(&mut *conn)?)
+    }
+
+    /// When each channel was last raided by a participant, keyed by channel id.
+    pub async fn last_raided(
+        &self,
+    ) -> Result<std::collections::HashMap<String, chrono::NaiveDateTime>, Report> {
+        use diesel::dsl::max;
+        use schema::raids::dsl::*;
+        let mut conn = self.conn.lock().await;
+        let last: Vec<(String, Option<chrono::NaiveDateTime>)> = raids
+            .group_by(to_id)
+            .select((to_id, max(raided_at)))
+            .load(&mut *conn)?;
+        Ok(last
+            .into_iter()
+            .filter_map(|(channel, at)| Some((channel, at?)))
+            .collect())
+    }
+
+    /// Opens a stream of a channel, or returns the one that is still open.
+    pub async fn start_stream(&self, stream: NewStream<'_>) -> Result<Stream, Report> {
+        use schema::streams::dsl::*;
+        let mut conn = self.conn.lock().await;
+        conn.transaction(|conn| {
+            let open = streams
+                .filter(channel_id.eq(stream.channel_id))
+                .filter(ended_at.is_null())
+                .select(Stream::as_select())
+                .first(conn)
+                .optional()?;
+            match open {
+                Some(open) => Ok(open),
+                None => diesel::insert_into(streams)
+                    .values(&stream)
+                    .returning(Stream::as_returning())
+                    .get_result(conn),
+            }
+        })
+        .map_err(Report::from)
+    }
+
+    /// Closes the open stream of a channel, returning `None` when there is none.
+    pub async fn end_stream(
+        &self,
+        channel: &str,
+        at: chrono::NaiveDateTime,
+    ) -> Result<Option<Stream>, Report> {
+        use schema::streams::dsl::*;
+        let mut conn = self.conn.lock().await;
+        Ok(diesel::update(
+            streams
+                .filter(channel_id.eq(channel))
+                .filter(ended_at.is_null()),
+        )
+        .set(ended_at.eq(at))
+        .returning(Stream::as_returning())
+        .get_result(&mut *conn)
+        .optional()?)
+    }
+
+    /// The latest stream of a channel.
+    pub async fn latest_stream(&self, channel: &str) -> Result<Option<Stream>, Report> {
+        use schema::streams::dsl::*;
+        let mut conn = self.conn.lock().await;
+        Ok(streams
+            .filter(channel_id.eq(channel))
+            .order((started_at.desc(), id.desc()))
+            .select(Stream::as_select())
+            .first(&mut *conn)
+            .optional()?)
+    }
+
+    /// How many donations to a campaign that are attributed to `channel` were received between
+    /// `from` and `to`, and their sum.
+    pub async fn donations_between(
+        &self,
+        campaign: &CampaignScope<'_>,
+        channel: &str,
+        from: chrono::NaiveDateTime,
+        to: chrono::NaiveDateTime,
+    ) -> Result<(usize, f64), Report> {
+        use schema::donations::dsl::*;
+        let mut conn = self.conn.lock().await;
+        let attributed = schema::attributions::table
+            .filter(schema::attributions::channel_id.eq(channel))
+            .select(schema::attributions::donation_id);
+        let amounts: Vec<String> = donations
+            .filter(received_at.between(from, to))
+            .filter(id.eq_any(attributed))
+            .filter(campaign.matches())
+            .select(amount_value)
+            .load(&mut *conn)?;
+        Ok((
+            amounts.len(),
+            amounts.iter().filter_map(|a| a.parse::<f64>().ok()).sum(),
+        ))
+    }
+
+    /// Stores who a donation is attributed to, keeping earlier attributions of it.
+    pub async fn attribute(&self, attributions: &[NewAttribution<'_>]) -> Result<(), Report> {
+        let mut conn = self.conn.lock().await;
+        diesel::insert_or_ignore_into(schema::attributions::table)
+            .values(attributions)
+            .execute(&mut *conn)?;
+        Ok(())
+    }
+
+    /// What each attributed channel raised, most raised while live first. Only donations to
+    /// `campaign` or its team event count when given.
+    pub async fn raised_by_channel(
+        &self,
+        campaign: Option<&str>,
+    ) -> Result<Vec<ChannelRaised>, Report> {
+        use schema::attributions::dsl::*;
+        use schema::donations::{amount_value, campaign_id, simulated, team_event_id};
+        let mut conn = self.conn.lock().await;
+        let mut query = attributions
+            .inner_join(schema::donations::table)
+            .filter(simulated.eq(false))
+            .select((channel_id, channel_name, live, credited, amount_value))
+            .into_boxed();
+        if let Some(campaign) = campaign {
+            query = query.filter(campaign_id.eq(campaign).or(team_event_id.eq(campaign)));
+        }
+        let rows: Vec<(String, String, bool, bool, String)> = query.load(&mut *conn)?;
+        let mut by_channel: Vec<ChannelRaised> = Vec::new();
+        for (channel, name, was_live, was_credited, amount) in rows {
+            let amount = amount.parse::<f64>().unwrap_or_default();
+            let index = match by_channel.iter().position(|r| r.channel_id == channel) {
+                Some(index) => index,
+                None => {
+                    by_channel.push(ChannelRaised {
+                        channel_id: channel,
+                        channel_name: name,
+                        ..ChannelRaised::default()
+                    });
+                    by_channel.len() - 1
+                }
+            };
+            let raised = &mut by_channel[index];
+            if was_live {
+                raised.live_donations += 1;
+                raised.raised_while_live += amount;
+            }
+            if was_credited {
+                raised.credited_donations += 1;
+                raised.credited += amount;
+            }
+        }
+        by_channel.sort_by(|a, b| b.raised_while_live.total_cmp(&a.raised_while_live));
+        Ok(by_channel)
+    }
+
+    /// Records that a target was reached, returning `false` when it was recorded before.
+    pub async fn reach_target(&self, target: NewReachedTarget<'_>) -> Result<bool, Report> {
+        let mut conn = self.conn.lock().await;
+        let inserted = diesel::insert_or_ignore_into(schema::reached_targets::table)
+            .values(target)
+            .execute(&mut *conn)?;
+        Ok(inserted == 1)
+    }
+
+    /// Queues rewards claimed with a donation.
+    pub async fn record_reward_claims(
+        &self,
+        claims: &[NewRewardClaim<'_>],
+    ) -> Result<Vec<RewardClaim>, Report> {
+        let mut conn = self.conn.lock().await;
+        conn.transaction(|conn| {
+            claims
+                .iter()
+                .map(|claim| {
+                    diesel::insert_into(schema::reward_claims::table)
+                        .values(claim)
+                        .returning(RewardClaim::as_returning())
+                        .get_result(conn)
+                })
+                .collect::<Result<Vec<_>, diesel::result::Error>>()
+        })
+        .wrap_err("couldn't record reward claims")
+    }
+
+    pub async fn reward_claim(&self, claim: i32) -> Result<Option<RewardClaim>, Report> {
+        let mut conn = self.conn.lock().await;
+        Ok(schema::reward_claims::table
+            .find(claim)
+            .select(RewardClaim::as_select())
+            .first(&mut *conn)
+            .optional()?)
+    }
+
+    /// Rewards claimed with a donation, in the order they were claimed.
+    pub async fn donation_reward_claims(&self, donation: &str) -> Result<Vec<RewardClaim>, Report> {
+        use schema::reward_claims::dsl::*;
+        let mut conn = self.conn.lock().await;
+        Ok(reward_claims
+            .filter(donation_id.eq(donation))
+            .order(id.asc())
+            .select(RewardClaim::as_select())
+            .load(&mut *conn)?)
+    }
+
+    /// Marks a pending reward claim done, returning `None` when it isn't pending (anymore).
+    pub async fn complete_reward_claim(
+        &self,
+        claim: i32,
+        by: &str,
+    ) -> Result<Option<RewardClaim>, Report> {
+        use schema::reward_claims::dsl::*;
+        let mut conn = self.conn.lock().await;
+        Ok(diesel::update(reward_claims.find(claim).filter(status.eq("pending")))
+            .set((
+                status.eq("done"),
+                done_at.eq(chrono::Utc::now().naive_utc()),
+                done_by.eq(by),
+            ))
+            .returning(RewardClaim::as_returning())
+            .get_result(&mut *conn)
+            .optional()?)
+    }
+
+    /// Pending reward claims, oldest first. Only those credited to `channel` or to nobody when
+    /// given.
+    pub async fn pending_reward_claims(
+        &self,
+        channel: Option<&str>,
+    ) -> Result<Vec<RewardClaim>, Report> {
+        use schema::reward_claims::dsl::*;
+        let mut conn = self.conn.lock().await;
+        let mut query = reward_claims
+            .filter(status.eq("pending"))
+            .order((claimed_at.asc(), id.asc()))
+            .select(RewardClaim::as_select())
+            .into_boxed();
+        if let Some(channel) = channel {
+            query = query.filter(channel_id.eq(channel).or(channel_id.is_null()));
+        }
+        Ok(query.load(&mut *conn)?)
+    }
+
+    /// Most recent reward claims, optionally only those with `with_status` and claimed with
+    /// donations to `campaign` or its team event.
+    pub async fn recent_reward_claims(
+        &self,
+        with_status: Option<&str>,
+        limit: i64,
+        campaign: Option<&str>,
+    ) -> Result<Vec<RewardClaimWithDonation>, Report> {
+        use schema::donations::{campaign_id, team_event_id};
+        let mut conn = self.conn.lock().await;
+        let mut query = schema::reward_claims::table
+            .inner_join(schema::donations::table)
+            .order((
+                schema::reward_claims::claimed_at.desc(),
+                schema::reward_claims::id.desc(),
+            ))
+            .limit(limit)
+            .select((RewardClaim::as_select(), Donation::as_select()))
+            .into_boxed();
+        if let Some(with_status) = with_status {
+            query = query.filter(schema::reward_claims::status.eq(with_status));
+        }
+        if let Some(campaign) = campaign {
+            query = query.filter(campaign_id.eq(campaign).or(team_event_id.eq(campaign)));
+        }
+        Ok(query
+            .load::<(RewardClaim, Donation)>(&mut *conn)?
+            .into_iter()
+            .map(|(claim, donation)| RewardClaimWithDonation { claim, donation })
+            .collect())
+    }
+
+    pub async fn ping(&self) -> Result<(), Report> {
+        let mut conn = self.conn.lock().await;
+        // Unlike `SELECT 1`, reading the schema has to reach the database file.
+        diesel::sql_query("PRAGMA schema_version").execute(&mut *conn)?;
+        Ok(())
+    }
+
+    pub async fn is_paused(&self) -> Result<bool, Report> {
+        use schema::bot_state::dsl::*;
+        let mut conn = self.conn.lock().await;
+        let paused = bot_state
+            .find(PAUSED_KEY)
+            .select(value)
+            .first::<String>(&mut *conn)
+            .optional()?;
+        Ok(paused.is_some_and(|v| v == "true"))
+    }
+
+    pub async fn set_paused(&self, paused: bool) -> Result<(), Report> {
+        use schema::bot_state::dsl::*;
+        let mut conn = self.conn.lock().await;
+        diesel::replace_into(bot_state)
+            .values((key.eq(PAUSED_KEY), value.eq(paused.to_string())))
+            .execute(&mut *conn)?;
+        Ok(())
+    }
+
+    /// Keeps something the bot would have announced while paused, to announce on resume.
+    pub async fn defer<T: Serialize>(&self, item: &T) -> Result<(), Report> {
+        use schema::bot_state::dsl::*;
+        let item = serde_json::to_value(item)?;
+        let mut conn = self.conn.lock().await;
+        conn.transaction::<_, Report, _>(|conn| {
+            let stored = bot_state
+                .find(DEFERRED_KEY)
+                .select(value)
+                .first::<String>(conn)
+                .optional()?;
+            let mut items: Vec<serde_json::Value> = match stored {
+                Some(stored) => serde_json::from_str(&stored)?,
+                None => Vec::new(),
+            };
+            items.push(item);
+            diesel::replace_into(bot_state)
+                .values((key.eq(DEFERRED_KEY), value.eq(serde_json::to_string(&items)?)))
+                .execute(conn)?;
+            Ok(())
+        })
+    }
+
+    /// Removes and returns everything deferred while paused, oldest first.
+    pub async fn take_deferred<T: DeserializeOwned>(&self) -> Result<Vec<T>, Report> {
+        use schema::bot_state::dsl::*;
+        let mut conn = self.conn.lock().await;
+        let stored = conn.transaction::<_, Report, _>(|conn| {
+            let stored = bot_state
+                .find(DEFERRED_KEY)
+                .select(value)
+                .first::<String>(conn)
+                .optional()?;
+            diesel::delete(bot_state.find(DEFERRED_KEY)).execute(conn)?;
+            Ok(stored)
+        })?;
+        match stored {
+            Some(stored) => Ok(serde_json::from_str(&stored)?),
+            None => Ok(Vec::new()),
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use crate::db::schema::{
+    attributions, deliveries, donations, holds, raids, reached_targets, reward_claims, streams, webhook_attempts,
+    webhook_deliveries,
+};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde_derive::Serialize;
+
+#[derive(Queryable, Selectable, Identifiable, Insertable, Serialize, Debug, Clone)]
+#[diesel(table_name = donations)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Donation {
+    pub id: String,
+    pub campaign_id: Option<String>,
+    pub amount_value: String,
+    pub amount_currency: String,
+    pub donor_name: Option<String>,
+    pub donor_comment: Option<String>,
+    pub received_at: NaiveDateTime,
+    /// Outcome of the donor comment review: `approved`, `dropped` or `held`.
+    pub comment_status: Option<String>,
+    pub comment_reason: Option<String>,
+    pub team_event_id: Option<String>,
+    /// The Tiltify poll the donation voted in and the option it went to.
+    pub poll_id: Option<String>,
+    pub poll_option_id: Option<String>,
+    /// The Tiltify target the donation counts toward.
+    pub target_id: Option<String>,
+    /// Injected through the admin API, left out of totals and summaries.
+    pub simulated: bool,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Serialize, Debug, Clone)]
+#[diesel(belongs_to(Donation))]
+#[diesel(table_name = deliveries)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Delivery {
+    pub id: i32,
+    pub donation_id: Option<String>,
+    pub channel_id: String,
+    pub channel_name: String,
+    pub kind: String,
+    pub status: String,
+    pub error: Option<String>,
+    pub attempted_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = deliveries)]
+pub struct NewDelivery<'a> {
+    pub donation_id: Option<&'a str>,
+    pub channel_id: &'a str,
+    pub channel_name: &'a str,
+    pub kind: &'a str,
+    pub status: &'a str,
+    pub error: Option<String>,
+    pub attempted_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DonationWithDeliveries {
+    #[serde(flatten)]
+    pub donation: Donation,
+    pub deliveries: Vec<Delivery>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Serialize, Debug, Clone)]
+#[diesel(table_name = webhook_deliveries)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct WebhookDelivery {
+    pub id: i32,
+    pub webhook: String,
+    pub event: String,
+    pub event_key: Option<String>,
+    pub url: String,
+    pub payload: String,
+    pub status: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = webhook_deliveries)]
+pub struct NewWebhookDelivery<'a> {
+    pub webhook: &'a str,
+    pub event: &'a str,
+    pub event_key: Option<&'a str>,
+    pub url: &'a str,
+    pub payload: &'a str,
+    pub status: &'a str,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Serialize, Debug, Clone)]
+#[diesel(belongs_to(WebhookDelivery, foreign_key = delivery_id))]
+#[diesel(table_name = webhook_attempts)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct WebhookAttempt {
+    pub id: i32,
+    pub delivery_id: i32,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub attempted_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = webhook_attempts)]
+pub struct NewWebhookAttempt {
+    pub delivery_id: i32,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub attempted_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct WebhookDeliveryWithAttempts {
+    #[serde(flatten)]
+    pub delivery: WebhookDelivery,
+    pub attempts: Vec<WebhookAttempt>,
+}
+
+/// A donation waiting for a moderator before it is announced.
+#[derive(Queryable, Selectable, Identifiable, Associations, Serialize, Debug, Clone)]
+#[diesel(belongs_to(Donation))]
+#[diesel(table_name = holds)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Hold {
+    pub id: i32,
+    pub donation_id: String,
+    /// Why the donation was held, separated by `; `.
+    pub reasons: String,
+    /// `pending`, `approved`, `rejected`, `released` or `dropped`.
+    pub status: String,
+    pub held_at: NaiveDateTime,
+    pub decided_at: Option<NaiveDateTime>,
+    pub decided_by: Option<String>,
+    /// Only the donor comment waits, the donation was announced without it.
+    pub comment_only: bool,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = holds)]
+pub struct NewHold<'a> {
+    pub donation_id: &'a str,
+    pub reasons: &'a str,
+    pub status: &'a str,
+    pub held_at: NaiveDateTime,
+    pub comment_only: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct HoldWithDonation {
+    #[serde(flatten)]
+    pub hold: Hold,
+    pub donation: Donation,
+}
+
+/// A raid between two participating channels.
+#[derive(Queryable, Selectable, Identifiable, Serialize, Debug, Clone, PartialEq)]
+#[diesel(table_name = raids)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Raid {
+    pub id: i32,
+    pub from_id: String,
+    pub from_name: String,
+    pub to_id: String,
+    pub to_name: String,
+    pub viewers: i64,
+    pub raided_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = raids)]
+pub struct NewRaid<'a> {
+    pub from_id: &'a str,
+    pub from_name: &'a str,
+    pub to_id: &'a str,
+    pub to_name: &'a str,
+    pub viewers: i64,
+    pub raided_at: NaiveDateTime,
+}
+
+/// A time a participating channel was live.
+#[derive(Queryable, Selectable, Identifiable, Serialize, Debug, Clone, PartialEq)]
+#[diesel(table_name = streams)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Stream {
+    pub id: i32,
+    pub channel_id: String,
+    pub channel_name: String,
+    pub started_at: NaiveDateTime,
+    /// Empty while the channel is live.
+    pub ended_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = streams)]
+pub struct NewStream<'a> {
+    pub channel_id: &'a str,
+    pub channel_name: &'a str,
+    pub started_at: NaiveDateTime,
+}
+
+/// A participant a donation is attributed to, because they were live when it came in or
+/// because its target, reward or campaign belongs to them.
+#[derive(Insertable, Debug, Clone, PartialEq)]
+#[diesel(table_name = attributions)]
+pub struct NewAttribution<'a> {
+    pub donation_id: &'a str,
+    pub channel_id: &'a str,
+    pub channel_name: &'a str,
+    pub live: bool,
+    pub credited: bool,
+}
+
+/// What a participant raised, from their attributions.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ChannelRaised {
+    pub channel_id: String,
+    pub channel_name: String,
+    /// Donations that came in while the channel was live.
+    pub live_donations: usize,
+    pub raised_while_live: f64,
+    /// Donations to a target, reward or campaign of the channel.
+    pub credited_donations: usize,
+    pub credited: f64,
+}
+
+impl ChannelRaised {
+    /// A one line summary for `!raised`.
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{} raised ${:.2} from {} {} while live",
+            self.channel_name,
+            self.raised_while_live,
+            self.live_donations,
+            if self.live_donations == 1 {
+                "donation"
+            } else {
+                "donations"
+            }
+        );
+        if self.credited_donations > 0 {
+            summary.push_str(&format!(", plus ${:.2} credited to them", self.credited));
+        }
+        summary
+    }
+}
+
+/// A reward a donor claimed, waiting for the streamer until it is done.
+#[derive(Queryable, Selectable, Identifiable, Associations, Serialize, Debug, Clone, PartialEq)]
+#[diesel(belongs_to(Donation))]
+#[diesel(table_name = reward_claims)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RewardClaim {
+    pub id: i32,
+    pub donation_id: String,
+    /// The donor as they were shown in chat.
+    pub donor_name: String,
+    pub reward_id: String,
+    /// The reward's name on Tiltify, or its id when it couldn't be looked up.
+    pub reward_name: String,
+    pub quantity: i32,
+    /// The donor's answer to the reward's custom question.
+    pub response: Option<String>,
+    /// The participant the reward is credited to, if any.
+    pub channel_id: Option<String>,
+    pub channel_name: Option<String>,
+    /// `pending` or `done`.
+    pub status: String,
+    pub claimed_at: NaiveDateTime,
+    pub done_at: Option<NaiveDateTime>,
+    pub done_by: Option<String>,
+}
+
+#[derive(Insertable, Debug, Clone, PartialEq)]
+#[diesel(table_name = reward_claims)]
+pub struct NewRewardClaim<'a> {
+    pub donation_id: &'a str,
+    pub donor_name: &'a str,
+    pub reward_id: &'a str,
+    pub reward_name: String,
+    pub quantity: i32,
+    pub response: Option<&'a str>,
+    pub channel_id: Option<&'a str>,
+    pub channel_name: Option<&'a str>,
+    pub status: &'a str,
+    pub claimed_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RewardClaimWithDonation {
+    #[serde(flatten)]
+    pub claim: RewardClaim,
+    pub donation: Donation,
+}
+
+/// A Tiltify target whose completion was announced.
+#[derive(Insertable, Debug)]
+#[diesel(table_name = reached_targets)]
+pub struct NewReachedTarget<'a> {
+    pub target_id: &'a str,
+    pub target_name: &'a str,
+    pub donation_id: Option<&'a str>,
+    pub reached_at: NaiveDateTime,
+}
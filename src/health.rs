@@ -0,0 +1,59 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::time::Duration;
+
+pub type SharedHealth = Arc<Health>;
+
+/// How long the token refresh loop may go without ticking before it counts as stalled.
+pub const REFRESH_STALL_AFTER: Duration = Duration::from_secs(180);
+
+/// Liveness signals reported by the long-running bot tasks.
+#[derive(Debug, Default)]
+pub struct Health {
+    refresh_tick: AtomicI64,
+    token_validated: AtomicBool,
+    token_valid: AtomicBool,
+    eventsub_connected: AtomicBool,
+}
+
+impl Health {
+    pub fn refresh_ticked(&self) {
+        self.refresh_tick
+            .store(chrono::Utc::now().timestamp(), Ordering::Relaxed);
+    }
+
+    /// Pretends the refresh loop last ticked `secs` seconds ago.
+    #[cfg(test)]
+    pub fn refresh_ticked_ago(&self, secs: i64) {
+        self.refresh_tick
+            .store(chrono::Utc::now().timestamp() - secs, Ordering::Relaxed);
+    }
+
+    /// Seconds since the refresh loop last ticked, or `None` if it never did.
+    pub fn since_refresh_tick(&self) -> Option<i64> {
+        match self.refresh_tick.load(Ordering::Relaxed) {
+            0 => None,
+            tick => Some(chrono::Utc::now().timestamp() - tick),
+        }
+    }
+
+    pub fn token_validated(&self, valid: bool) {
+        self.token_validated.store(true, Ordering::Relaxed);
+        self.token_valid.store(valid, Ordering::Relaxed);
+    }
+
+    /// Result of the last token validation, or `None` if it was never validated.
+    pub fn token_valid(&self) -> Option<bool> {
+        self.token_validated
+            .load(Ordering::Relaxed)
+            .then(|| self.token_valid.load(Ordering::Relaxed))
+    }
+
+    pub fn set_eventsub_connected(&self, connected: bool) {
+        self.eventsub_connected.store(connected, Ordering::Relaxed);
+    }
+
+    pub fn eventsub_connected(&self) -> bool {
+        self.eventsub_connected.load(Ordering::Relaxed)
+    }
+}
@@ -1,66 +1,153 @@
 mod bot;
+mod cli;
 mod config;
+mod db;
+mod health;
+mod metrics;
+mod notify;
+mod reload;
 mod routes;
+mod telemetry;
+mod tiltify;
+mod webhooks;
 
 use crate::bot::Bot;
-use crate::bot::auth::{Channels, User, UserError};
-use crate::config::Config;
+use crate::bot::auth::{Channels, User};
+use crate::bot::raids::LiveCache;
+use crate::bot::shoutouts::Shoutouts;
+use crate::cli::Cli;
+use crate::config::{Config, ConfigArgs, SharedConfig};
+use crate::db::Store;
+use crate::health::{Health, SharedHealth};
+use crate::notify::Notifier;
+use crate::tiltify::TiltifyClient;
+use crate::webhooks::Webhooks;
 use crate::routes::tiltify::TiltifyDonation;
 use axum::{
     Router,
 };
+use clap::Parser;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
-use std::env;
 use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::broadcast::{Receiver, Sender};
-use tokio::sync::{Mutex, broadcast};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tokio::sync::{Mutex, RwLock, broadcast};
 use twitch_api::HelixClient;
-use twitch_api::types::UserId;
+use twitch_oauth2::UserToken;
 
 pub type SharedAppState = Arc<Mutex<AppState>>;
 pub struct AppState {
-    received_donations: HashSet<TiltifyDonation>,
     tx: Sender<Commands>,
+    config: SharedConfig,
+    client: HelixClient<'static, reqwest::Client>,
+    token: Arc<Mutex<UserToken>>,
+    channels: Arc<Mutex<Channels>>,
+    store: Store,
+    health: SharedHealth,
+    webhooks: Webhooks,
+}
+
+#[cfg(test)]
+impl AppState {
+    /// State for route tests, with alice participating and an in-memory database.
+    fn test(config: Config) -> (SharedAppState, Receiver<Commands>) {
+        let (tx, rx) = broadcast::channel(16);
+        let config = Arc::new(RwLock::new(config));
+        let store = Store::open(":memory:").unwrap();
+        let channels: Channels =
+            serde_json::from_value(serde_json::json!([{ "user_id": "1", "name": "alice" }]))
+                .unwrap();
+        let token = UserToken::from_existing_unchecked(
+            "access",
+            None,
+            "client",
+            None,
+            "warbot".into(),
+            "99".into(),
+            None,
+            None,
+        );
+        let state = AppState {
+            tx,
+            webhooks: Webhooks::new(config.clone(), store.clone()),
+            config,
+            client: HelixClient::default(),
+            token: Arc::new(Mutex::new(token)),
+            channels: Arc::new(Mutex::new(channels)),
+            store,
+            health: SharedHealth::default(),
+        };
+        (Arc::new(Mutex::new(state)), rx)
+    }
 }
 
 #[tokio::main]
 async fn main() {
     dotenvy::dotenv().ok();
-    tracing_subscriber::registry()
-        .with(sentry::integrations::tracing::layer())
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
-                format!(
-                    "debug,{}=trace,hyper_util=debug,axum_serve=debug,tungstenite=debug",
-                    env!("CARGO_CRATE_NAME")
-                )
-                .into()
-            }),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-
-    let _sentry = sentry::init((
-        "https://bccb4a945782b51898cbf804aba13f19@o4507225498845184.ingest.de.sentry.io/4509317367332944",
-        sentry::ClientOptions {
-            release: sentry::release_name!(),
-            traces_sample_rate: 1.0,
-            // Capture user IPs and potentially sensitive headers when using HTTP server integrations
-            // see https://docs.sentry.io/platforms/rust/data-management/data-collected for more info
-            send_default_pii: true,
-            ..Default::default()
-        },
+    let cli = Cli::parse();
+    // Telemetry has to be up before anything logs, so it starts from defaults when the config
+    // can't be loaded. `execute` reports the broken config again where it needs it.
+    let config = cli.load_config().unwrap_or_else(|e| {
+        eprintln!("Starting without Sentry and OTLP, the config couldn't be loaded: {e}");
+        Config::default()
+    });
+    let _telemetry = telemetry::init(&config.sentry, &config.otlp);
+
+    if let Err(e) = cli::execute(cli).await {
+        eprintln!("{e:?}");
+        std::process::exit(1);
+    }
+}
+
+/// Runs the webhook server and the bot until they stop.
+pub async fn run(config: Config, args: ConfigArgs) {
+    metrics::init();
+
+    let store = Store::open(&config.storage.database).expect("Failed to open database");
+
+    let helix_client = HelixClient::default();
+    let bot_user = User::load_or_login(&config.storage.bot, &helix_client, &config.twitch)
+        .await
+        .expect("Failed to log in bot");
+    bot_user
+        .check_account(&config.twitch)
+        .expect("Logged in as the wrong account");
+    bot_user.save(Path::new(&config.storage.bot)).unwrap();
+    let bot_token = Arc::new(Mutex::new(
+        bot_user
+            .user_token
+            .clone()
+            .expect("Failed to load bot token"),
     ));
 
-    let config = Config::load("config.toml").expect("Failed to load config");
+    let channels = Channels::load(&config.storage.channels).unwrap_or_default();
+    channels.save(&config.storage.channels).unwrap();
+    let channels = Arc::new(Mutex::new(channels));
+    let health: SharedHealth = Arc::new(Health::default());
 
     let (tx, rx): (Sender<Commands>, Receiver<Commands>) = broadcast::channel(100);
+    let shared_config: SharedConfig = Arc::new(RwLock::new(config.clone()));
+    tokio::spawn({
+        let config = shared_config.clone();
+        let channels = channels.clone();
+        let tx = tx.clone();
+        async move {
+            if let Err(e) = reload::watch(args, config, channels, tx).await {
+                tracing::error!("Config reloading stopped: {e:?}");
+            }
+        }
+    });
+    let webhooks = Webhooks::new(shared_config.clone(), store.clone());
+    tokio::spawn(webhooks.clone().run(tx.subscribe()));
     let app_state: SharedAppState = Arc::new(Mutex::new(AppState {
-        received_donations: HashSet::new(),
         tx: tx.clone(),
+        config: shared_config.clone(),
+        client: HelixClient::default(),
+        token: bot_token.clone(),
+        channels: channels.clone(),
+        store: store.clone(),
+        health: health.clone(),
+        webhooks: webhooks.clone(),
     }));
 
     let http_server = {
@@ -72,7 +159,9 @@ async fn main() {
                 .unwrap();
             tracing::debug!("listening on {}", listener.local_addr().unwrap());
 
-            let app = Router::new().merge(routes::router()).with_state(app_state);
+            let app = Router::new()
+                .merge(routes::router(app_state.clone()))
+                .with_state(app_state);
 
             axum::serve(listener, app)
                 // .with_graceful_shutdown(async move {
@@ -89,46 +178,24 @@ async fn main() {
 
     let http_handle = tokio::spawn(http_server);
 
-    let helix_client = HelixClient::default();
-    let mut bot_user = User::load(&config.storage.bot).unwrap_or_else(|_| User {
-        user_id: env::var("BOT_USER_ID")
-            .expect("BOT_USER_ID not in environment")
-            .into(),
-        twitch_name: env::var("BOT_USER_NAME")
-            .expect("BOT_USER_NAME not in environment")
-            .into(),
-        user_token: None,
-        expires_in: None,
-        refresh_token: None,
-        access_token: None,
-    });
-
-    match bot_user.ensure_token(&helix_client).await {
-        Ok(_) => {}
-        Err(e) => {
-            match e {
-                UserError::NoTokens => bot_user.new_user_token(&helix_client).await.unwrap(),
-                UserError::TokenError(_) => bot_user.new_user_token(&helix_client).await.unwrap(),
-            };
-            bot_user.ensure_token(&helix_client).await.unwrap();
-        }
-    }
-    bot_user.save(Path::new(&config.storage.bot)).unwrap();
-    let bot_token = Arc::new(Mutex::new(
-        bot_user
-            .user_token
-            .clone()
-            .expect("Failed to load bot token"),
-    ));
-
-    let channels = Channels::load(&config.storage.channels).unwrap_or_default();
-    channels.save(&config.storage.channels).unwrap();
     let mut bot = Bot {
         client: HelixClient::default(),
         token: bot_token.clone(),
-        config: config.clone(),
-        broadcaster: UserId::new("Test".to_string()),
+        config: shared_config.clone(),
         channels: channels.clone(),
+        store: store.clone(),
+        health: health.clone(),
+        notifier: Notifier::new(
+            shared_config.clone(),
+            HelixClient::default(),
+            bot_token.clone(),
+            channels.clone(),
+            store.clone(),
+        ),
+        tiltify: TiltifyClient::new(shared_config.clone()),
+        live: LiveCache::default(),
+        shoutouts: Mutex::new(Shoutouts::default()),
+        tx: tx.clone(),
         rx,
     };
     let bot_handle = bot.start();
@@ -153,7 +220,21 @@ async fn main() {
 enum Commands {
     Shutdown,
     DonationReceived(TiltifyDonation),
-    RaidInitiated(String),
+    Announcement(String),
+    ChannelsChanged,
+    RaidInitiated {
+        from: String,
+        to: String,
+        viewers: i64,
+    },
     StreamStarted(String),
     StreamEnded(String),
+    /// A held donation that was approved or released, with its comment only if it may be shown.
+    DonationReleased(TiltifyDonation),
+    /// A held comment that was approved, on a donation already announced without it.
+    CommentReleased(TiltifyDonation),
+    /// A donation the bot announced, with its comment only if it was shown.
+    DonationAnnounced(TiltifyDonation),
+    /// Announcements were resumed, so what was deferred while paused is announced.
+    Resumed,
 }
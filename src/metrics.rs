@@ -0,0 +1,288 @@
+use crate::config::Config;
+use prometheus::{
+    GaugeVec, HistogramVec, IntCounter, IntCounterVec, IntGauge, register_gauge_vec,
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge,
+};
+use std::sync::LazyLock;
+use std::time::Instant;
+use twitch_api::helix::{ClientRequestError, HelixRequestGetError, HelixRequestPostError};
+
+pub static WEBHOOKS_RECEIVED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "warbot_webhooks_received_total",
+        "Tiltify webhooks received, by campaign and event type",
+        &["campaign", "event_type"]
+    )
+    .unwrap()
+});
+
+pub static DONATIONS_ANNOUNCED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "warbot_donations_announced_total",
+        "Donations that were fanned out to live channels, by campaign",
+        &["campaign"]
+    )
+    .unwrap()
+});
+
+pub static CAMPAIGN_RAISED: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!(
+        "warbot_campaign_raised",
+        "Amount raised at the last donation, by campaign",
+        &["campaign"]
+    )
+    .unwrap()
+});
+
+pub static CHAT_MESSAGES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "warbot_chat_messages_total",
+        "Chat messages and announcements sent, by channel, kind and result",
+        &["channel", "kind", "result"]
+    )
+    .unwrap()
+});
+
+pub static NOTIFICATIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "warbot_notifications_total",
+        "Notifications handed to sinks, by campaign, sink, event and result",
+        &["campaign", "sink", "event", "result"]
+    )
+    .unwrap()
+});
+
+pub static WEBHOOK_ATTEMPTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "warbot_webhook_attempts_total",
+        "Outbound webhook posts, by campaign, webhook and result",
+        &["campaign", "webhook", "result"]
+    )
+    .unwrap()
+});
+
+pub static HOLDS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "warbot_holds_total",
+        "Donations held for moderators and how their holds were settled, by campaign and status",
+        &["campaign", "status"]
+    )
+    .unwrap()
+});
+
+pub static REWARD_CLAIMS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "warbot_reward_claims_total",
+        "Rewards claimed with donations and fulfilled by streamers, by campaign and status",
+        &["campaign", "status"]
+    )
+    .unwrap()
+});
+
+pub static RAIDS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "warbot_raids_total",
+        "Raids from one participating channel to another"
+    )
+    .unwrap()
+});
+
+pub static SHOUTOUTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "warbot_shoutouts_total",
+        "Shoutouts for raiding participants, by outcome",
+        &["outcome"]
+    )
+    .unwrap()
+});
+
+pub static HELIX_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "warbot_helix_request_duration_seconds",
+        "Latency of Helix API requests, by endpoint",
+        &["endpoint"]
+    )
+    .unwrap()
+});
+
+pub static HELIX_RESPONSES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "warbot_helix_responses_total",
+        "Helix API responses, by endpoint and status code",
+        &["endpoint", "status"]
+    )
+    .unwrap()
+});
+
+pub static TOKEN_REFRESHES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "warbot_token_refreshes_total",
+        "Bot token refreshes, by result",
+        &["result"]
+    )
+    .unwrap()
+});
+
+pub static EVENTSUB_RECONNECTS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "warbot_eventsub_reconnects_total",
+        "EventSub websocket reconnects"
+    )
+    .unwrap()
+});
+
+pub static LIVE_CHANNELS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "warbot_live_channels",
+        "Participating channels that were live at the last check"
+    )
+    .unwrap()
+});
+
+pub static BROADCAST_QUEUE_DEPTH: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "warbot_broadcast_queue_depth",
+        "Commands waiting in the broadcast channel"
+    )
+    .unwrap()
+});
+
+/// Registers every metric up front so they show up before their first observation.
+pub fn init() {
+    LazyLock::force(&WEBHOOKS_RECEIVED);
+    LazyLock::force(&DONATIONS_ANNOUNCED);
+    LazyLock::force(&CAMPAIGN_RAISED);
+    LazyLock::force(&CHAT_MESSAGES);
+    LazyLock::force(&NOTIFICATIONS);
+    LazyLock::force(&WEBHOOK_ATTEMPTS);
+    LazyLock::force(&HOLDS);
+    LazyLock::force(&REWARD_CLAIMS);
+    LazyLock::force(&RAIDS);
+    LazyLock::force(&SHOUTOUTS);
+    LazyLock::force(&HELIX_REQUEST_DURATION);
+    LazyLock::force(&HELIX_RESPONSES);
+    LazyLock::force(&TOKEN_REFRESHES);
+    LazyLock::force(&EVENTSUB_RECONNECTS);
+    LazyLock::force(&LIVE_CHANNELS);
+    LazyLock::force(&BROADCAST_QUEUE_DEPTH);
+}
+
+/// The `campaign` label of a campaign in `campaigns`, `default` for `[campaign]`.
+pub fn campaign_label(key: Option<&str>) -> &str {
+    key.unwrap_or("default")
+}
+
+/// The `campaign` label of events that aren't about a donation, like raids.
+pub const NO_CAMPAIGN: &str = "none";
+
+/// The `campaign` label of a donation, by the campaign or team event it went to.
+pub fn donation_campaign_label<'a>(
+    config: &'a Config,
+    campaign_id: Option<&str>,
+    team_event_id: Option<&str>,
+) -> &'a str {
+    campaign_label(config.campaign_key(campaign_id, team_event_id))
+}
+
+pub fn result_label<T, E>(result: &Result<T, E>) -> &'static str {
+    if result.is_ok() { "success" } else { "failure" }
+}
+
+/// Records latency and status code of a Helix request started at `started`.
+pub fn observe_helix<T, RE>(
+    endpoint: &str,
+    started: Instant,
+    result: &Result<T, ClientRequestError<RE>>,
+) where
+    RE: std::error::Error + Send + Sync + 'static,
+{
+    HELIX_REQUEST_DURATION
+        .with_label_values(&[endpoint])
+        .observe(started.elapsed().as_secs_f64());
+    let status = match result {
+        Ok(_) => "2xx".to_string(),
+        Err(ClientRequestError::HelixRequestGetError(HelixRequestGetError::Error {
+            status, ..
+        }))
+        | Err(ClientRequestError::HelixRequestPostError(HelixRequestPostError::Error {
+            status,
+            ..
+        })) => status.as_u16().to_string(),
+        Err(_) => "error".to_string(),
+    };
+    HELIX_RESPONSES
+        .with_label_values(&[endpoint, status.as_str()])
+        .inc();
+}
+
+pub fn gather() -> String {
+    prometheus::TextEncoder::new()
+        .encode_to_string(&prometheus::gather())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AppState;
+
+    #[tokio::test]
+    async fn renders_the_families_and_counts_received_donations() {
+        init();
+        let (state, _rx) = AppState::test(Config::default());
+        let base = crate::routes::serve(state).await;
+        let received = || {
+            WEBHOOKS_RECEIVED
+                .with_label_values(&["default", "public:direct:donation_updated"])
+                .get()
+        };
+        let before = received();
+
+        let body = serde_json::json!({
+            "data": {
+                "amount": { "currency": "USD", "value": "25.00" },
+                "campaign_id": "campaign",
+                "cause_id": "cause",
+                "completed_at": "2026-10-18T12:00:00Z",
+                "created_at": "2026-10-18T12:00:00Z",
+                "id": "donation",
+                "legacy_id": 0,
+                "sustained": false,
+            },
+            "meta": {
+                "id": "event",
+                "event_type": "public:direct:donation_updated",
+                "attempted_at": "2026-10-18T12:00:00Z",
+                "generated_at": "2026-10-18T12:00:00Z",
+                "subscription_source_id": "campaign",
+                "subscription_source_type": "test",
+            },
+        });
+        let http = reqwest::Client::new();
+        let response = http
+            .post(format!("{base}/webhook"))
+            .header("content-type", "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+        assert_eq!(received(), before + 1);
+
+        let metrics = http.get(format!("{base}/metrics")).send().await.unwrap();
+        let rendered = metrics.text().await.unwrap();
+        for family in [
+            "warbot_raids_total",
+            "warbot_eventsub_reconnects_total",
+            "warbot_live_channels",
+            "warbot_broadcast_queue_depth",
+        ] {
+            assert!(rendered.contains(&format!("# TYPE {family} ")), "{family} missing");
+        }
+        assert!(rendered.contains(&format!(
+            "warbot_webhooks_received_total{{campaign=\"default\",\
+             event_type=\"public:direct:donation_updated\"}} {}",
+            before + 1
+        )));
+    }
+}
@@ -0,0 +1,103 @@
+use crate::config::TemplatesConfig;
+use crate::notify::{Notification, NotificationSink};
+use async_trait::async_trait;
+use eyre::{Report, WrapErr};
+use serde_derive::Serialize;
+
+/// Posts the announcement text to a Discord-compatible incoming webhook.
+pub struct DiscordSink {
+    pub http: reqwest::Client,
+    pub url: String,
+    pub username: Option<String>,
+    pub templates: TemplatesConfig,
+}
+
+#[derive(Debug, Serialize)]
+struct DiscordMessage<'a> {
+    content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    username: Option<&'a str>,
+    /// Donor names must never ping anyone.
+    allowed_mentions: AllowedMentions,
+}
+
+#[derive(Debug, Serialize)]
+struct AllowedMentions {
+    parse: [&'static str; 0],
+}
+
+#[async_trait]
+impl NotificationSink for DiscordSink {
+    async fn notify(&self, notification: &Notification) -> Result<(), Report> {
+        let mut content = notification.announcement(&self.templates);
+        if let Some(comment) = notification.comment_message(&self.templates) {
+            content.push('\n');
+            content.push_str(&comment);
+        }
+        let message = DiscordMessage {
+            content,
+            username: self.username.as_deref(),
+            allowed_mentions: AllowedMentions { parse: [] },
+        };
+        self.http
+            .post(&self.url)
+            .json(&message)
+            .send()
+            .await
+            .wrap_err("couldn't reach Discord webhook")?
+            .error_for_status()
+            .wrap_err("Discord webhook rejected the message")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::stand_in::StandIn;
+    use axum::http::StatusCode;
+
+    fn sink(url: String) -> DiscordSink {
+        DiscordSink {
+            http: reqwest::Client::new(),
+            url,
+            username: Some("warbot".to_string()),
+            templates: TemplatesConfig::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn posts_rendered_announcement() {
+        let stand_in = StandIn::start(|_| (StatusCode::NO_CONTENT, String::new())).await;
+        let notification = Notification::Raid {
+            from: "alice".to_string(),
+            to: "bob".to_string(),
+            viewers: 42,
+        };
+
+        sink(stand_in.url("/webhook")).notify(&notification).await.unwrap();
+
+        let requests = stand_in.requests().await;
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/webhook");
+        assert_eq!(
+            requests[0].json(),
+            serde_json::json!({
+                "content": "alice is raiding bob with 42 viewers!",
+                "username": "warbot",
+                "allowed_mentions": { "parse": [] },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn fails_on_error_status() {
+        let stand_in =
+            StandIn::start(|_| (StatusCode::BAD_REQUEST, "{\"message\":\"nope\"}".into())).await;
+        let notification = Notification::StreamStarted {
+            channel: "alice".to_string(),
+        };
+
+        assert!(sink(stand_in.url("/")).notify(&notification).await.is_err());
+    }
+}
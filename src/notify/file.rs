@@ -0,0 +1,104 @@
+use crate::notify::{Envelope, Notification, NotificationSink};
+use async_trait::async_trait;
+use eyre::{Report, WrapErr};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Appends every notification as a line of JSON (NDJSON) to a file.
+pub struct FileSink {
+    pub path: PathBuf,
+    pub lock: Arc<Mutex<()>>,
+}
+
+#[async_trait]
+impl NotificationSink for FileSink {
+    async fn notify(&self, notification: &Notification) -> Result<(), Report> {
+        let mut line = serde_json::to_string(&Envelope::new(notification))?;
+        line.push('\n');
+        let _guard = self.lock.lock().await;
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .wrap_err_with(|| format!("couldn't append to {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::stand_in::StandIn;
+    use crate::notify::webhook::WebhookSink;
+    use axum::http::StatusCode;
+
+    #[tokio::test]
+    async fn appends_one_line_per_notification() {
+        let path = std::env::temp_dir().join(format!(
+            "warbot-file-sink-{}-{}.ndjson",
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let sink = FileSink {
+            path: path.clone(),
+            lock: Arc::new(Mutex::new(())),
+        };
+        let started = Notification::StreamStarted {
+            channel: "alice".to_string(),
+        };
+        let ended = Notification::StreamEnded {
+            channel: "alice".to_string(),
+        };
+
+        sink.notify(&started).await.unwrap();
+        sink.notify(&ended).await.unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["event"], "stream_started");
+        assert_eq!(lines[1]["event"], "stream_ended");
+        assert_eq!(lines[1]["channel"], "alice");
+    }
+
+    #[tokio::test]
+    async fn lines_match_webhook_payloads() {
+        // Consumers can replay the file into a webhook receiver, so both carry the same document.
+        let stand_in = StandIn::start(|_| (StatusCode::OK, String::new())).await;
+        let path = std::env::temp_dir().join(format!(
+            "warbot-file-sink-replay-{}.ndjson",
+            std::process::id()
+        ));
+        let notification = Notification::Raid {
+            from: "alice".to_string(),
+            to: "bob".to_string(),
+            viewers: 7,
+        };
+        let file = FileSink {
+            path: path.clone(),
+            lock: Arc::new(Mutex::new(())),
+        };
+        let webhook = WebhookSink {
+            http: reqwest::Client::new(),
+            url: stand_in.url("/"),
+            secret: "secret".to_string(),
+        };
+
+        file.notify(&notification).await.unwrap();
+        webhook.notify(&notification).await.unwrap();
+
+        let mut line: serde_json::Value =
+            serde_json::from_str(std::fs::read_to_string(&path).unwrap().trim()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut posted = stand_in.requests().await[0].json();
+        line.as_object_mut().unwrap().remove("sent_at");
+        posted.as_object_mut().unwrap().remove("sent_at");
+        assert_eq!(line, posted);
+    }
+}
@@ -0,0 +1,331 @@
+use crate::bot::auth::{Channels, Streamers};
+use crate::bot::template::{self, TooLong};
+use crate::bot::donors;
+use crate::config::{Config, DonorsConfig, EventKind, SharedConfig, SinkConfig, SinkKind, TemplatesConfig};
+use crate::db::Store;
+use crate::metrics;
+use crate::routes::tiltify::TiltifyDonation;
+use crate::routes::webhook::Amount;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use eyre::Report;
+use serde_derive::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::error;
+use twitch_api::HelixClient;
+use twitch_oauth2::UserToken;
+
+pub mod discord;
+pub mod file;
+#[cfg(test)]
+pub(crate) mod stand_in;
+pub mod twitch;
+pub mod webhook;
+
+pub use discord::DiscordSink;
+pub use file::FileSink;
+pub use twitch::TwitchChatSink;
+pub use webhook::WebhookSink;
+
+/// An event the bot tells the outside world about.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Notification {
+    Donation {
+        id: String,
+        campaign_id: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        team_event_id: Option<String>,
+        amount: Amount,
+        name: String,
+        /// The donor's comment, only present once it passed moderation.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        comment: Option<String>,
+    },
+    Milestone {
+        campaign_id: Option<String>,
+        milestone: Amount,
+        total: Amount,
+    },
+    StreamStarted {
+        channel: String,
+    },
+    StreamEnded {
+        channel: String,
+    },
+    Raid {
+        from: String,
+        to: String,
+        viewers: i64,
+    },
+}
+
+impl From<&TiltifyDonation> for Notification {
+    fn from(donation: &TiltifyDonation) -> Self {
+        Notification::donation(donation, &DonorsConfig::default(), None)
+    }
+}
+
+impl Notification {
+    /// A donation notification under the donor's display name, carrying a comment that passed
+    /// moderation.
+    pub fn donation(
+        donation: &TiltifyDonation,
+        donors: &DonorsConfig,
+        comment: Option<String>,
+    ) -> Self {
+        Notification::Donation {
+            id: donation.id.clone(),
+            campaign_id: donation.campaign_id.clone(),
+            team_event_id: donation.team_event_id.clone(),
+            amount: donation.amount.clone(),
+            name: donors::display_name(donors, donation),
+            comment,
+        }
+    }
+
+    /// The key in `campaigns` of the campaign a donation or milestone belongs to.
+    pub fn campaign_key<'a>(&self, config: &'a Config) -> Option<&'a str> {
+        match self {
+            Notification::Donation {
+                campaign_id,
+                team_event_id,
+                ..
+            } => config.campaign_key(campaign_id.as_deref(), team_event_id.as_deref()),
+            Notification::Milestone { campaign_id, .. } => {
+                config.campaign_key(campaign_id.as_deref(), None)
+            }
+            _ => None,
+        }
+    }
+
+    /// The routing category of this notification.
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Notification::Donation { .. } => EventKind::Donation,
+            Notification::Milestone { .. } => EventKind::Milestone,
+            Notification::StreamStarted { .. } | Notification::StreamEnded { .. } => {
+                EventKind::Stream
+            }
+            Notification::Raid { .. } => EventKind::Raid,
+        }
+    }
+
+    /// Values for the placeholders of this notification's templates.
+    fn vars(&self) -> Vec<(&'static str, String)> {
+        match self {
+            Notification::Donation {
+                amount,
+                name,
+                comment,
+                ..
+            } => vec![
+                ("amount", amount.value.clone()),
+                ("currency", amount.currency.clone()),
+                ("name", name.clone()),
+                ("comment", comment.clone().unwrap_or_default()),
+            ],
+            Notification::Milestone {
+                milestone, total, ..
+            } => vec![
+                ("milestone", milestone.value.clone()),
+                ("total", total.value.clone()),
+                ("currency", total.currency.clone()),
+            ],
+            Notification::StreamStarted { channel } | Notification::StreamEnded { channel } => {
+                vec![("channel", channel.clone())]
+            }
+            Notification::Raid { from, to, viewers } => vec![
+                ("from", from.clone()),
+                ("to", to.clone()),
+                ("viewers", viewers.to_string()),
+            ],
+        }
+    }
+
+    /// Fills `template` with the values of this notification, as a single text.
+    pub fn render(&self, template: &str) -> String {
+        let vars = self.vars();
+        let vars: Vec<(&str, &str)> = vars.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        template::render(&template::unsplit(template), &vars)
+    }
+
+    /// Fills `template` with the values of this notification, as messages that fit into chat.
+    /// The donor comment is shortened first.
+    pub fn render_chat(&self, template: &str) -> Result<Vec<String>, TooLong> {
+        let vars = self.vars();
+        let vars: Vec<(&str, &str)> = vars.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        template::render_chat(template, &vars, "comment", template::CHAT_LIMIT)
+    }
+
+    /// The template of the announcement for this notification.
+    pub fn announcement_template<'a>(&self, templates: &'a TemplatesConfig) -> &'a str {
+        match self {
+            Notification::Donation { .. } => &templates.donation_announcement,
+            Notification::Milestone { .. } => &templates.milestone_announcement,
+            Notification::StreamStarted { .. } => &templates.stream_started_announcement,
+            Notification::StreamEnded { .. } => &templates.stream_ended_announcement,
+            Notification::Raid { .. } => &templates.raid_announcement,
+        }
+    }
+
+    /// The human readable text used by chat-like sinks.
+    pub fn announcement(&self, templates: &TemplatesConfig) -> String {
+        self.render(self.announcement_template(templates))
+    }
+
+    /// The `donation_comment` message, for donations with a comment that passed moderation.
+    pub fn comment_message(&self, templates: &TemplatesConfig) -> Option<String> {
+        match self {
+            Notification::Donation {
+                comment: Some(_), ..
+            } => Some(self.render(&templates.donation_comment)),
+            _ => None,
+        }
+    }
+}
+
+/// The JSON document machine-readable sinks send for a notification.
+#[derive(Debug, Serialize)]
+pub struct Envelope<'a> {
+    pub sent_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub notification: &'a Notification,
+}
+
+impl<'a> Envelope<'a> {
+    pub fn new(notification: &'a Notification) -> Self {
+        Self {
+            sent_at: Utc::now(),
+            notification,
+        }
+    }
+}
+
+/// A destination for notifications.
+#[async_trait]
+pub trait NotificationSink: Send + Sync {
+    async fn notify(&self, notification: &Notification) -> Result<(), Report>;
+}
+
+/// Sends notifications to the sinks configured in `[notifications]`.
+///
+/// Sinks are built from the live config for every notification, so routing changes apply
+/// without a restart.
+#[derive(Clone)]
+pub struct Notifier {
+    pub config: SharedConfig,
+    pub http: reqwest::Client,
+    pub client: HelixClient<'static, reqwest::Client>,
+    pub token: Arc<Mutex<UserToken>>,
+    pub channels: Arc<Mutex<Channels>>,
+    /// Streamer tokens for AutoMod checks, read from `storage.streamers` as needed.
+    pub streamers: Arc<Mutex<Streamers>>,
+    pub store: Store,
+    /// Serialises appends to NDJSON files so lines never interleave.
+    pub file_lock: Arc<Mutex<()>>,
+}
+
+impl Notifier {
+    pub fn new(
+        config: SharedConfig,
+        client: HelixClient<'static, reqwest::Client>,
+        token: Arc<Mutex<UserToken>>,
+        channels: Arc<Mutex<Channels>>,
+        store: Store,
+    ) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+            client,
+            token,
+            channels,
+            streamers: Arc::new(Mutex::new(Streamers::default())),
+            store,
+            file_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// The Twitch chat sink, for announcements that aren't routed.
+    pub async fn twitch(&self) -> TwitchChatSink {
+        let config = self.config.read().await.clone();
+        self.twitch_sink(config)
+    }
+
+    fn twitch_sink(&self, config: Config) -> TwitchChatSink {
+        TwitchChatSink {
+            client: self.client.clone(),
+            token: self.token.clone(),
+            channels: self.channels.clone(),
+            streamers: self.streamers.clone(),
+            store: self.store.clone(),
+            config,
+        }
+    }
+
+    fn sink(&self, sink: &SinkConfig, config: &Config) -> Box<dyn NotificationSink> {
+        match &sink.kind {
+            SinkKind::TwitchChat => Box::new(self.twitch_sink(config.clone())),
+            SinkKind::Discord { url, username } => Box::new(DiscordSink {
+                http: self.http.clone(),
+                url: url.clone(),
+                username: username.clone(),
+                templates: config.templates.clone(),
+            }),
+            SinkKind::Webhook { url, secret } => Box::new(WebhookSink {
+                http: self.http.clone(),
+                url: url.clone(),
+                secret: secret.clone(),
+            }),
+            SinkKind::File { path } => Box::new(FileSink {
+                path: path.into(),
+                lock: self.file_lock.clone(),
+            }),
+        }
+    }
+
+    /// Sends a notification to every sink routed for its kind. Failures are logged per sink.
+    pub async fn dispatch(&self, notification: &Notification) {
+        let config = self.config.read().await.clone();
+        let kind = notification.kind();
+        let campaign = match notification {
+            Notification::Donation {
+                campaign_id,
+                team_event_id,
+                ..
+            } => metrics::donation_campaign_label(
+                &config,
+                campaign_id.as_deref(),
+                team_event_id.as_deref(),
+            ),
+            Notification::Milestone { campaign_id, .. } => {
+                metrics::donation_campaign_label(&config, campaign_id.as_deref(), None)
+            }
+            _ => metrics::NO_CAMPAIGN,
+        };
+        let deliveries = config
+            .notifications
+            .sinks
+            .iter()
+            .filter(|sink| sink.events.contains(&kind))
+            .map(|sink| {
+                let config = &config;
+                async move {
+                    let result = self.sink(sink, config).notify(notification).await;
+                    metrics::NOTIFICATIONS
+                        .with_label_values(&[
+                            campaign,
+                            sink.name.as_str(),
+                            kind.as_str(),
+                            metrics::result_label(&result),
+                        ])
+                        .inc();
+                    if let Err(e) = result {
+                        error!("Notification sink {} failed: {e:?}", sink.name);
+                    }
+                }
+            });
+        futures::future::join_all(deliveries).await;
+    }
+}
@@ -0,0 +1,66 @@
+//! A local HTTP server standing in for Discord, webhook receivers and Helix in tests.
+
+use axum::Router;
+use axum::body::Bytes;
+use axum::http::{HeaderMap, Method, StatusCode, Uri, header};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub struct Recorded {
+    pub method: Method,
+    pub path: String,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl Recorded {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|v| v.to_str().ok())
+    }
+
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).expect("request body is JSON")
+    }
+}
+
+pub struct StandIn {
+    base: String,
+    requests: Arc<Mutex<Vec<Recorded>>>,
+}
+
+impl StandIn {
+    /// Serves every request with `respond`, recording it for later assertions.
+    pub async fn start<F>(respond: F) -> Self
+    where
+        F: Fn(&Recorded) -> (StatusCode, String) + Clone + Send + Sync + 'static,
+    {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let app = Router::new().fallback(
+            move |method: Method, uri: Uri, headers: HeaderMap, body: Bytes| async move {
+                let request = Recorded {
+                    method,
+                    path: uri.path().to_string(),
+                    headers,
+                    body,
+                };
+                let (status, body) = respond(&request);
+                recorded.lock().await.push(request);
+                (status, [(header::CONTENT_TYPE, "application/json")], body)
+            },
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        Self { base, requests }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.base)
+    }
+
+    pub async fn requests(&self) -> Vec<Recorded> {
+        self.requests.lock().await.clone()
+    }
+}
@@ -0,0 +1,737 @@
+use crate::bot::auth::{Channel, Channels, Streamers};
+use crate::bot::{attribution, helix, moderation, template};
+use crate::config::{CampaignConfig, Config, OtherChannels, TemplatesConfig, TwitchConfig};
+use crate::db::models::NewDelivery;
+use crate::db::{DeliveryKind, Store};
+use crate::metrics;
+use crate::notify::{Notification, NotificationSink};
+use async_trait::async_trait;
+use eyre::{Report, bail};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Mutex;
+use tracing::{Instrument, error, info};
+use twitch_api::HelixClient;
+use twitch_api::helix::chat::{
+    SendChatAnnouncementBody, SendChatAnnouncementRequest, SendChatMessageBody,
+    SendChatMessageRequest,
+};
+use twitch_oauth2::UserToken;
+
+/// Posts to every participating channel that is live and moderated by the bot.
+///
+/// Donations get the plain `donation_message` followed by an announcement, everything else only
+/// an announcement. A donor comment follows as `donation_comment` in every channel whose AutoMod
+/// permits it. Each channel's settings decide what it gets and in which language; paused channels
+/// and channels in their quiet hours get nothing. A donation to a participant's supporting
+/// campaign is announced in their channel, while `team.others` decides what the rest post. Each
+/// attempt is recorded as a delivery.
+pub struct TwitchChatSink {
+    pub client: HelixClient<'static, reqwest::Client>,
+    pub token: Arc<Mutex<UserToken>>,
+    pub channels: Arc<Mutex<Channels>>,
+    pub streamers: Arc<Mutex<Streamers>>,
+    pub store: Store,
+    pub config: Config,
+}
+
+/// A donor comment and the chat messages it is posted as.
+struct Comment<'a> {
+    text: &'a str,
+    messages: Vec<String>,
+}
+
+/// What a single channel is sent for a notification.
+#[derive(Default)]
+struct Plan<'a> {
+    messages: Vec<String>,
+    announcements: Vec<String>,
+    comment: Option<Comment<'a>>,
+}
+
+#[async_trait]
+impl NotificationSink for TwitchChatSink {
+    async fn notify(&self, notification: &Notification) -> Result<(), Report> {
+        let donation_id = match notification {
+            Notification::Donation { id, .. } => Some(id.as_str()),
+            _ => None,
+        };
+        let home = self.home(notification).await;
+        self.send(donation_id, |channel| {
+            self.plan(notification, channel, home.as_deref())
+        })
+        .await
+    }
+}
+
+impl TwitchChatSink {
+    /// Sends an announcement that isn't tied to a donation.
+    pub async fn announce(&self, announcement: &str) -> Result<(), Report> {
+        template::check_length(announcement, template::CHAT_LIMIT)?;
+        self.send(None, |channel| {
+            Ok(channel.settings.announcements.then(|| Plan {
+                announcements: vec![announcement.to_string()],
+                ..Plan::default()
+            }))
+        })
+        .await
+    }
+
+    /// Sends an announcement about a donation, rendered with each channel's templates, to every
+    /// channel that would announce the donation itself.
+    pub async fn announce_for_donation(
+        &self,
+        donation: &Notification,
+        render: impl Fn(&TemplatesConfig) -> String,
+    ) -> Result<(), Report> {
+        let Notification::Donation { id, .. } = donation else {
+            bail!("not a donation");
+        };
+        let home = self.home(donation).await;
+        self.send(Some(id), |channel| {
+            let Some((campaign, posts)) = self.posts(donation, channel, home.as_deref()) else {
+                return Ok(None);
+            };
+            if !channel.settings.announcements || posts != OtherChannels::Announcement {
+                return Ok(None);
+            }
+            let announcement = render(&channel.settings.templates(&self.config, campaign));
+            template::check_length(&announcement, template::CHAT_LIMIT)?;
+            Ok(Some(Plan {
+                announcements: vec![announcement],
+                ..Plan::default()
+            }))
+        })
+        .await
+    }
+
+    /// Posts the comment on a donation that was announced without it, in every channel that
+    /// would post it with the donation.
+    pub async fn comment_on(&self, donation: &Notification) -> Result<(), Report> {
+        let Notification::Donation { id, .. } = donation else {
+            bail!("not a donation");
+        };
+        let home = self.home(donation).await;
+        self.send(Some(id), |channel| {
+            let plan = self.plan(donation, channel, home.as_deref())?;
+            Ok(plan.and_then(|plan| plan.comment).map(|comment| Plan {
+                comment: Some(comment),
+                ..Plan::default()
+            }))
+        })
+        .await
+    }
+
+    /// Sends an announcement to a single channel, unless its settings keep it quiet.
+    pub async fn announce_in(&self, channel: &Channel, announcement: &str) -> Result<(), Report> {
+        template::check_length(announcement, template::CHAT_LIMIT)?;
+        if !channel.settings.announcements || channel.settings.is_quiet(chrono::Utc::now()) {
+            info!("Not announcing in {}, its settings keep it quiet", channel.name);
+            return Ok(());
+        }
+        let token = self.token.lock().await.clone();
+        self.post(&token, channel, DeliveryKind::Announcement, &[announcement.to_string()], None)
+            .await
+    }
+
+    /// Sends the parts of a split announcement to a single channel in order, like
+    /// [`Self::announce_in`].
+    pub async fn announce_parts_in(
+        &self,
+        channel: &Channel,
+        parts: &[String],
+    ) -> Result<(), Report> {
+        for part in parts {
+            self.announce_in(channel, part).await?;
+        }
+        Ok(())
+    }
+
+    /// Sends the parts of a split chat message to a single channel in order, like [`Self::say`].
+    pub async fn say_parts(&self, channel: &Channel, parts: &[String]) -> Result<(), Report> {
+        for part in parts {
+            self.say(channel, part).await?;
+        }
+        Ok(())
+    }
+
+    /// Sends a plain chat message to a single channel, recorded as a delivery.
+    pub async fn say(&self, channel: &Channel, message: &str) -> Result<(), Report> {
+        template::check_length(message, template::CHAT_LIMIT)?;
+        let token = self.token.lock().await.clone();
+        let result =
+            Self::send_chat_message(self.client.clone(), &self.config.twitch, &token, channel, message)
+                .await;
+        self.record_delivery(None, channel, DeliveryKind::Message, &result)
+            .await;
+        result
+    }
+
+    /// The channel whose supporting campaign a donation went to.
+    async fn home(&self, notification: &Notification) -> Option<String> {
+        let Notification::Donation {
+            campaign_id,
+            team_event_id,
+            ..
+        } = notification
+        else {
+            return None;
+        };
+        let channels = self.channels.lock().await;
+        attribution::campaign_owner(
+            &channels.0,
+            &self.config.team,
+            campaign_id.as_deref(),
+            team_event_id.as_deref(),
+        )
+        .map(|c| c.user_id.to_string())
+    }
+
+    /// The campaign a channel posts a notification for and what it posts, or `None` when the
+    /// channel doesn't want it or doesn't post for its campaign. `home` is the channel whose
+    /// supporting campaign a donation went to.
+    fn posts(
+        &self,
+        notification: &Notification,
+        channel: &Channel,
+        home: Option<&str>,
+    ) -> Option<(&CampaignConfig, OtherChannels)> {
+        let campaign = match notification {
+            Notification::Donation { .. } | Notification::Milestone { .. } => {
+                let campaign = self.config.campaign(notification.campaign_key(&self.config));
+                if !campaign.includes(channel) {
+                    return None;
+                }
+                campaign
+            }
+            _ => self.config.channel_campaign(channel),
+        };
+        let posts = match home {
+            Some(home) if home != channel.user_id.as_str() => self.config.team.others,
+            _ => OtherChannels::Announcement,
+        };
+        if !channel.settings.wants(notification) || posts == OtherChannels::None {
+            return None;
+        }
+        Some((campaign, posts))
+    }
+
+    /// Renders a notification with the channel's templates, or `None` when the channel doesn't
+    /// want it or doesn't post for its campaign. Donor comments are posted only where
+    /// announcements are.
+    fn plan<'a>(
+        &self,
+        notification: &'a Notification,
+        channel: &Channel,
+        home: Option<&str>,
+    ) -> Result<Option<Plan<'a>>, Report> {
+        let settings = &channel.settings;
+        let Some((campaign, posts)) = self.posts(notification, channel, home) else {
+            return Ok(None);
+        };
+        let announces = settings.announcements && posts == OtherChannels::Announcement;
+        let templates = settings.templates(&self.config, campaign);
+        let mut plan = Plan::default();
+        if announces {
+            plan.announcements =
+                notification.render_chat(notification.announcement_template(&templates))?;
+        }
+        if let Notification::Donation { comment, .. } = notification {
+            if settings.messages {
+                plan.messages = notification.render_chat(&templates.donation_message)?;
+            }
+            if let Some(text) = comment
+                && announces
+            {
+                plan.comment = Some(Comment {
+                    text,
+                    messages: notification.render_chat(&templates.donation_comment)?,
+                });
+            }
+        }
+        Ok(Some(plan))
+    }
+
+    async fn send<'a>(
+        &self,
+        donation_id: Option<&str>,
+        plan: impl Fn(&Channel) -> Result<Option<Plan<'a>>, Report>,
+    ) -> Result<(), Report> {
+        let token = self.token.lock().await.clone();
+        let moderated_live_channels = self
+            .channels
+            .lock()
+            .await
+            .clone()
+            .get_moderated_live_channels(&self.client, &self.config.twitch, &token)
+            .await;
+        info!("Live channels: {:?}", moderated_live_channels);
+
+        let now = chrono::Utc::now();
+        let mut failed = 0;
+        for live_channel in &moderated_live_channels {
+            if live_channel.settings.is_quiet(now) {
+                info!("Not posting in {}, it is paused or in its quiet hours", live_channel.name);
+                continue;
+            }
+            // Everything for a channel is rendered before anything is sent to it, so a message
+            // that can't fit fails without leaving half a notification behind.
+            let plan = match plan(live_channel) {
+                Ok(Some(plan)) => plan,
+                Ok(None) => continue,
+                Err(e) => {
+                    error!("Error rendering for {}: {e}", live_channel.name);
+                    self.record_delivery(donation_id, live_channel, DeliveryKind::Announcement, &Err(e))
+                        .await;
+                    failed += 1;
+                    continue;
+                }
+            };
+            failed += self
+                .post(&token, live_channel, DeliveryKind::Message, &plan.messages, donation_id)
+                .await
+                .is_err() as usize;
+            failed += self
+                .post(&token, live_channel, DeliveryKind::Announcement, &plan.announcements, donation_id)
+                .await
+                .is_err() as usize;
+            if let Some(comment) = &plan.comment {
+                failed += self
+                    .send_comment(&token, live_channel, comment, donation_id)
+                    .await
+                    .is_err() as usize;
+            }
+        }
+        info!(
+            "Sent to {} channels. Channels were: {:?}",
+            moderated_live_channels.len(),
+            moderated_live_channels
+        );
+        if failed > 0 {
+            bail!("{failed} chat deliveries failed");
+        }
+        Ok(())
+    }
+
+    /// Sends the parts of a split message in order, stopping at the first one that fails so
+    /// the channel never sees a later part without the earlier ones.
+    async fn post(
+        &self,
+        token: &UserToken,
+        channel: &Channel,
+        kind: DeliveryKind,
+        texts: &[String],
+        donation_id: Option<&str>,
+    ) -> Result<(), Report> {
+        for text in texts {
+            let result = match kind {
+                DeliveryKind::Announcement => {
+                    Self::send_chat_announcement(self.client.clone(), &self.config.twitch, token, channel, text).await
+                }
+                DeliveryKind::Message | DeliveryKind::Comment => {
+                    Self::send_chat_message(self.client.clone(), &self.config.twitch, token, channel, text).await
+                }
+            };
+            self.record_delivery(donation_id, channel, kind, &result)
+                .await;
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Posts a donor comment if the channel's AutoMod permits it, otherwise records it as
+    /// dropped. Only failures to post count as errors.
+    async fn send_comment(
+        &self,
+        token: &UserToken,
+        channel: &Channel,
+        comment: &Comment<'_>,
+        donation_id: Option<&str>,
+    ) -> Result<(), Report> {
+        let verdict = if self.config.moderation.automod {
+            let mut streamers = self.streamers.lock().await;
+            moderation::check_automod(
+                &self.client,
+                &self.config.twitch,
+                &mut streamers,
+                &self.config.storage.streamers,
+                channel,
+                comment.text,
+            )
+            .await
+        } else {
+            Ok(())
+        };
+        if let Err(reason) = verdict {
+            info!("Not posting comment in {}: {reason}", channel.name);
+            let delivery = NewDelivery {
+                donation_id,
+                channel_id: channel.user_id.as_str(),
+                channel_name: channel.name.as_str(),
+                kind: DeliveryKind::Comment.as_str(),
+                status: "dropped",
+                error: Some(reason),
+                attempted_at: chrono::Utc::now().naive_utc(),
+            };
+            if let Err(e) = self.store.record_delivery(delivery).await {
+                error!("Error recording delivery to {}: {e:?}", channel.name);
+            }
+            return Ok(());
+        }
+        self.post(token, channel, DeliveryKind::Comment, &comment.messages, donation_id)
+            .await
+    }
+
+    #[tracing::instrument(skip(client, twitch))]
+    async fn send_chat_announcement(
+        client: HelixClient<'static, reqwest::Client>,
+        twitch: &TwitchConfig,
+        token: &UserToken,
+        channel: &Channel,
+        message: &str,
+    ) -> Result<(), Report> {
+        info!("Sending announcement sent to channel: {}", channel.name);
+        let req = SendChatAnnouncementRequest::new(&channel.user_id, &token.user_id);
+        let body = SendChatAnnouncementBody::new(message, channel.settings.color.clone())?;
+        let started = Instant::now();
+        let res = helix::post(&client, twitch, req, body.clone(), token)
+            .in_current_span()
+            .await;
+        metrics::observe_helix("send_chat_announcement", started, &res);
+        metrics::CHAT_MESSAGES
+            .with_label_values(&[channel.name.as_str(), "announcement", metrics::result_label(&res)])
+            .inc();
+        match res {
+            Ok(r) => {
+                info!("SendChatAnnouncement returned: {:?}", r);
+                info!("Announcement sent to channel: {} {r:?}", channel.name);
+                Ok(())
+            }
+            Err(e) => {
+                error!("Error sending announcement: {e:?}");
+                Err(e.into())
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(client, twitch))]
+    async fn send_chat_message(
+        client: HelixClient<'static, reqwest::Client>,
+        twitch: &TwitchConfig,
+        token: &UserToken,
+        channel: &Channel,
+        message: &str,
+    ) -> Result<(), Report> {
+        info!("Sending message to channel: {}", channel.name);
+        let req = SendChatMessageRequest::new();
+        let body = SendChatMessageBody::new(&channel.user_id, &token.user_id, message);
+        let started = Instant::now();
+        let res = helix::post(&client, twitch, req, body.clone(), token)
+            .in_current_span()
+            .await;
+        metrics::observe_helix("send_chat_message", started, &res);
+        metrics::CHAT_MESSAGES
+            .with_label_values(&[channel.name.as_str(), "message", metrics::result_label(&res)])
+            .inc();
+        match res {
+            Ok(r) => {
+                info!("SendChatAnnouncement returned: {:?}", r);
+                info!("Message sent to channel: {} {r:?}", channel.name);
+                Ok(())
+            }
+            Err(e) => {
+                error!("Error sending message: {e:?}");
+                Err(e.into())
+            }
+        }
+    }
+
+    async fn record_delivery(
+        &self,
+        donation_id: Option<&str>,
+        channel: &Channel,
+        kind: DeliveryKind,
+        result: &Result<(), Report>,
+    ) {
+        let delivery = NewDelivery {
+            donation_id,
+            channel_id: channel.user_id.as_str(),
+            channel_name: channel.name.as_str(),
+            kind: kind.as_str(),
+            status: if result.is_ok() { "sent" } else { "failed" },
+            error: result.as_ref().err().map(|e| e.to_string()),
+            attempted_at: chrono::Utc::now().naive_utc(),
+        };
+        if let Err(e) = self.store.record_delivery(delivery).await {
+            error!("Error recording delivery to {}: {e:?}", channel.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::auth::User;
+    use crate::config::{CampaignConfig, DonorsConfig, TemplateOverrides};
+    use crate::notify::stand_in::{Recorded, StandIn};
+    use crate::routes::tiltify::TiltifyDonation;
+    use crate::routes::webhook::Amount;
+    use axum::http::{Method, StatusCode};
+    use serde_json::json;
+
+    fn helix(request: &Recorded) -> (StatusCode, String) {
+        let body = match (&request.method, request.path.as_str()) {
+            (&Method::GET, "/helix/streams") => json!({
+                "data": [{
+                    "id": "1", "user_id": "1", "user_login": "alice", "user_name": "Alice",
+                    "game_id": "1", "game_name": "Game", "type": "live", "title": "Charity",
+                    "tags": [], "viewer_count": 10, "started_at": "2026-10-18T12:00:00Z",
+                    "language": "en", "thumbnail_url": "", "is_mature": false
+                }],
+                "pagination": {}
+            }),
+            (&Method::GET, "/helix/moderation/channels") => json!({
+                "data": [
+                    { "broadcaster_id": "1", "broadcaster_login": "alice", "broadcaster_name": "Alice" },
+                    { "broadcaster_id": "2", "broadcaster_login": "bob", "broadcaster_name": "Bob" }
+                ],
+                "pagination": {}
+            }),
+            (&Method::POST, "/helix/chat/messages") => {
+                json!({ "data": [{ "message_id": "abc", "is_sent": true }] })
+            }
+            (&Method::POST, "/helix/chat/announcements") => {
+                return (StatusCode::NO_CONTENT, String::new());
+            }
+            (&Method::POST, "/helix/moderation/enforcements/status") => {
+                let text = request.json()["data"][0]["msg_text"].as_str().unwrap().to_string();
+                json!({ "data": [{ "msg_id": "comment", "is_permitted": !text.contains("boo") }] })
+            }
+            _ => return (StatusCode::NOT_FOUND, String::new()),
+        };
+        (StatusCode::OK, body.to_string())
+    }
+
+    #[tokio::test]
+    async fn sends_message_and_announcement_to_moderated_live_channels() {
+        let stand_in = StandIn::start(helix).await;
+        let mut config = Config::default();
+        config.twitch.helix_url = stand_in.url("/helix/");
+        config.moderation.automod = true;
+        config.storage.streamers = "/nonexistent/streamers.json".to_string();
+
+        let channels: Channels = serde_json::from_value(json!([
+            { "user_id": "1", "name": "alice", "settings": { "color": "purple" } },
+            { "user_id": "2", "name": "bob" }
+        ]))
+        .unwrap();
+        let token = |login: &str, id: &str| {
+            UserToken::from_existing_unchecked(
+                "access",
+                None,
+                "client",
+                None,
+                login.into(),
+                id.into(),
+                None,
+                None,
+            )
+        };
+        let alice = User::from(token("alice", "1"));
+        let store = Store::open(":memory:").unwrap();
+        let mut donation = TiltifyDonation {
+            name: Some("Carol".to_string()),
+            ..TiltifyDonation::test("donation", "5.00")
+        };
+        store.insert_donation(&donation).await.unwrap();
+        let sink = TwitchChatSink {
+            client: HelixClient::default(),
+            token: Arc::new(Mutex::new(token("warbot", "99"))),
+            channels: Arc::new(Mutex::new(channels)),
+            streamers: Arc::new(Mutex::new(Streamers(vec![alice]))),
+            store: store.clone(),
+            config,
+        };
+
+        sink.notify(&Notification::donation(&donation, &DonorsConfig::default(), Some("Good luck!".to_string())))
+            .await
+            .unwrap();
+
+        let requests = stand_in.requests().await;
+        let messages: Vec<_> = requests
+            .iter()
+            .filter(|r| r.path == "/helix/chat/messages")
+            .collect();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].json()["broadcaster_id"], "1");
+        assert_eq!(messages[0].json()["message"], "!donation_received 5.00");
+        assert_eq!(messages[1].json()["message"], "Carol says: Good luck!");
+        let announcements: Vec<_> = requests
+            .iter()
+            .filter(|r| r.path == "/helix/chat/announcements")
+            .collect();
+        assert_eq!(announcements.len(), 1);
+        assert_eq!(
+            announcements[0].json()["message"],
+            "A donation of $5.00 has been made by Carol!"
+        );
+        assert_eq!(announcements[0].json()["color"], "purple");
+
+        let stored = store.recent_donations(1, None).await.unwrap();
+        let deliveries = &stored[0].deliveries;
+        assert_eq!(deliveries.len(), 3);
+        assert!(deliveries.iter().all(|d| d.status == "sent" && d.channel_name == "alice"));
+
+        // A comment AutoMod objects to is recorded but never posted.
+        donation.id = "booed".to_string();
+        store.insert_donation(&donation).await.unwrap();
+        sink.notify(&Notification::donation(&donation, &DonorsConfig::default(), Some("boo!".to_string())))
+            .await
+            .unwrap();
+
+        let requests = stand_in.requests().await;
+        assert!(
+            !requests
+                .iter()
+                .filter(|r| r.path == "/helix/chat/messages")
+                .any(|r| r.json()["message"] == "Carol says: boo!")
+        );
+        let stored = store.recent_donations(1, None).await.unwrap();
+        let comment = stored[0]
+            .deliveries
+            .iter()
+            .find(|d| d.kind == "comment")
+            .unwrap();
+        assert_eq!(comment.status, "dropped");
+        assert_eq!(comment.error.as_deref(), Some("rejected by AutoMod"));
+
+        // Without the streamer's token AutoMod can't be asked, and the comment is posted.
+        sink.streamers.lock().await.0.clear();
+        donation.id = "unchecked".to_string();
+        store.insert_donation(&donation).await.unwrap();
+        sink.notify(&Notification::donation(&donation, &DonorsConfig::default(), Some("boo!".to_string())))
+            .await
+            .unwrap();
+
+        let requests = stand_in.requests().await;
+        assert!(
+            requests
+                .iter()
+                .filter(|r| r.path == "/helix/chat/messages")
+                .any(|r| r.json()["message"] == "Carol says: boo!")
+        );
+    }
+
+    fn planner(channels: &Channels, config: Config) -> TwitchChatSink {
+        TwitchChatSink {
+            client: HelixClient::default(),
+            token: Arc::new(Mutex::new(UserToken::from_existing_unchecked(
+                "access",
+                None,
+                "client",
+                None,
+                "warbot".into(),
+                "99".into(),
+                None,
+                None,
+            ))),
+            channels: Arc::new(Mutex::new(channels.clone())),
+            streamers: Arc::new(Mutex::new(Streamers(Vec::new()))),
+            store: Store::open(":memory:").unwrap(),
+            config,
+        }
+    }
+
+    fn donation_to(campaign_id: &str) -> Notification {
+        Notification::Donation {
+            id: "donation".to_string(),
+            campaign_id: Some(campaign_id.to_string()),
+            team_event_id: None,
+            amount: Amount {
+                currency: "USD".to_string(),
+                value: "5.00".to_string(),
+            },
+            name: "Carol".to_string(),
+            comment: Some("Go Bob!".to_string()),
+        }
+    }
+
+    #[test]
+    fn announces_donations_to_a_supporting_campaign_in_its_channel() {
+        let channels: Channels = serde_json::from_value(json!([
+            { "user_id": "1", "name": "alice" },
+            { "user_id": "2", "name": "bob", "settings": { "campaign_id": "bob-campaign" } }
+        ]))
+        .unwrap();
+        let mut config = Config::default();
+        config.team.others = OtherChannels::Message;
+        let mut sink = planner(&channels, config);
+        let notification = donation_to("bob-campaign");
+        let home = Some("2");
+        let [alice, bob] = [&channels.0[0], &channels.0[1]];
+
+        let plan = sink.plan(&notification, bob, home).unwrap().unwrap();
+        assert_eq!(plan.announcements.len(), 1);
+        assert!(plan.comment.is_some());
+        let plan = sink.plan(&notification, alice, home).unwrap().unwrap();
+        assert!(plan.announcements.is_empty() && plan.comment.is_none());
+        assert_eq!(plan.messages, ["!donation_received 5.00"]);
+
+        sink.config.team.others = OtherChannels::None;
+        assert!(sink.plan(&notification, alice, home).unwrap().is_none());
+        let plan = sink.plan(&notification, alice, None).unwrap().unwrap();
+        assert_eq!(plan.announcements.len(), 1);
+    }
+
+    #[test]
+    fn posts_donations_only_in_channels_of_their_campaign() {
+        let channels: Channels = serde_json::from_value(json!([
+            { "user_id": "1", "name": "alice" },
+            { "user_id": "2", "name": "bob" }
+        ]))
+        .unwrap();
+        let mut config = Config::default();
+        config.campaigns.insert(
+            "winter".to_string(),
+            CampaignConfig {
+                channels: vec!["bob".to_string()],
+                templates: TemplateOverrides {
+                    donation_message: Some("!winter {amount}".to_string()),
+                    ..TemplateOverrides::default()
+                },
+                ..CampaignConfig::default()
+            },
+        );
+        let sink = planner(&channels, config);
+        let [alice, bob] = [&channels.0[0], &channels.0[1]];
+
+        let winter = donation_to("winter");
+        assert!(sink.plan(&winter, alice, None).unwrap().is_none());
+        let plan = sink.plan(&winter, bob, None).unwrap().unwrap();
+        assert_eq!(plan.messages, ["!winter 5.00"]);
+        let other = donation_to("summer");
+        assert!(sink.plan(&other, alice, None).unwrap().is_some());
+        assert!(sink.plan(&other, bob, None).unwrap().is_some());
+    }
+
+    #[test]
+    fn follows_channel_settings_and_team_rules_for_donation_announcements() {
+        let channels: Channels = serde_json::from_value(json!([
+            { "user_id": "1", "name": "alice", "settings": { "min_amount": 10.0 } },
+            { "user_id": "2", "name": "bob", "settings": { "campaign_id": "bob-campaign" } },
+            { "user_id": "3", "name": "carol" }
+        ]))
+        .unwrap();
+        let mut config = Config::default();
+        config.team.others = OtherChannels::Message;
+        let sink = planner(&channels, config);
+        let notification = donation_to("bob-campaign");
+        let [alice, bob, carol] = [&channels.0[0], &channels.0[1], &channels.0[2]];
+
+        assert!(sink.posts(&notification, alice, Some("2")).is_none());
+        let (_, posts) = sink.posts(&notification, bob, Some("2")).unwrap();
+        assert_eq!(posts, OtherChannels::Announcement);
+        let (_, posts) = sink.posts(&notification, carol, Some("2")).unwrap();
+        assert_eq!(posts, OtherChannels::Message);
+    }
+}
@@ -0,0 +1,111 @@
+use crate::notify::{Envelope, Notification, NotificationSink};
+use async_trait::async_trait;
+use eyre::{Report, WrapErr};
+use hmac::{Hmac, Mac};
+use reqwest::header::CONTENT_TYPE;
+use sha2::Sha256;
+
+/// Header carrying `sha256=<hex HMAC of the body>`.
+pub const SIGNATURE_HEADER: &str = "X-Warbot-Signature-256";
+/// Header carrying the notification's event name.
+pub const EVENT_HEADER: &str = "X-Warbot-Event";
+
+/// Posts notifications as JSON, signed with a shared secret so receivers can verify them.
+pub struct WebhookSink {
+    pub http: reqwest::Client,
+    pub url: String,
+    pub secret: String,
+}
+
+/// The value of [`SIGNATURE_HEADER`] for `body`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[async_trait]
+impl NotificationSink for WebhookSink {
+    async fn notify(&self, notification: &Notification) -> Result<(), Report> {
+        let body = serde_json::to_vec(&Envelope::new(notification))?;
+        self.http
+            .post(&self.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, sign(&self.secret, &body))
+            .header(EVENT_HEADER, notification.kind().as_str())
+            .body(body)
+            .send()
+            .await
+            .wrap_err_with(|| format!("couldn't reach webhook {}", self.url))?
+            .error_for_status()
+            .wrap_err("webhook rejected the notification")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::stand_in::StandIn;
+    use crate::routes::webhook::Amount;
+    use axum::http::StatusCode;
+
+    fn sink(url: String) -> WebhookSink {
+        WebhookSink {
+            http: reqwest::Client::new(),
+            url,
+            secret: "hunter2".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn posts_signed_envelope() {
+        let stand_in = StandIn::start(|_| (StatusCode::OK, String::new())).await;
+        let notification = Notification::Milestone {
+            campaign_id: Some("campaign".to_string()),
+            milestone: Amount {
+                currency: "USD".to_string(),
+                value: "1000.00".to_string(),
+            },
+            total: Amount {
+                currency: "USD".to_string(),
+                value: "1012.50".to_string(),
+            },
+        };
+
+        sink(stand_in.url("/hook")).notify(&notification).await.unwrap();
+
+        let requests = stand_in.requests().await;
+        assert_eq!(requests.len(), 1);
+        let request = &requests[0];
+        assert_eq!(
+            request.header(SIGNATURE_HEADER),
+            Some(sign("hunter2", &request.body).as_str())
+        );
+        assert_eq!(request.header(EVENT_HEADER), Some("milestone"));
+        let json = request.json();
+        assert_eq!(json["event"], "milestone");
+        assert_eq!(json["total"]["value"], "1012.50");
+        assert!(json["sent_at"].is_string());
+    }
+
+    #[tokio::test]
+    async fn fails_on_error_status() {
+        let stand_in = StandIn::start(|_| (StatusCode::INTERNAL_SERVER_ERROR, String::new())).await;
+        let notification = Notification::StreamEnded {
+            channel: "alice".to_string(),
+        };
+
+        assert!(sink(stand_in.url("/hook")).notify(&notification).await.is_err());
+    }
+
+    #[test]
+    fn signature_matches_known_value() {
+        // echo -n '{}' | openssl dgst -sha256 -hmac hunter2
+        assert_eq!(
+            sign("hunter2", b"{}"),
+            "sha256=603176255680307a81ec5b984e3a7b4143d0aef1fd1576987618e55c50868ad7"
+        );
+    }
+}
@@ -0,0 +1,270 @@
+use crate::Commands;
+use crate::bot::auth::Channels;
+use crate::config::{Config, ConfigArgs, SharedConfig};
+use eyre::{Report, bail};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::Mutex;
+use tokio::sync::broadcast::Sender;
+use tracing::{error, info, warn};
+
+/// How often the watched files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Config keys whose values are left out of logged diffs.
+const SECRET_KEYS: &[&str] = &["secret", "api_key", "dsn", "token", "url"];
+
+/// A file that is reloaded when its modification time changes.
+struct Watched {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    contents: String,
+}
+
+impl Watched {
+    fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        Self {
+            modified: modified(&path),
+            contents: std::fs::read_to_string(&path).unwrap_or_default(),
+            path,
+        }
+    }
+
+    /// Returns the new contents when the file changed since the last accepted version.
+    fn changed(&mut self, forced: bool) -> Option<String> {
+        let modified = modified(&self.path);
+        if !forced && modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        let contents = std::fs::read_to_string(&self.path).ok()?;
+        (contents != self.contents).then_some(contents)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Watches `config.toml` and the channels file, and reloads them when they change or the
+/// process receives SIGHUP.
+pub async fn watch(
+    args: ConfigArgs,
+    config: SharedConfig,
+    channels: Arc<Mutex<Channels>>,
+    tx: Sender<Commands>,
+) -> Result<(), Report> {
+    let mut hangup = signal(SignalKind::hangup())?;
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    let mut config_file = Watched::new(args.config_path());
+    let mut channels_file = Watched::new(&config.read().await.storage.channels);
+
+    loop {
+        let forced = tokio::select! {
+            _ = interval.tick() => false,
+            _ = hangup.recv() => {
+                info!("Received SIGHUP, reloading config and channels");
+                true
+            }
+        };
+
+        if let Some(contents) = config_file.changed(forced) {
+            let diff = diff(&config_file.contents, &contents);
+            match reload_config(&args, &config).await {
+                Ok(()) => {
+                    info!("Reloaded {}:\n{diff}", config_file.path.display());
+                    config_file.contents = contents;
+                    // The channels file may have moved along with the config.
+                    let path = config.read().await.storage.channels.clone();
+                    if Path::new(&path) != channels_file.path {
+                        channels_file = Watched {
+                            path: path.into(),
+                            modified: None,
+                            contents: String::new(),
+                        };
+                    }
+                }
+                Err(e) => error!(
+                    "Rejected reload of {}: {e}\n{diff}",
+                    config_file.path.display()
+                ),
+            }
+        }
+
+        if let Some(contents) = channels_file.changed(forced) {
+            let diff = channels_diff(&channels_file.contents, &contents);
+            match reload_channels(&contents, &channels, &tx).await {
+                Ok(()) => {
+                    info!("Reloaded {}:\n{diff}", channels_file.path.display());
+                    channels_file.contents = contents;
+                }
+                Err(e) => error!(
+                    "Rejected reload of {}: {e}\n{diff}",
+                    channels_file.path.display()
+                ),
+            }
+        }
+    }
+}
+
+async fn reload_config(args: &ConfigArgs, config: &SharedConfig) -> Result<(), Report> {
+    let new = Config::load_layered(args)?;
+    let mut current = config.write().await;
+    if new.server.to_socket_addrs() != current.server.to_socket_addrs() {
+        warn!("server address changed, this only takes effect after a restart");
+    }
+    if new.storage.database != current.storage.database {
+        warn!("storage.database changed, this only takes effect after a restart");
+    }
+    if new.sentry != current.sentry || new.otlp != current.otlp {
+        warn!("sentry and otlp settings only take effect after a restart");
+    }
+    *current = new;
+    Ok(())
+}
+
+async fn reload_channels(
+    contents: &str,
+    channels: &Arc<Mutex<Channels>>,
+    tx: &Sender<Commands>,
+) -> Result<(), Report> {
+    let new: Channels = serde_json::from_str(contents)?;
+    let mut ids = HashSet::new();
+    for channel in &new.0 {
+        if channel.user_id.as_str().is_empty() || channel.name.as_str().is_empty() {
+            bail!("channel entries need both a user_id and a name");
+        }
+        if !ids.insert(channel.user_id.clone()) {
+            bail!("channel {} ({}) is listed twice", channel.name, channel.user_id);
+        }
+        let problems = channel.settings.problems(channel.name.as_str());
+        if !problems.is_empty() {
+            bail!("invalid channel settings: {}", problems.join("; "));
+        }
+    }
+
+    let mut current = channels.lock().await;
+    let added: Vec<_> = new
+        .0
+        .iter()
+        .filter(|c| current.find(c.user_id.as_str()).is_none())
+        .map(|c| c.name.to_string())
+        .collect();
+    let removed: Vec<_> = current
+        .0
+        .iter()
+        .filter(|c| new.find(c.user_id.as_str()).is_none())
+        .map(|c| c.name.to_string())
+        .collect();
+    *current = new;
+    drop(current);
+
+    if !added.is_empty() || !removed.is_empty() {
+        info!("Channels added: {:?}, removed: {:?}", added, removed);
+        let _ = tx.send(Commands::ChannelsChanged);
+    }
+    Ok(())
+}
+
+/// A line based diff between two versions of a file, with secrets masked.
+fn diff(old: &str, new: &str) -> String {
+    let old_lines: HashSet<&str> = old.lines().collect();
+    let new_lines: HashSet<&str> = new.lines().collect();
+    let removed = old
+        .lines()
+        .filter(|l| !new_lines.contains(l))
+        .map(|l| format!("- {}", mask(l)));
+    let added = new
+        .lines()
+        .filter(|l| !old_lines.contains(l))
+        .map(|l| format!("+ {}", mask(l)));
+    let lines: Vec<String> = removed.chain(added).collect();
+    if lines.is_empty() {
+        "(no changes)".to_string()
+    } else {
+        lines.join("\n")
+    }
+}
+
+/// Diffs channel files entry by entry, falling back to lines when one doesn't parse.
+fn channels_diff(old: &str, new: &str) -> String {
+    let entries = |contents: &str| -> Option<String> {
+        let channels: Channels = serde_json::from_str(contents).ok()?;
+        let lines: Vec<String> = channels
+            .0
+            .iter()
+            .filter_map(|c| serde_json::to_string(c).ok())
+            .collect();
+        Some(lines.join("\n"))
+    };
+    match (entries(old), entries(new)) {
+        (Some(old), Some(new)) => diff(&old, &new),
+        _ => diff(old, new),
+    }
+}
+
+fn mask(line: &str) -> String {
+    match line.split_once('=') {
+        Some((key, _)) if SECRET_KEYS.iter().any(|s| key.to_lowercase().contains(s)) => {
+            format!("{key}= <redacted>")
+        }
+        _ => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::broadcast;
+
+    fn channels(json: &str) -> Arc<Mutex<Channels>> {
+        Arc::new(Mutex::new(serde_json::from_str(json).unwrap()))
+    }
+
+    #[tokio::test]
+    async fn keeps_the_old_channels_when_the_new_ones_are_invalid() {
+        let current = channels(r#"[{ "user_id": "1", "name": "alice" }]"#);
+        let (tx, mut rx) = broadcast::channel(4);
+
+        for invalid in [
+            "not json",
+            r#"[{ "user_id": "", "name": "bob" }]"#,
+            r#"[{ "user_id": "2", "name": "bob" }, { "user_id": "2", "name": "bobby" }]"#,
+            r#"[{ "user_id": "2", "name": "bob", "settings": { "home_only": true } }]"#,
+        ] {
+            assert!(reload_channels(invalid, &current, &tx).await.is_err(), "{invalid}");
+        }
+        let names: Vec<_> = current.lock().await.0.iter().map(|c| c.name.to_string()).collect();
+        assert_eq!(names, ["alice"]);
+        assert!(rx.try_recv().is_err());
+
+        let valid = r#"[{ "user_id": "1", "name": "alice" }, { "user_id": "2", "name": "bob" }]"#;
+        reload_channels(valid, &current, &tx).await.unwrap();
+        assert_eq!(current.lock().await.0.len(), 2);
+        assert!(matches!(rx.try_recv(), Ok(Commands::ChannelsChanged)));
+    }
+
+    #[test]
+    fn masks_secrets_in_logged_diffs() {
+        let old = "[twitch]\nclient_id=\"id\"\nclient_secret=\"old\"\n[admin]\napi_key=\"old\"";
+        let new = "[twitch]\nclient_id=\"id\"\nclient_secret=\"new\"\n[admin]\napi_key=\"new\"";
+
+        let diff = diff(old, new);
+
+        assert_eq!(
+            diff,
+            "- client_secret= <redacted>\n- api_key= <redacted>\n\
+             + client_secret= <redacted>\n+ api_key= <redacted>"
+        );
+        assert!(!diff.contains("old") && !diff.contains("new"));
+        assert_eq!(
+            mask("sentry_dsn = \"https://key@sentry.example/1\""),
+            "sentry_dsn = <redacted>"
+        );
+        assert_eq!(mask("port=28257"), "port=28257");
+    }
+}
@@ -0,0 +1,790 @@
+use crate::bot::auth::{Channel, Channels};
+use crate::bot::holds::{self, HoldError};
+use crate::bot::rewards::{self, RewardError};
+use crate::bot::raids::{self, RaidTrain};
+use crate::bot::settings::ChannelSettings;
+use crate::bot::template::{self, TooLong};
+use crate::config::{CampaignConfig, Config};
+use crate::db::Store;
+use crate::db::models::{
+    ChannelRaised, Donation, DonationWithDeliveries, Hold, HoldWithDonation, RewardClaim,
+    RewardClaimWithDonation, WebhookDelivery, WebhookDeliveryWithAttempts,
+};
+use crate::routes::tiltify::TiltifyDonation;
+use crate::routes::webhook::Amount;
+use crate::{Commands, SharedAppState};
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post, put};
+use axum::{Json, Router};
+use axum_extra::extract::WithRejection;
+use eyre::Report;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::atomic::{AtomicU64, Ordering};
+use thiserror::Error;
+use tracing::{error, info};
+
+pub fn router(state: SharedAppState) -> Router<SharedAppState> {
+    Router::new()
+        .route("/channels", get(list_channels).post(add_channel))
+        .route("/channels/{channel}", delete(remove_channel))
+        .route("/channels/{channel}/settings", put(update_channel_settings))
+        .route("/status", get(status))
+        .route("/pause", post(pause))
+        .route("/resume", post(resume))
+        .route("/campaigns", get(list_campaigns))
+        .route("/campaigns/{campaign}", get(campaign))
+        .route("/donations", get(recent_donations))
+        .route("/announcements", post(announce))
+        .route("/simulate/donation", post(simulate_donation))
+        .route("/webhooks/deliveries", get(webhook_deliveries))
+        .route("/webhooks/deliveries/{id}/resend", post(resend_webhook_delivery))
+        .route("/raids", get(raid_trains))
+        .route("/raised", get(raised_by_channel))
+        .route("/holds", get(list_holds))
+        .route("/holds/{id}/approve", post(approve_hold))
+        .route("/holds/{id}/reject", post(reject_hold))
+        .route("/rewards", get(list_rewards))
+        .route("/rewards/{id}/done", post(complete_reward))
+        .route_layer(middleware::from_fn_with_state(state, require_api_key))
+}
+
+async fn require_api_key(
+    State(state): State<SharedAppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AdminError> {
+    let config = state.lock().await.config.clone();
+    let api_key = config.read().await.admin.api_key.clone();
+    let Some(api_key) = api_key.filter(|k| !k.is_empty()) else {
+        return Err(AdminError::Disabled);
+    };
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match provided {
+        Some(provided) if constant_time_eq(provided.as_bytes(), api_key.as_bytes()) => {
+            Ok(next.run(request).await)
+        }
+        _ => Err(AdminError::Unauthorized),
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn list_channels(State(state): State<SharedAppState>) -> Json<Channels> {
+    let channels = state.lock().await.channels.clone();
+    Json(channels.lock().await.clone())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddChannel {
+    pub name: String,
+}
+
+async fn add_channel(
+    State(state): State<SharedAppState>,
+    WithRejection(Json(body), _): WithRejection<Json<AddChannel>, AdminError>,
+) -> Result<(StatusCode, Json<Channel>), AdminError> {
+    let (client, token, channels, config, tx) = {
+        let state = state.lock().await;
+        (
+            state.client.clone(),
+            state.token.clone(),
+            state.channels.clone(),
+            state.config.clone(),
+            state.tx.clone(),
+        )
+    };
+    let token = token.lock().await.clone();
+    let twitch = config.read().await.twitch.clone();
+    let channel = Channel::resolve(&client, &twitch, &token, &body.name)
+        .await?
+        .ok_or_else(|| AdminError::UserNotFound(body.name.clone()))?;
+
+    let mut channels = channels.lock().await;
+    if !channels.add(channel.clone()) {
+        return Err(AdminError::ChannelExists(channel.name.to_string()));
+    }
+    channels.save(&config.read().await.storage.channels)?;
+    let _ = tx.send(Commands::ChannelsChanged);
+    info!("Added channel {} ({})", channel.name, channel.user_id);
+    Ok((StatusCode::CREATED, Json(channel)))
+}
+
+async fn remove_channel(
+    State(state): State<SharedAppState>,
+    Path(id_or_name): Path<String>,
+) -> Result<Json<Channel>, AdminError> {
+    let (channels, config, tx) = {
+        let state = state.lock().await;
+        (state.channels.clone(), state.config.clone(), state.tx.clone())
+    };
+    let mut channels = channels.lock().await;
+    let channel = channels
+        .remove(&id_or_name)
+        .ok_or_else(|| AdminError::ChannelNotFound(id_or_name.clone()))?;
+    channels.save(&config.read().await.storage.channels)?;
+    let _ = tx.send(Commands::ChannelsChanged);
+    info!("Removed channel {} ({})", channel.name, channel.user_id);
+    Ok(Json(channel))
+}
+
+/// Replaces a channel's settings. Fields left out go back to their defaults.
+async fn update_channel_settings(
+    State(state): State<SharedAppState>,
+    Path(id_or_name): Path<String>,
+    WithRejection(Json(settings), _): WithRejection<Json<ChannelSettings>, AdminError>,
+) -> Result<Json<Channel>, AdminError> {
+    let (channels, config) = {
+        let state = state.lock().await;
+        (state.channels.clone(), state.config.clone())
+    };
+    let mut channels = channels.lock().await;
+    let channel = channels
+        .find_mut(&id_or_name)
+        .ok_or_else(|| AdminError::ChannelNotFound(id_or_name.clone()))?;
+    let problems = settings.problems(channel.name.as_str());
+    if !problems.is_empty() {
+        return Err(AdminError::InvalidSettings(problems.join("; ")));
+    }
+    channel.settings = settings;
+    let channel = channel.clone();
+    channels.save(&config.read().await.storage.channels)?;
+    info!("Updated settings of channel {} ({})", channel.name, channel.user_id);
+    Ok(Json(channel))
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChannelStatus {
+    #[serde(flatten)]
+    pub channel: Channel,
+    pub live: bool,
+    pub moderated: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Status {
+    pub paused: bool,
+    pub channels: Vec<ChannelStatus>,
+}
+
+async fn status(State(state): State<SharedAppState>) -> Result<Json<Status>, AdminError> {
+    let (client, token, channels, store, config) = {
+        let state = state.lock().await;
+        (
+            state.client.clone(),
+            state.token.clone(),
+            state.channels.clone(),
+            state.store.clone(),
+            state.config.clone(),
+        )
+    };
+    let token = token.lock().await.clone();
+    let channels = channels.lock().await.clone();
+    let twitch = config.read().await.twitch.clone();
+    let (live, moderated) = tokio::join!(
+        channels.get_live_channels(&client, &twitch, &token),
+        channels.get_moderated_channels(&client, &twitch, &token)
+    );
+    let channels = channels
+        .0
+        .into_iter()
+        .map(|channel| ChannelStatus {
+            live: live.iter().any(|c| c.user_id == channel.user_id),
+            moderated: moderated.iter().any(|c| c.user_id == channel.user_id),
+            channel,
+        })
+        .collect();
+    Ok(Json(Status {
+        paused: store.is_paused().await?,
+        channels,
+    }))
+}
+
+async fn pause(State(state): State<SharedAppState>) -> Result<StatusCode, AdminError> {
+    let store = state.lock().await.store.clone();
+    store.set_paused(true).await?;
+    info!("Announcements paused");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn resume(State(state): State<SharedAppState>) -> Result<StatusCode, AdminError> {
+    let (store, tx) = {
+        let state = state.lock().await;
+        (state.store.clone(), state.tx.clone())
+    };
+    store.set_paused(false).await?;
+    info!("Announcements resumed");
+    let _ = tx.send(Commands::Resumed);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Recent {
+    pub limit: Option<i64>,
+    /// Only those about donations to this campaign or team event.
+    pub campaign: Option<String>,
+}
+
+async fn recent_donations(
+    State(state): State<SharedAppState>,
+    Query(query): Query<Recent>,
+) -> Result<Json<Vec<DonationWithDeliveries>>, AdminError> {
+    let store = state.lock().await.store.clone();
+    let limit = query.limit.unwrap_or(20).clamp(1, 500);
+    Ok(Json(store.recent_donations(limit, query.campaign.as_deref()).await?))
+}
+
+/// A campaign from the config with what it raised so far. The signing secret is left out.
+#[derive(Debug, Serialize)]
+pub struct CampaignStatus {
+    /// The key in `campaigns`, empty for `[campaign]`.
+    pub id: Option<String>,
+    pub name: String,
+    pub url: Option<String>,
+    pub goal: Option<f64>,
+    pub milestones: Vec<f64>,
+    pub channels: Vec<String>,
+    pub raised: f64,
+}
+
+async fn campaign_status(
+    store: &Store,
+    config: &Config,
+    id: Option<&str>,
+    campaign: &CampaignConfig,
+) -> Result<CampaignStatus, AdminError> {
+    Ok(CampaignStatus {
+        id: id.map(str::to_string),
+        name: campaign.name.clone(),
+        url: campaign.url.clone(),
+        goal: campaign.goal,
+        milestones: campaign.milestones.clone(),
+        channels: campaign.channels.clone(),
+        raised: store.campaign_total(&config.campaign_scope(id)).await?,
+    })
+}
+
+/// `[campaign]` followed by every campaign in `campaigns`.
+async fn list_campaigns(
+    State(state): State<SharedAppState>,
+) -> Result<Json<Vec<CampaignStatus>>, AdminError> {
+    let (store, config) = {
+        let state = state.lock().await;
+        (state.store.clone(), state.config.read().await.clone())
+    };
+    let mut campaigns = vec![campaign_status(&store, &config, None, &config.campaign).await?];
+    for (id, campaign) in &config.campaigns {
+        campaigns.push(campaign_status(&store, &config, Some(id), campaign).await?);
+    }
+    Ok(Json(campaigns))
+}
+
+async fn campaign(
+    State(state): State<SharedAppState>,
+    Path(id): Path<String>,
+) -> Result<Json<CampaignStatus>, AdminError> {
+    let (store, config) = {
+        let state = state.lock().await;
+        (state.store.clone(), state.config.read().await.clone())
+    };
+    let campaign = config
+        .campaigns
+        .get(&id)
+        .ok_or_else(|| AdminError::CampaignNotFound(id.clone()))?;
+    Ok(Json(campaign_status(&store, &config, Some(&id), campaign).await?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Announce {
+    pub message: String,
+}
+
+async fn announce(
+    State(state): State<SharedAppState>,
+    WithRejection(Json(body), _): WithRejection<Json<Announce>, AdminError>,
+) -> Result<StatusCode, AdminError> {
+    if body.message.trim().is_empty() {
+        return Err(AdminError::EmptyMessage);
+    }
+    template::check_length(&body.message, template::CHAT_LIMIT)?;
+    state
+        .lock()
+        .await
+        .tx
+        .send(Commands::Announcement(body.message))
+        .map_err(|e| AdminError::Internal(e.into()))?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SimulateDonation {
+    pub amount: String,
+    pub currency: Option<String>,
+    pub name: Option<String>,
+    pub comment: Option<String>,
+}
+
+/// Tells apart donations simulated within the same millisecond.
+static SIMULATED: AtomicU64 = AtomicU64::new(0);
+
+async fn simulate_donation(
+    State(state): State<SharedAppState>,
+    WithRejection(Json(body), _): WithRejection<Json<SimulateDonation>, AdminError>,
+) -> Result<(StatusCode, Json<TiltifyDonation>), AdminError> {
+    if !body
+        .amount
+        .parse::<f64>()
+        .is_ok_and(|amount| amount.is_finite() && amount > 0.0)
+    {
+        return Err(AdminError::InvalidAmount(body.amount));
+    }
+    let donation = TiltifyDonation {
+        id: format!(
+            "simulated-{}-{}",
+            chrono::Utc::now().timestamp_millis(),
+            SIMULATED.fetch_add(1, Ordering::Relaxed)
+        ),
+        amount: Amount {
+            currency: body.currency.unwrap_or_else(|| "USD".to_string()),
+            value: body.amount,
+        },
+        name: body.name,
+        message: body.comment,
+        simulated: true,
+        ..TiltifyDonation::default()
+    };
+    info!("Simulating donation {}", donation.id);
+    state
+        .lock()
+        .await
+        .tx
+        .send(Commands::DonationReceived(donation.clone()))
+        .map_err(|e| AdminError::Internal(e.into()))?;
+    Ok((StatusCode::ACCEPTED, Json(donation)))
+}
+
+async fn webhook_deliveries(
+    State(state): State<SharedAppState>,
+    Query(query): Query<Recent>,
+) -> Result<Json<Vec<WebhookDeliveryWithAttempts>>, AdminError> {
+    let store = state.lock().await.store.clone();
+    let limit = query.limit.unwrap_or(20).clamp(1, 500);
+    Ok(Json(store.recent_webhook_deliveries(limit, query.campaign.as_deref()).await?))
+}
+
+async fn resend_webhook_delivery(
+    State(state): State<SharedAppState>,
+    Path(id): Path<i32>,
+) -> Result<(StatusCode, Json<WebhookDelivery>), AdminError> {
+    let (store, config, webhooks) = {
+        let state = state.lock().await;
+        (state.store.clone(), state.config.clone(), state.webhooks.clone())
+    };
+    let delivery = store
+        .webhook_delivery(id)
+        .await?
+        .ok_or(AdminError::DeliveryNotFound(id))?;
+    let webhook = config
+        .read()
+        .await
+        .webhooks
+        .iter()
+        .find(|w| w.name == delivery.webhook)
+        .cloned()
+        .ok_or_else(|| AdminError::WebhookNotConfigured(delivery.webhook.clone()))?;
+    info!("Re-sending webhook delivery {} to {}", delivery.id, webhook.name);
+    webhooks.resend(delivery.clone(), webhook).await;
+    Ok((StatusCode::ACCEPTED, Json(delivery)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Holds {
+    /// Only holds with this status, `pending` by default. `all` lists every hold.
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+    /// Only holds of donations to this campaign or team event.
+    pub campaign: Option<String>,
+}
+
+async fn list_holds(
+    State(state): State<SharedAppState>,
+    Query(query): Query<Holds>,
+) -> Result<Json<Vec<HoldWithDonation>>, AdminError> {
+    let store = state.lock().await.store.clone();
+    let status = match query.status.as_deref() {
+        None => Some("pending"),
+        Some("all") => None,
+        Some(status) => Some(status),
+    };
+    let limit = query.limit.unwrap_or(20).clamp(1, 500);
+    Ok(Json(store.recent_holds(status, limit, query.campaign.as_deref()).await?))
+}
+
+/// Raid trains within `raids.history_hours`, newest first. With a campaign, only trains passing
+/// a channel raising for it.
+async fn raid_trains(
+    State(state): State<SharedAppState>,
+    Query(query): Query<CampaignScope>,
+) -> Result<Json<Vec<RaidTrain>>, AdminError> {
+    let (store, config, channels) = {
+        let state = state.lock().await;
+        (
+            state.store.clone(),
+            state.config.read().await.clone(),
+            state.channels.clone(),
+        )
+    };
+    let since = config.raids.history_start();
+    let mut trains = raids::trains(store.raids_since(since).await?);
+    if let Some(campaign) = query.campaign.as_deref() {
+        let channels = channels.lock().await;
+        let raising: Vec<&Channel> = channels
+            .0
+            .iter()
+            .filter(|c| config.channel_tiltify_ids(c).contains(&campaign))
+            .collect();
+        trains.retain(|train| raising.iter().any(|c| train.involves(c.user_id.as_str())));
+    }
+    trains.reverse();
+    Ok(Json(trains))
+}
+
+/// What each participant raised while live and what was credited to them, most raised first.
+async fn raised_by_channel(
+    State(state): State<SharedAppState>,
+    Query(query): Query<CampaignScope>,
+) -> Result<Json<Vec<ChannelRaised>>, AdminError> {
+    let store = state.lock().await.store.clone();
+    Ok(Json(store.raised_by_channel(query.campaign.as_deref()).await?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CampaignScope {
+    /// Only donations to this campaign or team event.
+    pub campaign: Option<String>,
+}
+
+/// Whether a stored donation went to `campaign` or its team event.
+fn donated_to(donation: Option<&Donation>, campaign: &str) -> bool {
+    donation.is_some_and(|d| {
+        d.campaign_id.as_deref() == Some(campaign) || d.team_event_id.as_deref() == Some(campaign)
+    })
+}
+
+async fn approve_hold(
+    State(state): State<SharedAppState>,
+    Path(id): Path<i32>,
+    Query(query): Query<CampaignScope>,
+) -> Result<Json<Hold>, AdminError> {
+    settle_hold(state, id, query.campaign.as_deref(), true).await
+}
+
+async fn reject_hold(
+    State(state): State<SharedAppState>,
+    Path(id): Path<i32>,
+    Query(query): Query<CampaignScope>,
+) -> Result<Json<Hold>, AdminError> {
+    settle_hold(state, id, query.campaign.as_deref(), false).await
+}
+
+/// Settles a hold. With a campaign, holds of donations to other campaigns aren't found.
+async fn settle_hold(
+    state: SharedAppState,
+    id: i32,
+    campaign: Option<&str>,
+    approve: bool,
+) -> Result<Json<Hold>, AdminError> {
+    let (store, config, tx) = {
+        let state = state.lock().await;
+        (state.store.clone(), state.config.read().await.clone(), state.tx.clone())
+    };
+    if let Some(campaign) = campaign {
+        let hold = store.hold(id).await?.ok_or(HoldError::NotFound(id))?;
+        if !donated_to(store.donation(&hold.donation_id).await?.as_ref(), campaign) {
+            return Err(HoldError::NotFound(id).into());
+        }
+    }
+    let (hold, released) = holds::settle(&store, &config, id, approve, "admin").await?;
+    if let Some(donation) = released {
+        let command = if hold.comment_only {
+            Commands::CommentReleased(donation)
+        } else {
+            Commands::DonationReleased(donation)
+        };
+        tx.send(command).map_err(|e| AdminError::Internal(e.into()))?;
+    }
+    Ok(Json(hold))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Rewards {
+    /// Only reward claims with this status, `pending` by default. `all` lists every claim.
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+    /// Only rewards claimed with donations to this campaign or team event.
+    pub campaign: Option<String>,
+}
+
+async fn list_rewards(
+    State(state): State<SharedAppState>,
+    Query(query): Query<Rewards>,
+) -> Result<Json<Vec<RewardClaimWithDonation>>, AdminError> {
+    let store = state.lock().await.store.clone();
+    let status = match query.status.as_deref() {
+        None => Some("pending"),
+        Some("all") => None,
+        Some(status) => Some(status),
+    };
+    let limit = query.limit.unwrap_or(20).clamp(1, 500);
+    Ok(Json(store.recent_reward_claims(status, limit, query.campaign.as_deref()).await?))
+}
+
+/// Marks a reward claim done. With a campaign, claims with donations to other campaigns aren't
+/// found.
+async fn complete_reward(
+    State(state): State<SharedAppState>,
+    Path(id): Path<i32>,
+    Query(query): Query<CampaignScope>,
+) -> Result<Json<RewardClaim>, AdminError> {
+    let (store, config) = {
+        let state = state.lock().await;
+        (state.store.clone(), state.config.read().await.clone())
+    };
+    if let Some(campaign) = query.campaign.as_deref() {
+        let claim = store.reward_claim(id).await?.ok_or(RewardError::NotFound(id))?;
+        if !donated_to(store.donation(&claim.donation_id).await?.as_ref(), campaign) {
+            return Err(RewardError::NotFound(id).into());
+        }
+    }
+    Ok(Json(rewards::complete(&store, &config, id, None, "admin").await?))
+}
+
+#[derive(Debug, Error)]
+pub enum AdminError {
+    #[error("admin API is disabled, set admin.api_key to enable it")]
+    Disabled,
+    #[error("missing or invalid API key")]
+    Unauthorized,
+    #[error("Twitch user {0} not found")]
+    UserNotFound(String),
+    #[error("channel {0} not found")]
+    ChannelNotFound(String),
+    #[error("channel {0} is already participating")]
+    ChannelExists(String),
+    #[error("webhook delivery {0} not found")]
+    DeliveryNotFound(i32),
+    #[error("webhook {0} is no longer configured")]
+    WebhookNotConfigured(String),
+    #[error("campaign {0} not found")]
+    CampaignNotFound(String),
+    #[error("hold {0} not found")]
+    HoldNotFound(i32),
+    #[error("hold {0} is already {1}")]
+    HoldSettled(i32, String),
+    #[error("reward claim {0} not found")]
+    RewardNotFound(i32),
+    #[error("reward claim {0} is already done")]
+    RewardDone(i32),
+    #[error("invalid channel settings: {0}")]
+    InvalidSettings(String),
+    #[error("message must not be empty")]
+    EmptyMessage,
+    #[error("amount {0:?} is not a positive number")]
+    InvalidAmount(String),
+    #[error(transparent)]
+    MessageTooLong(#[from] TooLong),
+    #[error(transparent)]
+    JsonExtractorRejection(#[from] JsonRejection),
+    #[error(transparent)]
+    Internal(#[from] Report),
+}
+
+impl From<HoldError> for AdminError {
+    fn from(e: HoldError) -> Self {
+        match e {
+            HoldError::NotFound(id) => AdminError::HoldNotFound(id),
+            HoldError::AlreadySettled(id, status) => AdminError::HoldSettled(id, status),
+            HoldError::Internal(e) => AdminError::Internal(e),
+        }
+    }
+}
+
+impl From<RewardError> for AdminError {
+    fn from(e: RewardError) -> Self {
+        match e {
+            RewardError::NotFound(id) => AdminError::RewardNotFound(id),
+            RewardError::AlreadyDone(id) => AdminError::RewardDone(id),
+            // Only chat is limited to the channel a reward is for.
+            RewardError::OtherChannel(..) => AdminError::Internal(eyre::eyre!("{e}")),
+            RewardError::Internal(e) => AdminError::Internal(e),
+        }
+    }
+}
+
+impl IntoResponse for AdminError {
+    fn into_response(self) -> Response {
+        let code = match &self {
+            AdminError::Disabled => StatusCode::SERVICE_UNAVAILABLE,
+            AdminError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AdminError::UserNotFound(_)
+            | AdminError::ChannelNotFound(_)
+            | AdminError::DeliveryNotFound(_)
+            | AdminError::CampaignNotFound(_)
+            | AdminError::HoldNotFound(_)
+            | AdminError::RewardNotFound(_) => StatusCode::NOT_FOUND,
+            AdminError::ChannelExists(_)
+            | AdminError::WebhookNotConfigured(_)
+            | AdminError::HoldSettled(..)
+            | AdminError::RewardDone(_) => StatusCode::CONFLICT,
+            AdminError::InvalidSettings(_)
+            | AdminError::EmptyMessage
+            | AdminError::InvalidAmount(_)
+            | AdminError::MessageTooLong(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AdminError::JsonExtractorRejection(x) => x.status(),
+            AdminError::Internal(e) => {
+                error!("Admin API error: {e:?}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        let payload = json!({
+            "message": self.to_string(),
+            "origin": "admin"
+        });
+        (code, Json(payload)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AppState;
+    use crate::notify::stand_in::StandIn;
+    use tokio::sync::broadcast;
+
+    /// A config for the admin API with `key` as its API key and a channels file of its own.
+    fn config() -> Config {
+        let mut config = Config::default();
+        config.admin.api_key = Some("key".to_string());
+        config.storage.channels = std::env::temp_dir()
+            .join(format!(
+                "warbot-admin-channels-{}-{}.json",
+                std::process::id(),
+                chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+            ))
+            .to_string_lossy()
+            .into_owned();
+        config
+    }
+
+    /// Serves the admin API with alice participating, returning its base url.
+    async fn serve(config: Config) -> (String, SharedAppState, broadcast::Receiver<Commands>) {
+        let (state, rx) = AppState::test(config);
+        let base = crate::routes::serve(state.clone()).await;
+        (format!("{base}/admin"), state, rx)
+    }
+
+    #[tokio::test]
+    async fn requires_the_api_key() {
+        let http = reqwest::Client::new();
+        let (base, _, _) = serve(config()).await;
+        let channels = format!("{base}/channels");
+
+        let missing = http.get(&channels).send().await.unwrap();
+        assert_eq!(missing.status(), StatusCode::UNAUTHORIZED);
+        for wrong in ["kex", "key2", ""] {
+            let res = http.get(&channels).bearer_auth(wrong).send().await.unwrap();
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED, "{wrong}");
+        }
+        let res = http.get(&channels).bearer_auth("key").send().await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.json::<serde_json::Value>().await.unwrap()[0]["name"], "alice");
+
+        let (base, _, _) = serve(Config::default()).await;
+        let res = http
+            .get(format!("{base}/channels"))
+            .bearer_auth("key")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn pauses_and_resumes_announcements() {
+        let http = reqwest::Client::new();
+        let (base, state, mut rx) = serve(config()).await;
+        let store = state.lock().await.store.clone();
+
+        let res = http.post(format!("{base}/pause")).bearer_auth("key").send().await.unwrap();
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert!(store.is_paused().await.unwrap());
+        assert!(rx.try_recv().is_err());
+
+        let res = http.post(format!("{base}/resume")).bearer_auth("key").send().await.unwrap();
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert!(!store.is_paused().await.unwrap());
+        assert!(matches!(rx.try_recv(), Ok(Commands::Resumed)));
+    }
+
+    #[tokio::test]
+    async fn adds_updates_and_removes_channels() {
+        let stand_in = StandIn::start(|_| {
+            let users = json!({ "data": [{
+                "id": "3",
+                "login": "carol",
+                "display_name": "Carol",
+                "type": "",
+                "broadcaster_type": "",
+                "description": "",
+                "profile_image_url": "",
+                "offline_image_url": "",
+                "created_at": "2026-10-18T12:00:00Z",
+            }]});
+            (StatusCode::OK, users.to_string())
+        })
+        .await;
+        let mut config = config();
+        config.twitch.helix_url = stand_in.url("/helix/");
+        let path = config.storage.channels.clone();
+        let http = reqwest::Client::new();
+        let (base, _, mut rx) = serve(config).await;
+        let send = |request: reqwest::RequestBuilder| async {
+            request.bearer_auth("key").send().await.unwrap().status()
+        };
+
+        let carol = json!({ "name": "carol" });
+        let added = send(http.post(format!("{base}/channels")).json(&carol)).await;
+        assert_eq!(added, StatusCode::CREATED);
+        assert!(matches!(rx.try_recv(), Ok(Commands::ChannelsChanged)));
+        let again = send(http.post(format!("{base}/channels")).json(&carol)).await;
+        assert_eq!(again, StatusCode::CONFLICT);
+
+        let settings = format!("{base}/channels/carol/settings");
+        let invalid = send(http.put(&settings).json(&json!({ "home_only": true }))).await;
+        assert_eq!(invalid, StatusCode::UNPROCESSABLE_ENTITY);
+        let home = json!({ "home_only": true, "campaign_id": "carol-campaign" });
+        assert_eq!(send(http.put(&settings).json(&home)).await, StatusCode::OK);
+
+        let removed = send(http.delete(format!("{base}/channels/alice"))).await;
+        assert_eq!(removed, StatusCode::OK);
+        assert!(matches!(rx.try_recv(), Ok(Commands::ChannelsChanged)));
+        let missing = send(http.delete(format!("{base}/channels/alice"))).await;
+        assert_eq!(missing, StatusCode::NOT_FOUND);
+
+        let saved = Channels::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(saved.0.len(), 1);
+        assert_eq!(saved.0[0].name.as_str(), "carol");
+        assert_eq!(saved.0[0].settings.campaign_id.as_deref(), Some("carol-campaign"));
+    }
+}
@@ -0,0 +1,171 @@
+use crate::SharedAppState;
+use crate::health::REFRESH_STALL_AFTER;
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use serde_derive::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use twitch_oauth2::TwitchToken;
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl Check {
+    fn ok(detail: impl Into<String>) -> Self {
+        Self {
+            ok: true,
+            detail: detail.into(),
+        }
+    }
+
+    fn failed(detail: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            detail: detail.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub status: &'static str,
+    pub checks: BTreeMap<&'static str, Check>,
+}
+
+pub async fn liveness() -> Json<serde_json::Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+pub async fn readiness(State(state): State<SharedAppState>) -> (StatusCode, Json<Readiness>) {
+    let (token, health, store) = {
+        let state = state.lock().await;
+        (state.token.clone(), state.health.clone(), state.store.clone())
+    };
+    let mut checks = BTreeMap::new();
+
+    let expires_in = token.lock().await.expires_in();
+    let token_check = match health.token_valid() {
+        None => Check::failed("bot token has not been validated yet"),
+        Some(false) => Check::failed("bot token failed validation"),
+        Some(true) if expires_in.is_zero() => Check::failed("bot token is expired"),
+        Some(true) => Check::ok(format!("expires in {}s", expires_in.as_secs())),
+    };
+    checks.insert("token", token_check);
+
+    let refresh_check = match health.since_refresh_tick() {
+        None => Check::failed("token refresh loop has not started"),
+        Some(secs) if secs > REFRESH_STALL_AFTER.as_secs() as i64 => {
+            Check::failed(format!("token refresh loop last ticked {secs}s ago"))
+        }
+        Some(secs) => Check::ok(format!("last ticked {secs}s ago")),
+    };
+    checks.insert("token_refresh", refresh_check);
+
+    let eventsub_check = if health.eventsub_connected() {
+        Check::ok("connected")
+    } else {
+        Check::failed("disconnected")
+    };
+    checks.insert("eventsub", eventsub_check);
+
+    let database_check = match store.ping().await {
+        Ok(()) => Check::ok("reachable"),
+        Err(e) => Check::failed(format!("unreachable: {e}")),
+    };
+    checks.insert("database", database_check);
+
+    let ready = checks.values().all(|c| c.ok);
+    let code = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        code,
+        Json(Readiness {
+            status: if ready { "ready" } else { "not_ready" },
+            checks,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AppState;
+    use crate::config::Config;
+    use crate::db::Store;
+    use serde_json::Value;
+
+    /// Serves a bot whose token is validated, refresh loop ticking and EventSub connected.
+    async fn serve(store: Option<Store>) -> (String, crate::SharedAppState) {
+        let (state, _) = AppState::test(Config::default());
+        {
+            let mut state = state.lock().await;
+            state.health.token_validated(true);
+            state.health.refresh_ticked();
+            state.health.set_eventsub_connected(true);
+            if let Some(store) = store {
+                state.store = store;
+            }
+        }
+        (crate::routes::serve(state.clone()).await, state)
+    }
+
+    /// The status code of `/readyz` and the checks it reports as failed.
+    async fn readiness(base: &str) -> (u16, Vec<String>) {
+        let response = reqwest::get(format!("{base}/readyz")).await.unwrap();
+        let status = response.status().as_u16();
+        let body: Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+        let failed = body["checks"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .filter(|(_, check)| check["ok"] == false)
+            .map(|(name, _)| name.clone())
+            .collect();
+        (status, failed)
+    }
+
+    #[tokio::test]
+    async fn is_ready_when_every_check_passes() {
+        let (base, _) = serve(None).await;
+        assert_eq!(readiness(&base).await, (200, vec![]));
+    }
+
+    #[tokio::test]
+    async fn fails_on_a_stalled_token_refresh() {
+        let (base, state) = serve(None).await;
+        let health = state.lock().await.health.clone();
+        health.refresh_ticked_ago(170);
+        assert_eq!(readiness(&base).await, (200, vec![]));
+        health.refresh_ticked_ago(190);
+        assert_eq!(readiness(&base).await, (503, vec!["token_refresh".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn fails_when_eventsub_is_down() {
+        let (base, state) = serve(None).await;
+        state.lock().await.health.set_eventsub_connected(false);
+        assert_eq!(readiness(&base).await, (503, vec!["eventsub".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn fails_when_the_database_cannot_be_read() {
+        let path = std::env::temp_dir().join(format!(
+            "warbot-readyz-{}-{}.db",
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let store = Store::open(&path.to_string_lossy()).unwrap();
+        let (base, _) = serve(Some(store)).await;
+        assert_eq!(readiness(&base).await, (200, vec![]));
+
+        std::fs::write(&path, vec![0xff; 4096]).unwrap();
+        assert_eq!(readiness(&base).await, (503, vec!["database".to_string()]));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,14 @@
+use crate::SharedAppState;
+use crate::metrics;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+
+pub async fn handler(State(state): State<SharedAppState>) -> impl IntoResponse {
+    let queue_depth = state.lock().await.tx.len();
+    metrics::BROADCAST_QUEUE_DEPTH.set(queue_depth as i64);
+    (
+        [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        metrics::gather(),
+    )
+}
@@ -3,17 +3,34 @@ use axum::Router;
 use axum::routing::{get, post};
 use crate::SharedAppState;
 
+pub mod admin;
+pub mod health;
+pub mod metrics;
 pub mod webhook;
 pub mod tiltify;
 pub mod twitch;
 
-pub fn router() -> Router<SharedAppState> {
+pub fn router(state: SharedAppState) -> Router<SharedAppState> {
     Router::new()
         .route("/", get(home_handler))
+        .route("/healthz", get(health::liveness))
+        .route("/readyz", get(health::readiness))
+        .route("/metrics", get(metrics::handler))
         .route("/webhook", post(tiltify::webhook::handler))
         .nest("/tiltify", tiltify::router())
+        .nest("/admin", admin::router(state))
 }
 
 pub async fn home_handler() -> impl IntoResponse {
     "Hello, world!"
+}
+
+/// Serves every route on a free port for tests, returning the base url.
+#[cfg(test)]
+pub async fn serve(state: SharedAppState) -> String {
+    let app = router(state.clone()).with_state(state);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    base
 }
\ No newline at end of file
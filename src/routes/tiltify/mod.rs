@@ -1,5 +1,5 @@
 use crate::SharedAppState;
-use crate::routes::webhook::{Amount, TiltifyWebhookRequest};
+use crate::routes::webhook::{Amount, RewardClaim, TiltifyWebhookRequest, id};
 use axum::Router;
 use axum::routing::post;
 use serde_derive::{Deserialize, Serialize};
@@ -8,11 +8,14 @@ use crate::routes::tiltify::TiltifyEventType::Other;
 pub mod webhook;
 
 pub fn router() -> Router<SharedAppState> {
-    Router::new().route("/webhook", post(webhook::handler))
+    Router::new()
+        .route("/webhook", post(webhook::handler))
+        .route("/campaigns/{campaign}/webhook", post(webhook::campaign_handler))
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub enum TiltifyEventType {
+    #[default]
     DonationUpdated,
     Other
 }
@@ -29,21 +32,135 @@ impl From<String> for TiltifyEventType {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct TiltifyDonation {
+    pub id: String,
+    pub campaign_id: Option<String>,
     pub event_type: TiltifyEventType,
     pub amount: Amount,
     pub name: Option<String>,
     pub message: Option<String>,
+    #[serde(default)]
+    pub target_id: Option<String>,
+    #[serde(default)]
+    pub reward_id: Option<String>,
+    #[serde(default)]
+    pub team_event_id: Option<String>,
+    #[serde(default)]
+    pub reward_claims: Vec<RewardClaim>,
+    /// The poll the donation votes in and the option it votes for.
+    #[serde(default)]
+    pub poll_id: Option<String>,
+    #[serde(default)]
+    pub poll_option_id: Option<String>,
+    /// Injected through the admin API rather than received from Tiltify.
+    #[serde(default)]
+    pub simulated: bool,
+}
+
+#[cfg(test)]
+impl TiltifyDonation {
+    /// A donation of `amount` USD for tests, the rest filled in with struct update syntax.
+    pub fn test(id: &str, amount: &str) -> Self {
+        TiltifyDonation {
+            id: id.to_string(),
+            amount: Amount {
+                currency: "USD".to_string(),
+                value: amount.to_string(),
+            },
+            ..TiltifyDonation::default()
+        }
+    }
+}
+
+impl TiltifyDonation {
+    /// The rewards the donor claimed. Events without `reward_claims` claim their `reward_id` once.
+    pub fn claimed_rewards(&self) -> Vec<RewardClaim> {
+        if !self.reward_claims.is_empty() {
+            return self.reward_claims.clone();
+        }
+        self.reward_id
+            .iter()
+            .map(|reward_id| RewardClaim {
+                id: None,
+                reward_id: Some(reward_id.clone()),
+                quantity: 1,
+                custom_question_response: None,
+            })
+            .collect()
+    }
 }
 
 impl From<TiltifyWebhookRequest> for TiltifyDonation {
     fn from(value: TiltifyWebhookRequest) -> Self {
         Self {
+            id: value.data.id,
+            campaign_id: value.data.campaign_id,
             event_type: TiltifyEventType::from(value.meta.event_type),
             amount: value.data.amount,
             name: value.data.donor_name,
             message: value.data.donor_comment,
+            target_id: value.data.target_id,
+            reward_id: value.data.reward_id,
+            team_event_id: id(value.data.team_event_id),
+            reward_claims: value.data.reward_claims.unwrap_or_default(),
+            poll_id: value.data.poll_id,
+            poll_option_id: value.data.poll_option_id,
+            simulated: false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(data: serde_json::Value) -> TiltifyWebhookRequest {
+        let mut request = serde_json::json!({
+            "data": {
+                "amount": { "currency": "USD", "value": "25.00" },
+                "campaign_id": "campaign",
+                "cause_id": "cause",
+                "completed_at": "2026-10-18T12:00:00Z",
+                "created_at": "2026-10-18T12:00:00Z",
+                "id": "donation",
+                "legacy_id": 0,
+                "sustained": false,
+            },
+            "meta": {
+                "id": "event",
+                "event_type": "public:direct:donation_updated",
+                "attempted_at": "2026-10-18T12:00:00Z",
+                "generated_at": "2026-10-18T12:00:00Z",
+                "subscription_source_id": "campaign",
+                "subscription_source_type": "test",
+            },
+        });
+        for (key, value) in data.as_object().unwrap() {
+            request["data"][key] = value.clone();
+        }
+        serde_json::from_value(request).unwrap()
+    }
+
+    #[test]
+    fn reads_claimed_rewards() {
+        let claims = TiltifyDonation::from(request(serde_json::json!({
+            "reward_id": 7,
+            "reward_claims": [
+                { "id": "claim", "reward_id": "song", "quantity": 2 },
+                { "reward_id": 7, "custom_question_response": "Wonderwall" },
+            ],
+        })))
+        .claimed_rewards();
+        let claimed: Vec<_> = claims
+            .iter()
+            .map(|c| (c.reward_id.as_deref(), c.quantity))
+            .collect();
+        assert_eq!(claimed, [(Some("song"), 2), (Some("7"), 1)]);
+        assert_eq!(claims[1].custom_question_response.as_deref(), Some("Wonderwall"));
+
+        let single = TiltifyDonation::from(request(serde_json::json!({ "reward_id": "hat" })));
+        assert_eq!(single.claimed_rewards()[0].reward_id.as_deref(), Some("hat"));
+        assert!(TiltifyDonation::from(request(serde_json::json!({}))).claimed_rewards().is_empty());
+    }
+}
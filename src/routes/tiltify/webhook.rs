@@ -1,43 +1,141 @@
+use crate::config::Config;
 use crate::routes::tiltify::TiltifyDonation;
 use crate::routes::webhook::TiltifyWebhookRequest;
-use crate::{Commands, SharedAppState};
+use crate::{Commands, SharedAppState, metrics};
 use axum::Json;
-use axum::extract::State;
+use axum::body::Bytes;
 use axum::extract::rejection::JsonRejection;
-use axum::http::{Method, StatusCode};
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, Method, StatusCode};
 use axum::response::{IntoResponse, Response};
-use axum_extra::extract::WithRejection;
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use hmac::{Hmac, Mac};
 use serde_json::json;
+use sha2::Sha256;
 use thiserror::Error;
 use tracing::info;
 
+/// Header carrying the base64 HMAC-SHA256 of `<timestamp>.<body>`.
+pub const SIGNATURE_HEADER: &str = "X-Tiltify-Signature";
+/// Header carrying the timestamp the signature covers.
+pub const TIMESTAMP_HEADER: &str = "X-Tiltify-Timestamp";
+
+/// Receives webhooks for `[campaign]`, checked against its signing secret. Donations to a
+/// campaign in `campaigns` are checked against that campaign's secret instead.
 pub async fn handler(
     State(state): State<SharedAppState>,
     method: Method,
-    WithRejection(Json(json), _): WithRejection<Json<TiltifyWebhookRequest>, ApiError>,
+    headers: HeaderMap,
+    body: Bytes,
 ) -> Result<Json<TiltifyWebhookRequest>, Response> {
     if method != Method::POST {
         return Err(StatusCode::METHOD_NOT_ALLOWED.into_response());
     }
-    state
-        .lock()
+    receive(state, None, &headers, &body)
+        .await
+        .map_err(IntoResponse::into_response)
+}
+
+/// Receives webhooks for a campaign in `campaigns`, checked against its signing secret.
+pub async fn campaign_handler(
+    State(state): State<SharedAppState>,
+    Path(campaign): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<TiltifyWebhookRequest>, Response> {
+    receive(state, Some(campaign), &headers, &body)
         .await
-        .tx
-        .send(Commands::DonationReceived(TiltifyDonation::from(
-            json.clone(),
-        )))
+        .map_err(IntoResponse::into_response)
+}
+
+async fn receive(
+    state: SharedAppState,
+    campaign: Option<String>,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<Json<TiltifyWebhookRequest>, ApiError> {
+    let (tx, config) = {
+        let state = state.lock().await;
+        (state.tx.clone(), state.config.clone())
+    };
+    let config = config.read().await;
+    let parsed = Json::<TiltifyWebhookRequest>::from_bytes(body)
+        .map(|Json(json)| (TiltifyDonation::from(json.clone()), json));
+    let key = parsed.as_ref().ok().and_then(|(donation, _)| {
+        config.campaign_key(
+            donation.campaign_id.as_deref(),
+            donation.team_event_id.as_deref(),
+        )
+    });
+    if let Some(secret) = signing_secret(&config, campaign.as_deref(), key)? {
+        let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+        let (Some(signature), Some(timestamp)) = (header(SIGNATURE_HEADER), header(TIMESTAMP_HEADER))
+        else {
+            return Err(ApiError::InvalidSignature);
+        };
+        if !verify(secret, timestamp, body, signature) {
+            return Err(ApiError::InvalidSignature);
+        }
+    }
+    let (donation, json) = parsed?;
+    if let Some(campaign) = campaign
+        && key != Some(campaign.as_str())
+    {
+        return Err(ApiError::WrongCampaign(campaign));
+    }
+    metrics::WEBHOOKS_RECEIVED
+        .with_label_values(&[metrics::campaign_label(key), json.meta.event_type.as_str()])
+        .inc();
+    tx.send(Commands::DonationReceived(donation))
         .expect("Failed to send message");
     info!("Tiltify Webhook received",);
 
     Ok(Json(json))
 }
 
+/// The secret a webhook has to be signed with: that of the campaign whose route it came in on,
+/// or on the default route that of the campaign the donation belongs to.
+fn signing_secret<'a>(
+    config: &'a Config,
+    route: Option<&str>,
+    key: Option<&str>,
+) -> Result<Option<&'a str>, ApiError> {
+    let campaign = match route {
+        Some(route) => config
+            .campaigns
+            .get(route)
+            .ok_or_else(|| ApiError::UnknownCampaign(route.to_string()))?,
+        None => config.campaign(key),
+    };
+    Ok(campaign.signing_secret.as_deref())
+}
+
+/// Checks Tiltify's signature of a webhook body, the base64 HMAC-SHA256 of `<timestamp>.<body>`.
+pub fn verify(secret: &str, timestamp: &str, body: &[u8], signature: &str) -> bool {
+    let Ok(signature) = BASE64_STANDARD.decode(signature) else {
+        return false;
+    };
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
 #[derive(Debug, Error)]
 pub enum ApiError {
     // The `#[from]` attribute generates `From<JsonRejection> for ApiError`
     // implementation. See `thiserror` docs for more information
     #[error(transparent)]
     JsonExtractorRejection(#[from] JsonRejection),
+    #[error("missing or invalid Tiltify signature")]
+    InvalidSignature,
+    #[error("campaign {0} is not configured")]
+    UnknownCampaign(String),
+    #[error("donation doesn't belong to campaign {0}")]
+    WrongCampaign(String),
 }
 
 impl IntoResponse for ApiError {
@@ -53,7 +151,50 @@ impl IntoResponse for ApiError {
                 JsonRejection::MissingJsonContentType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
                 _ => StatusCode::INTERNAL_SERVER_ERROR,
             },
+            ApiError::InvalidSignature => StatusCode::UNAUTHORIZED,
+            ApiError::UnknownCampaign(_) => StatusCode::NOT_FOUND,
+            ApiError::WrongCampaign(_) => StatusCode::UNPROCESSABLE_ENTITY,
         };
         (code, Json(payload)).into_response()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CampaignConfig;
+
+    #[test]
+    fn verifies_tiltify_signatures() {
+        let timestamp = "2026-10-18T12:00:00Z";
+        // echo -n '2026-10-18T12:00:00Z.{}' | openssl dgst -sha256 -hmac hunter2 -binary | base64
+        let signature = "loSlAHW7CqufQ9Me0TncAvlmS1Q5RUswseSLmEHHJqk=";
+
+        assert!(verify("hunter2", timestamp, b"{}", signature));
+        assert!(!verify("hunter3", timestamp, b"{}", signature));
+        assert!(!verify("hunter2", timestamp, b"{ }", signature));
+        assert!(!verify("hunter2", timestamp, b"{}", "not base64"));
+    }
+
+    #[test]
+    fn checks_campaign_donations_on_the_default_route_with_their_secret() {
+        let mut config = Config::default();
+        config.campaign.signing_secret = Some("default".to_string());
+        config.campaigns.insert(
+            "winter".to_string(),
+            CampaignConfig {
+                signing_secret: Some("winter".to_string()),
+                ..CampaignConfig::default()
+            },
+        );
+        let secret = |route, key| signing_secret(&config, route, key).unwrap();
+
+        assert_eq!(secret(None, None), Some("default"));
+        assert_eq!(secret(None, Some("winter")), Some("winter"));
+        assert_eq!(secret(Some("winter"), None), Some("winter"));
+        assert!(matches!(
+            signing_secret(&config, Some("summer"), None),
+            Err(ApiError::UnknownCampaign(_))
+        ));
+    }
+}
@@ -1,3 +1,4 @@
+use serde::{Deserialize as _, Deserializer};
 use serde_derive::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -19,16 +20,38 @@ pub struct Data {
     pub fundraising_event_id: Option<serde_json::Value>,
     pub id: String,
     pub legacy_id: i64,
-    pub poll_id: Option<serde_json::Value>,
-    pub poll_option_id: Option<serde_json::Value>,
-    pub reward_claims: Option<serde_json::Value>,
-    pub reward_id: Option<serde_json::Value>,
+    #[serde(default, deserialize_with = "optional_id")]
+    pub poll_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_id")]
+    pub poll_option_id: Option<String>,
+    pub reward_claims: Option<Vec<RewardClaim>>,
+    #[serde(default, deserialize_with = "optional_id")]
+    pub reward_id: Option<String>,
     pub sustained: bool,
-    pub target_id: Option<serde_json::Value>,
+    #[serde(default, deserialize_with = "optional_id")]
+    pub target_id: Option<String>,
     pub team_event_id: Option<serde_json::Value>,
 }
 
+/// A reward the donor claimed with their donation.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RewardClaim {
+    #[serde(default, deserialize_with = "optional_id")]
+    pub id: Option<String>,
+    #[serde(default, deserialize_with = "optional_id")]
+    pub reward_id: Option<String>,
+    #[serde(default = "one")]
+    pub quantity: u32,
+    /// The donor's answer to the reward's custom question, if it asks one.
+    #[serde(default)]
+    pub custom_question_response: Option<String>,
+}
+
+fn one() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Amount {
     pub currency: String,
     pub value: String,
@@ -43,3 +66,16 @@ pub struct Meta {
     pub subscription_source_id: String,
     pub subscription_source_type: String,
 }
+
+/// Tiltify ids are UUID strings, but older events carry numbers.
+pub fn id(value: Option<serde_json::Value>) -> Option<String> {
+    match value? {
+        serde_json::Value::String(id) => Some(id),
+        serde_json::Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+fn optional_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(id(Option::<serde_json::Value>::deserialize(deserializer)?))
+}
@@ -0,0 +1,88 @@
+use crate::config::{OtlpConfig, SentryConfig};
+use eyre::Report;
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider};
+use std::borrow::Cow;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+/// Keeps the Sentry client and the OTLP exporter alive, flushing them when dropped.
+pub struct Telemetry {
+    _sentry: Option<sentry::ClientInitGuard>,
+    tracer_provider: Option<SdkTracerProvider>,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Some(provider) = self.tracer_provider.take()
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("Failed to flush OTLP traces: {e}");
+        }
+    }
+}
+
+/// Sets up logging, and Sentry and OTLP reporting when they are configured.
+pub fn init(sentry_config: &SentryConfig, otlp_config: &OtlpConfig) -> Telemetry {
+    let sentry = sentry_config.dsn.as_ref().map(|dsn| {
+        sentry::init((
+            dsn.as_str(),
+            sentry::ClientOptions {
+                release: sentry::release_name!(),
+                environment: sentry_config.environment.clone().map(Cow::Owned),
+                sample_rate: sentry_config.sample_rate,
+                traces_sample_rate: sentry_config.traces_sample_rate,
+                send_default_pii: sentry_config.send_default_pii,
+                ..Default::default()
+            },
+        ))
+    });
+
+    let tracer_provider = otlp_config.endpoint.as_ref().and_then(|endpoint| {
+        tracer_provider(endpoint, otlp_config)
+            .inspect_err(|e| eprintln!("Failed to set up the OTLP exporter: {e:?}"))
+            .ok()
+    });
+    let otlp_layer = tracer_provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer().with_tracer(provider.tracer(env!("CARGO_CRATE_NAME")))
+    });
+
+    tracing_subscriber::registry()
+        .with(sentry.is_some().then(sentry::integrations::tracing::layer))
+        .with(otlp_layer)
+        .with(
+            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+                format!(
+                    "debug,{}=trace,hyper_util=debug,axum_serve=debug,tungstenite=debug,opentelemetry_sdk=info",
+                    env!("CARGO_CRATE_NAME")
+                )
+                .into()
+            }),
+        )
+        .with(tracing_subscriber::fmt::layer())
+        .init();
+
+    Telemetry {
+        _sentry: sentry,
+        tracer_provider,
+    }
+}
+
+fn tracer_provider(endpoint: &str, config: &OtlpConfig) -> Result<SdkTracerProvider, Report> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()?;
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.traces_sample_rate,
+        ))))
+        .with_resource(
+            Resource::builder()
+                .with_service_name(config.service_name.clone())
+                .build(),
+        )
+        .build())
+}
@@ -0,0 +1,371 @@
+//! Lookups in Tiltify's public API, authenticated as the application in `[tiltify]`.
+
+use crate::config::SharedConfig;
+use crate::routes::tiltify::TiltifyDonation;
+use crate::routes::webhook::Amount;
+use eyre::{Report, WrapErr};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Polls and targets asked for in chat are fetched at most this often, so chat can't make the
+/// bot hammer Tiltify.
+pub const CHAT_MAX_AGE: Duration = Duration::from_secs(15);
+
+/// Tokens are renewed this long before Tiltify would expire them.
+const TOKEN_MARGIN: Duration = Duration::from_secs(60);
+
+#[derive(Clone)]
+pub struct TiltifyClient {
+    config: SharedConfig,
+    http: reqwest::Client,
+    token: Arc<Mutex<Option<(String, Instant)>>>,
+    /// Reward names by id, rewards are rarely renamed during a campaign.
+    rewards: Arc<Mutex<HashMap<String, String>>>,
+    /// Lists of a campaign by `<id>/<resource>`, with when they were fetched.
+    lists: Arc<Mutex<HashMap<String, (Instant, serde_json::Value)>>>,
+}
+
+/// Tiltify wraps every response in `data`.
+#[derive(Debug, Deserialize)]
+struct Page<T> {
+    data: T,
+}
+
+#[derive(Debug, Serialize)]
+struct TokenRequest<'a> {
+    client_id: &'a str,
+    client_secret: &'a str,
+    grant_type: &'static str,
+    scope: &'static str,
+}
+
+#[derive(Debug, Deserialize)]
+struct Token {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Reward {
+    pub id: String,
+    pub name: String,
+}
+
+/// A poll donors vote in by donating to one of its options.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Poll {
+    pub id: String,
+    pub name: String,
+    pub active: bool,
+    pub options: Vec<PollOption>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PollOption {
+    pub id: String,
+    pub name: String,
+    pub amount_raised: Amount,
+}
+
+/// An incentive goal, such as a challenge the streamer takes on once `amount` is raised for it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Target {
+    pub id: String,
+    pub name: String,
+    pub active: bool,
+    pub amount: Amount,
+    pub amount_raised: Amount,
+}
+
+impl TiltifyClient {
+    pub fn new(config: SharedConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+            token: Arc::default(),
+            rewards: Arc::default(),
+            lists: Arc::default(),
+        }
+    }
+
+    /// Whether `[tiltify]` has the credentials lookups need.
+    pub async fn is_configured(&self) -> bool {
+        let config = self.config.read().await;
+        config.tiltify.client_id.is_some() && config.tiltify.client_secret.is_some()
+    }
+
+    async fn token(&self) -> Result<String, Report> {
+        let mut token = self.token.lock().await;
+        if let Some((access_token, expires_at)) = token.as_ref()
+            && Instant::now() + TOKEN_MARGIN < *expires_at
+        {
+            return Ok(access_token.clone());
+        }
+        let config = self.config.read().await.tiltify.clone();
+        let (Some(client_id), Some(client_secret)) = (&config.client_id, &config.client_secret)
+        else {
+            eyre::bail!("tiltify.client_id and tiltify.client_secret aren't set");
+        };
+        let requested_at = Instant::now();
+        let response: Token = self
+            .http
+            .post(format!("{}/oauth/token", config.api_url))
+            .json(&TokenRequest {
+                client_id,
+                client_secret,
+                grant_type: "client_credentials",
+                scope: "public",
+            })
+            .send()
+            .await
+            .wrap_err("couldn't reach Tiltify")?
+            .error_for_status()
+            .wrap_err("Tiltify didn't grant a token")?
+            .json()
+            .await
+            .wrap_err("couldn't read Tiltify's token")?;
+        let expires_at = requested_at + Duration::from_secs(response.expires_in);
+        *token = Some((response.access_token.clone(), expires_at));
+        Ok(response.access_token)
+    }
+
+    /// Fetches `path` of the public API and unwraps its `data`, `None` when Tiltify doesn't
+    /// know it.
+    async fn fetch<T: DeserializeOwned>(&self, path: &str) -> Result<Option<T>, Report> {
+        let token = self.token().await?;
+        let api_url = self.config.read().await.tiltify.api_url.clone();
+        let response = self
+            .http
+            .get(format!("{api_url}{path}"))
+            .bearer_auth(token)
+            .send()
+            .await
+            .wrap_err("couldn't reach Tiltify")?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let page: Page<T> = response
+            .error_for_status()
+            .wrap_err_with(|| format!("Tiltify rejected GET {path}"))?
+            .json()
+            .await
+            .wrap_err_with(|| format!("couldn't read Tiltify's response to GET {path}"))?;
+        Ok(Some(page.data))
+    }
+
+    /// The `resource` list of a campaign, or of the team campaign with that id when there is no
+    /// such campaign. A list fetched within `max_age` is reused.
+    pub async fn campaign_list<T: DeserializeOwned>(
+        &self,
+        id: &str,
+        resource: &str,
+        max_age: Duration,
+    ) -> Result<Vec<T>, Report> {
+        let key = format!("{id}/{resource}");
+        let cached = self.lists.lock().await.get(&key).cloned();
+        let list = match cached {
+            Some((fetched_at, list)) if fetched_at.elapsed() < max_age => list,
+            _ => {
+                let mut list = None;
+                for kind in ["campaigns", "team_campaigns"] {
+                    let path = format!("/api/public/{kind}/{id}/{resource}?limit=100");
+                    list = self.fetch::<serde_json::Value>(&path).await?;
+                    if list.is_some() {
+                        break;
+                    }
+                }
+                let list = list.unwrap_or_else(|| serde_json::Value::Array(Vec::new()));
+                self.lists
+                    .lock()
+                    .await
+                    .insert(key, (Instant::now(), list.clone()));
+                list
+            }
+        };
+        serde_json::from_value(list).wrap_err_with(|| format!("couldn't read Tiltify's {resource}"))
+    }
+
+    /// The `resource` lists of the campaigns with these ids, without duplicates, fetched at most
+    /// every `max_age`. Nothing is looked up without credentials.
+    async fn campaign_lists<T: DeserializeOwned>(
+        &self,
+        ids: &[&str],
+        resource: &str,
+        max_age: Duration,
+        id_of: fn(&T) -> &str,
+    ) -> Result<Vec<T>, Report> {
+        if !self.is_configured().await {
+            return Ok(Vec::new());
+        }
+        let mut items: Vec<T> = Vec::new();
+        for id in ids {
+            for item in self.campaign_list::<T>(id, resource, max_age).await? {
+                if !items.iter().any(|i| id_of(i) == id_of(&item)) {
+                    items.push(item);
+                }
+            }
+        }
+        Ok(items)
+    }
+
+    /// The polls of the campaigns with these ids.
+    pub async fn polls(&self, ids: &[&str], max_age: Duration) -> Result<Vec<Poll>, Report> {
+        self.campaign_lists(ids, "polls", max_age, |p: &Poll| &p.id)
+            .await
+    }
+
+    /// The targets of the campaigns with these ids.
+    pub async fn targets(&self, ids: &[&str], max_age: Duration) -> Result<Vec<Target>, Report> {
+        self.campaign_lists(ids, "targets", max_age, |t: &Target| &t.id)
+            .await
+    }
+
+    /// The name of a reward claimed with a donation, from its campaign's or team campaign's
+    /// rewards. `None` when Tiltify doesn't know it or `[tiltify]` has no credentials.
+    pub async fn reward_name(
+        &self,
+        donation: &TiltifyDonation,
+        reward_id: &str,
+    ) -> Result<Option<String>, Report> {
+        if let Some(name) = self.rewards.lock().await.get(reward_id) {
+            return Ok(Some(name.clone()));
+        }
+        if !self.is_configured().await {
+            return Ok(None);
+        }
+        let ids = [&donation.campaign_id, &donation.team_event_id];
+        for id in ids.into_iter().flatten() {
+            let rewards: Vec<Reward> = self.campaign_list(id, "rewards", Duration::ZERO).await?;
+            let mut names = self.rewards.lock().await;
+            names.extend(rewards.into_iter().map(|r| (r.id, r.name)));
+            if let Some(name) = names.get(reward_id) {
+                return Ok(Some(name.clone()));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, TiltifyConfig};
+    use crate::notify::stand_in::{Recorded, StandIn};
+    use axum::http::StatusCode;
+    use tokio::sync::RwLock;
+
+    fn client(api_url: String, credentials: bool) -> TiltifyClient {
+        let secret = |s: &str| Some(s.to_string()).filter(|_| credentials);
+        TiltifyClient::new(Arc::new(RwLock::new(Config {
+            tiltify: TiltifyConfig {
+                api_url,
+                client_id: secret("id"),
+                client_secret: secret("secret"),
+            },
+            ..Config::default()
+        })))
+    }
+
+    fn donation() -> TiltifyDonation {
+        TiltifyDonation {
+            campaign_id: Some("alice".to_string()),
+            reward_id: Some("song".to_string()),
+            team_event_id: Some("team".to_string()),
+            ..TiltifyDonation::test("1", "25.00")
+        }
+    }
+
+    fn tiltify(request: &Recorded) -> (StatusCode, String) {
+        match request.path.as_str() {
+            "/oauth/token" => (
+                StatusCode::OK,
+                r#"{"access_token":"token","expires_in":7200,"token_type":"bearer"}"#.to_string(),
+            ),
+            "/api/public/campaigns/alice/rewards" => (StatusCode::OK, r#"{"data":[]}"#.to_string()),
+            "/api/public/team_campaigns/team/rewards" => (
+                StatusCode::OK,
+                r#"{"data":[{"id":"song","name":"Sing a song","amount":{"value":"10.00"}}]}"#
+                    .to_string(),
+            ),
+            _ => (StatusCode::NOT_FOUND, "{}".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn resolves_reward_names_once() {
+        let stand_in = StandIn::start(tiltify).await;
+        let client = client(stand_in.url(""), true);
+
+        for _ in 0..2 {
+            let name = client.reward_name(&donation(), "song").await.unwrap();
+            assert_eq!(name.as_deref(), Some("Sing a song"));
+        }
+        assert_eq!(client.reward_name(&donation(), "nope").await.unwrap(), None);
+
+        let requests = stand_in.requests().await;
+        let paths: Vec<&str> = requests.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "/oauth/token",
+                "/api/public/campaigns/alice/rewards",
+                "/api/public/campaigns/team/rewards",
+                "/api/public/team_campaigns/team/rewards",
+                "/api/public/campaigns/alice/rewards",
+                "/api/public/campaigns/team/rewards",
+                "/api/public/team_campaigns/team/rewards",
+            ]
+        );
+        assert_eq!(requests[0].json()["grant_type"], "client_credentials");
+        assert_eq!(requests[1].header("authorization"), Some("Bearer token"));
+    }
+
+    #[tokio::test]
+    async fn reuses_polls_while_they_are_fresh() {
+        let stand_in = StandIn::start(|request: &Recorded| match request.path.as_str() {
+            "/oauth/token" => tiltify(request),
+            "/api/public/team_campaigns/team/polls" => (
+                StatusCode::OK,
+                r#"{"data":[{"id":"game","name":"Next game","active":true,"options":[
+                    {"id":"doom","name":"Doom","amount_raised":{"currency":"USD","value":"5.00"}}
+                ]}]}"#
+                    .to_string(),
+            ),
+            _ => (StatusCode::NOT_FOUND, "{}".to_string()),
+        })
+        .await;
+        let client = client(stand_in.url(""), true);
+
+        for max_age in [
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+            Duration::ZERO,
+        ] {
+            let polls = client.polls(&["team"], max_age).await.unwrap();
+            assert_eq!(polls.len(), 1);
+            assert_eq!(polls[0].options[0].name, "Doom");
+        }
+
+        let polls_fetched = stand_in
+            .requests()
+            .await
+            .iter()
+            .filter(|r| r.path.ends_with("/polls"))
+            .count();
+        assert_eq!(polls_fetched, 4);
+    }
+
+    #[tokio::test]
+    async fn looks_nothing_up_without_credentials() {
+        let stand_in = StandIn::start(tiltify).await;
+        let client = client(stand_in.url(""), false);
+
+        assert_eq!(client.reward_name(&donation(), "song").await.unwrap(), None);
+        assert!(stand_in.requests().await.is_empty());
+    }
+}
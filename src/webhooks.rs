@@ -0,0 +1,476 @@
+use crate::bot::{donors, template};
+use crate::config::{DonorsConfig, SharedConfig, WebhookConfig, WebhookEvent};
+use crate::db::Store;
+use crate::db::models::{NewWebhookAttempt, NewWebhookDelivery, WebhookDelivery};
+use crate::notify::webhook::{EVENT_HEADER, SIGNATURE_HEADER, sign};
+use crate::{Commands, metrics};
+use reqwest::StatusCode;
+use reqwest::header::CONTENT_TYPE;
+use serde_json::{Value, json};
+use std::time::Duration;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+
+/// Header carrying the delivery id, so receivers can recognise re-sends.
+pub const DELIVERY_HEADER: &str = "X-Warbot-Delivery";
+
+/// Delay before the first retry, doubled for every further one.
+const BACKOFF: Duration = Duration::from_secs(2);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// A bot event in the shape webhooks consume.
+struct Event {
+    kind: WebhookEvent,
+    /// Identifies the event across retries by its source, e.g. a Tiltify donation id.
+    key: Option<String>,
+    vars: Vec<(&'static str, String)>,
+    data: Value,
+}
+
+impl Event {
+    /// Donations are only sent once the bot announced them, under the name they were announced
+    /// with, so held donations wait for a moderator and donors shown as anonymous stay so.
+    fn from_command(command: &Commands, donors: &DonorsConfig) -> Option<Self> {
+        let event = match command {
+            Commands::DonationAnnounced(donation) => {
+                let name = donors::display_name(donors, donation);
+                Event {
+                    kind: WebhookEvent::DonationReceived,
+                    key: Some(donation.id.clone()),
+                    vars: vec![
+                        ("id", donation.id.clone()),
+                        ("campaign_id", donation.campaign_id.clone().unwrap_or_default()),
+                        ("amount", donation.amount.value.clone()),
+                        ("currency", donation.amount.currency.clone()),
+                        ("name", name.clone()),
+                    ],
+                    data: json!({
+                        "id": donation.id,
+                        "campaign_id": donation.campaign_id,
+                        "amount": donation.amount,
+                        "name": name,
+                    }),
+                }
+            }
+            Commands::Announcement(message) => Event {
+                kind: WebhookEvent::Announcement,
+                key: None,
+                vars: vec![("message", message.clone())],
+                data: json!({ "message": message }),
+            },
+            Commands::RaidInitiated { from, to, viewers } => Event {
+                kind: WebhookEvent::RaidInitiated,
+                key: None,
+                vars: vec![
+                    ("from", from.clone()),
+                    ("to", to.clone()),
+                    ("viewers", viewers.to_string()),
+                ],
+                data: json!({ "from": from, "to": to, "viewers": viewers }),
+            },
+            Commands::StreamStarted(channel) | Commands::StreamEnded(channel) => Event {
+                kind: if matches!(command, Commands::StreamStarted(_)) {
+                    WebhookEvent::StreamStarted
+                } else {
+                    WebhookEvent::StreamEnded
+                },
+                key: None,
+                vars: vec![("channel", channel.clone())],
+                data: json!({ "channel": channel }),
+            },
+            Commands::ChannelsChanged => Event {
+                kind: WebhookEvent::ChannelsChanged,
+                key: None,
+                vars: Vec::new(),
+                data: json!({}),
+            },
+            Commands::Shutdown
+            | Commands::Resumed
+            | Commands::DonationReceived(_)
+            | Commands::DonationReleased(_)
+            | Commands::CommentReleased(_) => return None,
+        };
+        Some(event)
+    }
+
+    fn payload(&self, webhook: &WebhookConfig) -> Value {
+        match &webhook.payload {
+            Some(template) => render_payload(template, &self.vars),
+            None => json!({ "event": self.kind.as_str(), "data": self.data }),
+        }
+    }
+}
+
+/// Fills placeholders in every string of a JSON payload template.
+fn render_payload(template: &Value, vars: &[(&str, String)]) -> Value {
+    match template {
+        Value::String(s) => {
+            let vars: Vec<(&str, &str)> = vars.iter().map(|(k, v)| (*k, v.as_str())).collect();
+            Value::String(template::render(s, &vars))
+        }
+        Value::Array(values) => values.iter().map(|v| render_payload(v, vars)).collect(),
+        Value::Object(map) => map
+            .iter()
+            .map(|(k, v)| (k.clone(), render_payload(v, vars)))
+            .collect(),
+        other => other.clone(),
+    }
+}
+
+/// Posts bot events to the `[[webhooks]]` that subscribed to them, recording every attempt.
+#[derive(Clone)]
+pub struct Webhooks {
+    config: SharedConfig,
+    store: Store,
+    http: reqwest::Client,
+    backoff: Duration,
+}
+
+impl Webhooks {
+    pub fn new(config: SharedConfig, store: Store) -> Self {
+        Self {
+            config,
+            store,
+            http: reqwest::Client::new(),
+            backoff: BACKOFF,
+        }
+    }
+
+    /// Delivers events from the command bus until it closes, after resuming deliveries that were
+    /// still pending when the bot last stopped.
+    pub async fn run(self, mut rx: Receiver<Commands>) {
+        for (delivery, webhook) in self.pending().await {
+            info!("Resuming delivery {} to webhook {}", delivery.id, webhook.name);
+            self.spawn(delivery, webhook);
+        }
+        loop {
+            match rx.recv().await {
+                Ok(command) => {
+                    for (delivery, webhook) in self.enqueue(&command).await {
+                        self.spawn(delivery, webhook);
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Webhooks lagged behind, {skipped} events were not delivered");
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+
+    /// Stored deliveries that haven't been sent or given up on. Those of webhooks that are no
+    /// longer configured are marked failed.
+    async fn pending(&self) -> Vec<(WebhookDelivery, WebhookConfig)> {
+        let deliveries = match self.store.pending_webhook_deliveries().await {
+            Ok(deliveries) => deliveries,
+            Err(e) => {
+                error!("Error reading pending webhook deliveries: {e:?}");
+                return Vec::new();
+            }
+        };
+        let webhooks = self.config.read().await.webhooks.clone();
+        let mut pending = Vec::new();
+        for delivery in deliveries {
+            match webhooks.iter().find(|w| w.name == delivery.webhook) {
+                Some(webhook) => pending.push((delivery, webhook.clone())),
+                None => {
+                    warn!(
+                        "Webhook {} of pending delivery {} is no longer configured",
+                        delivery.webhook, delivery.id
+                    );
+                    if let Err(e) = self
+                        .store
+                        .set_webhook_delivery_status(delivery.id, "failed")
+                        .await
+                    {
+                        error!("Error updating webhook delivery {}: {e:?}", delivery.id);
+                    }
+                }
+            }
+        }
+        pending
+    }
+
+    /// Stores a pending delivery for every webhook subscribed to the command's event.
+    async fn enqueue(&self, command: &Commands) -> Vec<(WebhookDelivery, WebhookConfig)> {
+        let (webhooks, donors) = {
+            let config = self.config.read().await;
+            (config.webhooks.clone(), config.donors.clone())
+        };
+        let Some(event) = Event::from_command(command, &donors) else {
+            return Vec::new();
+        };
+        let mut deliveries = Vec::new();
+        for webhook in webhooks.into_iter().filter(|w| w.events.contains(&event.kind)) {
+            let payload = event.payload(&webhook).to_string();
+            let delivery = NewWebhookDelivery {
+                webhook: &webhook.name,
+                event: event.kind.as_str(),
+                event_key: event.key.as_deref(),
+                url: &webhook.url,
+                payload: &payload,
+                status: "pending",
+                created_at: chrono::Utc::now().naive_utc(),
+            };
+            match self.store.create_webhook_delivery(delivery).await {
+                Ok(Some(delivery)) => deliveries.push((delivery, webhook)),
+                Ok(None) => info!(
+                    "{} {:?} was already delivered to webhook {}, skipping",
+                    event.kind.as_str(),
+                    event.key,
+                    webhook.name
+                ),
+                Err(e) => error!("Error storing delivery for webhook {}: {e:?}", webhook.name),
+            }
+        }
+        deliveries
+    }
+
+    /// Sends a stored delivery again, with a fresh set of attempts.
+    pub async fn resend(&self, delivery: WebhookDelivery, webhook: WebhookConfig) {
+        if let Err(e) = self.store.set_webhook_delivery_status(delivery.id, "pending").await {
+            error!("Error updating webhook delivery {}: {e:?}", delivery.id);
+        }
+        self.spawn(delivery, webhook);
+    }
+
+    fn spawn(&self, delivery: WebhookDelivery, webhook: WebhookConfig) {
+        let webhooks = self.clone();
+        tokio::spawn(async move { webhooks.deliver(&delivery, &webhook).await });
+    }
+
+    /// The `campaign` label of a delivery, by the donation it is keyed by.
+    async fn campaign_label(&self, delivery: &WebhookDelivery) -> String {
+        let donation = match &delivery.event_key {
+            Some(key) => self.store.donation(key).await.ok().flatten(),
+            None => None,
+        };
+        let Some(donation) = donation else {
+            return metrics::NO_CAMPAIGN.to_string();
+        };
+        let config = self.config.read().await;
+        metrics::donation_campaign_label(
+            &config,
+            donation.campaign_id.as_deref(),
+            donation.team_event_id.as_deref(),
+        )
+        .to_string()
+    }
+
+    /// Posts a delivery until it succeeds, fails permanently or runs out of attempts.
+    async fn deliver(&self, delivery: &WebhookDelivery, webhook: &WebhookConfig) -> bool {
+        let campaign = self.campaign_label(delivery).await;
+        let mut backoff = self.backoff;
+        let mut sent = false;
+        for attempt in 1..=webhook.max_attempts {
+            let result = self
+                .http
+                .post(&delivery.url)
+                .header(CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, sign(&webhook.secret, delivery.payload.as_bytes()))
+                .header(EVENT_HEADER, &delivery.event)
+                .header(DELIVERY_HEADER, delivery.id.to_string())
+                .body(delivery.payload.clone())
+                .send()
+                .await;
+            let (status_code, error, retry) = match result {
+                Ok(res) if res.status().is_success() => (Some(res.status()), None, false),
+                Ok(res) => {
+                    let status = res.status();
+                    let retry = status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS;
+                    (Some(status), Some(format!("responded with {status}")), retry)
+                }
+                Err(e) => (None, Some(e.to_string()), true),
+            };
+            sent = error.is_none();
+            metrics::WEBHOOK_ATTEMPTS
+                .with_label_values(&[
+                    campaign.as_str(),
+                    webhook.name.as_str(),
+                    if sent { "success" } else { "failure" },
+                ])
+                .inc();
+            if let Some(error) = &error {
+                warn!(
+                    "Webhook {} delivery {} attempt {attempt}/{} failed: {error}",
+                    webhook.name, delivery.id, webhook.max_attempts
+                );
+            }
+            let attempt_record = NewWebhookAttempt {
+                delivery_id: delivery.id,
+                status_code: status_code.map(|s| s.as_u16() as i32),
+                error,
+                attempted_at: chrono::Utc::now().naive_utc(),
+            };
+            if let Err(e) = self.store.record_webhook_attempt(attempt_record).await {
+                error!("Error recording webhook attempt for {}: {e:?}", delivery.id);
+            }
+            if sent || !retry || attempt == webhook.max_attempts {
+                break;
+            }
+            sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+
+        let status = if sent { "sent" } else { "failed" };
+        if let Err(e) = self.store.set_webhook_delivery_status(delivery.id, status).await {
+            error!("Error updating webhook delivery {}: {e:?}", delivery.id);
+        }
+        sent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::notify::stand_in::StandIn;
+    use crate::routes::tiltify::TiltifyDonation;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::RwLock;
+
+    fn donation() -> Commands {
+        Commands::DonationAnnounced(TiltifyDonation {
+            campaign_id: Some("campaign".to_string()),
+            name: Some("Carol".to_string()),
+            ..TiltifyDonation::test("donation-1", "25.00")
+        })
+    }
+
+    fn webhooks(url: String, payload: Option<Value>) -> Webhooks {
+        let config = Config {
+            webhooks: vec![WebhookConfig {
+                name: "lights".to_string(),
+                url,
+                secret: "secret".to_string(),
+                events: vec![WebhookEvent::DonationReceived],
+                payload,
+                max_attempts: 3,
+            }],
+            ..Default::default()
+        };
+        Webhooks {
+            config: Arc::new(RwLock::new(config)),
+            store: Store::open(":memory:").unwrap(),
+            http: reqwest::Client::new(),
+            backoff: Duration::from_millis(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors_until_delivered() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let stand_in = StandIn::start(move |_| {
+            match counter.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => (StatusCode::SERVICE_UNAVAILABLE, String::new()),
+                _ => (StatusCode::OK, String::new()),
+            }
+        })
+        .await;
+        let webhooks = webhooks(stand_in.url("/hook"), None);
+
+        let deliveries = webhooks.enqueue(&donation()).await;
+        assert_eq!(deliveries.len(), 1);
+        let (delivery, webhook) = &deliveries[0];
+        assert!(webhooks.deliver(delivery, webhook).await);
+
+        let requests = stand_in.requests().await;
+        assert_eq!(requests.len(), 3);
+        let body = &requests[2].body;
+        assert_eq!(
+            requests[2].header(SIGNATURE_HEADER),
+            Some(sign("secret", body).as_str())
+        );
+        assert_eq!(requests[2].header(DELIVERY_HEADER), Some(delivery.id.to_string().as_str()));
+        assert_eq!(requests[2].json()["event"], "donation_received");
+        assert_eq!(requests[2].json()["data"]["amount"]["value"], "25.00");
+
+        let stored = webhooks.store.recent_webhook_deliveries(1, None).await.unwrap();
+        assert_eq!(stored[0].delivery.status, "sent");
+        let codes: Vec<_> = stored[0].attempts.iter().map(|a| a.status_code).collect();
+        assert_eq!(codes, [Some(503), Some(503), Some(200)]);
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_client_errors() {
+        let stand_in = StandIn::start(|_| (StatusCode::NOT_FOUND, String::new())).await;
+        let webhooks = webhooks(stand_in.url("/hook"), None);
+
+        let (delivery, webhook) = webhooks.enqueue(&donation()).await.remove(0);
+        assert!(!webhooks.deliver(&delivery, &webhook).await);
+
+        assert_eq!(stand_in.requests().await.len(), 1);
+        let stored = webhooks.store.recent_webhook_deliveries(1, None).await.unwrap();
+        assert_eq!(stored[0].delivery.status, "failed");
+        assert_eq!(stored[0].attempts.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn enqueues_each_donation_once() {
+        let webhooks = webhooks("http://127.0.0.1:9/".to_string(), None);
+
+        assert_eq!(webhooks.enqueue(&donation()).await.len(), 1);
+        assert!(webhooks.enqueue(&donation()).await.is_empty());
+        assert!(webhooks.enqueue(&Commands::ChannelsChanged).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn waits_for_donations_to_be_announced() {
+        let webhooks = webhooks("http://127.0.0.1:9/".to_string(), None);
+        let Commands::DonationAnnounced(donation) = donation() else {
+            unreachable!()
+        };
+
+        let received = Commands::DonationReceived(donation.clone());
+        assert!(webhooks.enqueue(&received).await.is_empty());
+        let released = Commands::DonationReleased(donation.clone());
+        assert!(webhooks.enqueue(&released).await.is_empty());
+
+        webhooks.config.write().await.donors.anonymous_below = Some(100.0);
+        let (delivery, _) = webhooks.enqueue(&Commands::DonationAnnounced(donation)).await.remove(0);
+        let payload: Value = serde_json::from_str(&delivery.payload).unwrap();
+        assert_eq!(payload["data"]["name"], "an anonymous user");
+    }
+
+    #[tokio::test]
+    async fn resumes_pending_deliveries() {
+        let webhooks = webhooks("http://127.0.0.1:9/".to_string(), None);
+        let (delivery, _) = webhooks.enqueue(&donation()).await.remove(0);
+
+        let pending = webhooks.pending().await;
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].0.id, delivery.id);
+
+        webhooks.config.write().await.webhooks.clear();
+        assert!(webhooks.pending().await.is_empty());
+        let stored = webhooks.store.recent_webhook_deliveries(1, None).await.unwrap();
+        assert_eq!(stored[0].delivery.status, "failed");
+    }
+
+    #[tokio::test]
+    async fn renders_payload_template() {
+        let template = json!({
+            "entity_id": "light.stream",
+            "flash": "long",
+            "message": "{name} donated {amount} {currency}",
+            "tags": ["{id}", 3]
+        });
+        let webhooks = webhooks("http://127.0.0.1:9/".to_string(), Some(template));
+
+        let (delivery, _) = webhooks.enqueue(&donation()).await.remove(0);
+        let payload: Value = serde_json::from_str(&delivery.payload).unwrap();
+        assert_eq!(
+            payload,
+            json!({
+                "entity_id": "light.stream",
+                "flash": "long",
+                "message": "Carol donated 25.00 USD",
+                "tags": ["donation-1", 3]
+            })
+        );
+    }
+}